@@ -169,26 +169,35 @@ fn main() {
 
     gl::load_with(|symbol| gl_window.get_proc_address(symbol) as *const _);
 
-    let mut renderer =  GlCanvasRenderer::new();
+    let mut renderer =  GlCanvasRenderer::new(GlBackend::Gl3).unwrap();
     let mut canvas = Canvas::new();
 
     let mut running = true;
     let mut t = 0.0;
+    let mut debug_mode = DebugMode::None;
     while running {
         events_loop.poll_events(|event| {
-            match event {
-                Event::WindowEvent{ event, .. } => match event {
+            if let Event::WindowEvent{ event, .. } = event {
+                match event {
                     WindowEvent::CloseRequested => running = false,
-                    WindowEvent::KeyboardInput { input, .. } => if input.virtual_keycode == Some(VirtualKeyCode::Q) && input.modifiers.logo {
+                    WindowEvent::KeyboardInput { input, .. } if input.virtual_keycode == Some(VirtualKeyCode::Q) && input.modifiers.logo => {
                         running = false
                     },
+                    WindowEvent::KeyboardInput { input, .. } if input.virtual_keycode == Some(VirtualKeyCode::D) && input.state == ElementState::Pressed => {
+                        debug_mode = match debug_mode {
+                            DebugMode::None => DebugMode::Wireframe,
+                            DebugMode::Wireframe => DebugMode::Fringes,
+                            DebugMode::Fringes => DebugMode::StencilOverdraw,
+                            DebugMode::StencilOverdraw => DebugMode::BoundingBoxes,
+                            DebugMode::BoundingBoxes => DebugMode::None,
+                        };
+                    },
                     WindowEvent::Resized(logical_size) => {
                         let dpi_factor = gl_window.get_hidpi_factor();
                         gl_window.resize(logical_size.to_physical(dpi_factor));
                     },
                     _ => ()
-                },
-                _ => ()
+                }
             }
         });
 
@@ -196,6 +205,7 @@ fn main() {
         let logical_size = gl_window.get_inner_size().unwrap();
         let physical_size = logical_size.to_physical(dpi_factor);
         renderer.set_viewport_size(physical_size.width as f32, physical_size.height as f32, dpi_factor as f32);
+        renderer.set_debug_mode(debug_mode);
         canvas.set_pixels_per_point(dpi_factor as f32);
         render_demo(&mut renderer, &mut canvas, logical_size.width as f32, logical_size.height as f32, t);
 
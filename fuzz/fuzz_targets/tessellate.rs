@@ -0,0 +1,92 @@
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use rdraw::*;
+
+#[derive(Arbitrary, Debug)]
+enum Op {
+    MoveTo(i16, i16),
+    LineTo(i16, i16),
+    BezierTo(i16, i16, i16, i16, i16, i16),
+    ClosePath,
+    PathWinding(bool),
+    Rect(i16, i16, i16, i16),
+    Circle(i16, i16, i16),
+}
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    ops: Vec<Op>,
+    line_width: u8,
+    line_cap: u8,
+    line_join: u8,
+    anti_alias: bool,
+}
+
+/// Coordinates on a 1/16 grid, so that points often coincide.
+fn coord(value: i16) -> f32 {
+    value as f32 / 16.0
+}
+
+struct CheckRenderer;
+
+impl CheckRenderer {
+    fn check(&self, paths: Paths) {
+        for path in paths.iter() {
+            for vert in path.stroke().into_iter().chain(path.fill()).flatten() {
+                assert!(vert.x.is_finite() && vert.y.is_finite(), "non-finite vertex");
+                assert!(vert.u.is_finite() && vert.v.is_finite(), "non-finite texture coordinate");
+            }
+        }
+    }
+}
+
+impl CanvasRenderer for CheckRenderer {
+    fn stroke(&mut self, _paint: &Paint, _composite: &CompositeState, _scissor: &Scissor, _clip_depth: usize, _fringe: f32, _line_width: f32, paths: Paths) {
+        self.check(paths);
+    }
+
+    fn fill(&mut self, _paint: &Paint, _composite: &CompositeState, _scissor: &Scissor, _clip_depth: usize, _fringe: f32, bounds: Rect, paths: Paths) {
+        assert!(!bounds.origin.x.is_nan() && !bounds.origin.y.is_nan(), "NaN bounds");
+        assert!(!bounds.size.width.is_nan() && !bounds.size.height.is_nan(), "NaN bounds");
+        self.check(paths);
+    }
+
+    fn clip(&mut self, _depth: usize, paths: Paths) {
+        self.check(paths);
+    }
+
+    fn push_layer(&mut self, _opacity: f32, _composite: &CompositeState, _clip_depth: usize) {}
+
+    fn pop_layer(&mut self) {}
+}
+
+fuzz_target!(|input: Input| {
+    let mut canvas = Canvas::new();
+    let mut renderer = CheckRenderer;
+
+    canvas.set_stroke_width(input.line_width as f32 * 0.25);
+    canvas.set_line_cap([LineCap::Butt, LineCap::Round, LineCap::Square][input.line_cap as usize % 3]);
+    canvas.set_line_join([LineJoin::Round, LineJoin::Bevel, LineJoin::Miter][input.line_join as usize % 3]);
+    canvas.set_shape_anti_alias(input.anti_alias);
+
+    canvas.begin_path();
+    for op in input.ops.iter() {
+        match *op {
+            Op::MoveTo(x, y) => { canvas.move_to(coord(x), coord(y)); }
+            Op::LineTo(x, y) => { canvas.line_to(coord(x), coord(y)); }
+            Op::BezierTo(cp1x, cp1y, cp2x, cp2y, x, y) => {
+                canvas.bezier_to(coord(cp1x), coord(cp1y), coord(cp2x), coord(cp2y), coord(x), coord(y));
+            }
+            Op::ClosePath => { canvas.close_path(); }
+            Op::PathWinding(cw) => { canvas.path_winding(if cw { Winding::CW } else { Winding::CCW }); }
+            Op::Rect(x, y, w, h) => { canvas.rect(coord(x), coord(y), coord(w), coord(h)); }
+            Op::Circle(cx, cy, r) => { canvas.circle(coord(cx), coord(cy), coord(r)); }
+        }
+    }
+
+    canvas.stroke(&mut renderer);
+    canvas.fill(&mut renderer);
+    canvas.clip(&mut renderer);
+});
@@ -0,0 +1,440 @@
+use std::{
+    error::Error,
+    fmt,
+    str::FromStr,
+};
+
+use crate::{clamp, Scalar};
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Color {
+    pub fn rgb(r: u8, g: u8, b: u8) -> Color {
+        Color { r, g, b, a: 255 }
+    }
+
+    pub fn rgba(r: u8, g: u8, b: u8, a: u8) -> Color {
+        Color { r, g, b, a }
+    }
+
+    /// Creates a color from components in the range [0, 1].
+    pub fn rgbaf(r: Scalar, g: Scalar, b: Scalar, a: Scalar) -> Color {
+        Color {
+            r: unit_to_u8(r),
+            g: unit_to_u8(g),
+            b: unit_to_u8(b),
+            a: unit_to_u8(a),
+        }
+    }
+
+    /// Creates an opaque color from hue, saturation and lightness, all in the range [0, 1].
+    pub fn hsl(h: Scalar, s: Scalar, l: Scalar) -> Color {
+        Color::hsla(h, s, l, 255)
+    }
+
+    pub fn hsla(h: Scalar, s: Scalar, l: Scalar, a: u8) -> Color {
+        let h = h.rem_euclid(1.0);
+        let s = clamp(s, 0.0, 1.0);
+        let l = clamp(l, 0.0, 1.0);
+        let m2 = if l <= 0.5 { l * (1.0 + s) } else { l + s - l * s };
+        let m1 = 2.0 * l - m2;
+        Color {
+            r: unit_to_u8(hue(h + 1.0 / 3.0, m1, m2)),
+            g: unit_to_u8(hue(h, m1, m2)),
+            b: unit_to_u8(hue(h - 1.0 / 3.0, m1, m2)),
+            a,
+        }
+    }
+
+    /// Creates an opaque color from hue, saturation and value, all in the range [0, 1].
+    pub fn hsv(h: Scalar, s: Scalar, v: Scalar) -> Color {
+        let h = h.rem_euclid(1.0) * 6.0;
+        let s = clamp(s, 0.0, 1.0);
+        let v = clamp(v, 0.0, 1.0);
+        let c = v * s;
+        let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+        let (r, g, b) = match h as u32 {
+            0 => (c, x, 0.0),
+            1 => (x, c, 0.0),
+            2 => (0.0, c, x),
+            3 => (0.0, x, c),
+            4 => (x, 0.0, c),
+            _ => (c, 0.0, x),
+        };
+        let m = v - c;
+        Color::rgbaf(r + m, g + m, b + m, 1.0)
+    }
+
+    /// Parses `#rgb`, `#rgba`, `#rrggbb` or `#rrggbbaa`.
+    pub fn from_hex(hex: &str) -> Result<Color, ParseColorError> {
+        let digits = hex.strip_prefix('#').ok_or(ParseColorError::MissingHash)?;
+        if !digits.bytes().all(|c| c.is_ascii_hexdigit()) {
+            return Err(ParseColorError::InvalidDigit);
+        }
+
+        let value = |i: usize, len: usize| u8::from_str_radix(&digits[i * len..(i + 1) * len], 16).unwrap();
+        let short = |i: usize| value(i, 1) * 0x11;
+        match digits.len() {
+            3 => Ok(Color::rgb(short(0), short(1), short(2))),
+            4 => Ok(Color::rgba(short(0), short(1), short(2), short(3))),
+            6 => Ok(Color::rgb(value(0, 2), value(1, 2), value(2, 2))),
+            8 => Ok(Color::rgba(value(0, 2), value(1, 2), value(2, 2), value(3, 2))),
+            _ => Err(ParseColorError::InvalidLength),
+        }
+    }
+
+    /// Looks up a CSS named color, ignoring case.
+    pub fn from_name(name: &str) -> Option<Color> {
+        let name = name.to_ascii_lowercase();
+        if name == "transparent" {
+            return Some(Color::rgba(0, 0, 0, 0));
+        }
+        NAMED_COLORS.iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, color)| *color)
+    }
+
+    /// Linearly interpolates from `self` at `t = 0` to `other` at `t = 1`.
+    pub fn lerp(self, other: Color, t: Scalar) -> Color {
+        let t = clamp(t, 0.0, 1.0);
+        let mix = |a: u8, b: u8| (a as Scalar + (b as Scalar - a as Scalar) * t).round() as u8;
+        Color {
+            r: mix(self.r, other.r),
+            g: mix(self.g, other.g),
+            b: mix(self.b, other.b),
+            a: mix(self.a, other.a),
+        }
+    }
+
+    pub fn with_alpha(self, a: u8) -> Color {
+        Color { a, ..self }
+    }
+
+    /// Returns the color with its color components multiplied by alpha.
+    pub fn premultiplied(self) -> Color {
+        let mul = |c: u8| ((c as u32 * self.a as u32 + 127) / 255) as u8;
+        Color {
+            r: mul(self.r),
+            g: mul(self.g),
+            b: mul(self.b),
+            a: self.a,
+        }
+    }
+}
+
+impl From<Color> for [f32; 4] {
+    fn from(color: Color) -> Self {
+        [
+            color.r as f32 / 255.0,
+            color.g as f32 / 255.0,
+            color.b as f32 / 255.0,
+            color.a as f32 / 255.0,
+        ]
+    }
+}
+
+impl From<[f32; 4]> for Color {
+    fn from(color: [f32; 4]) -> Self {
+        Color::rgbaf(color[0], color[1], color[2], color[3])
+    }
+}
+
+impl FromStr for Color {
+    type Err = ParseColorError;
+
+    /// Parses a hex color or a CSS named color.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.starts_with('#') {
+            Color::from_hex(s)
+        } else {
+            Color::from_name(s).ok_or(ParseColorError::UnknownName)
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ParseColorError {
+    MissingHash,
+    InvalidDigit,
+    InvalidLength,
+    UnknownName,
+}
+
+impl fmt::Display for ParseColorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            ParseColorError::MissingHash => "hex color must start with '#'",
+            ParseColorError::InvalidDigit => "hex color contains a non hexadecimal digit",
+            ParseColorError::InvalidLength => "hex color must have 3, 4, 6 or 8 digits",
+            ParseColorError::UnknownName => "unknown color name",
+        };
+        f.write_str(message)
+    }
+}
+
+impl Error for ParseColorError {}
+
+#[inline(always)]
+fn unit_to_u8(a: Scalar) -> u8 {
+    (clamp(a, 0.0, 1.0) * 255.0).round() as u8
+}
+
+fn hue(mut h: Scalar, m1: Scalar, m2: Scalar) -> Scalar {
+    if h < 0.0 {
+        h += 1.0;
+    }
+    if h > 1.0 {
+        h -= 1.0;
+    }
+    if h < 1.0 / 6.0 {
+        m1 + (m2 - m1) * h * 6.0
+    } else if h < 3.0 / 6.0 {
+        m2
+    } else if h < 4.0 / 6.0 {
+        m1 + (m2 - m1) * (2.0 / 3.0 - h) * 6.0
+    } else {
+        m1
+    }
+}
+
+/// Converts an sRGB encoded component to linear light.
+pub(crate) fn srgb_to_linear(c: Scalar) -> Scalar {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub(crate) fn linear_to_srgb(c: Scalar) -> Scalar {
+    if c <= 0.003_130_8 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+pub(crate) fn linear_srgb_to_oklab(c: [Scalar; 3]) -> [Scalar; 3] {
+    let l = (0.412_221_46 * c[0] + 0.536_332_55 * c[1] + 0.051_445_995 * c[2]).cbrt();
+    let m = (0.211_903_5 * c[0] + 0.680_699_5 * c[1] + 0.107_396_96 * c[2]).cbrt();
+    let s = (0.088_302_46 * c[0] + 0.281_718_85 * c[1] + 0.629_978_7 * c[2]).cbrt();
+    [
+        0.210_454_26 * l + 0.793_617_8 * m - 0.004_072_047 * s,
+        1.977_998_5 * l - 2.428_592_2 * m + 0.450_593_7 * s,
+        0.025_904_037 * l + 0.782_771_77 * m - 0.808_675_77 * s,
+    ]
+}
+
+pub(crate) fn oklab_to_linear_srgb(c: [Scalar; 3]) -> [Scalar; 3] {
+    let l = (c[0] + 0.396_337_78 * c[1] + 0.215_803_76 * c[2]).powi(3);
+    let m = (c[0] - 0.105_561_346 * c[1] - 0.063_854_17 * c[2]).powi(3);
+    let s = (c[0] - 0.089_484_18 * c[1] - 1.291_485_5 * c[2]).powi(3);
+    [
+        4.076_741_7 * l - 3.307_711_6 * m + 0.230_969_94 * s,
+        -1.268_438 * l + 2.609_757_4 * m - 0.341_319_38 * s,
+        -0.004_196_086_3 * l - 0.703_418_6 * m + 1.707_614_7 * s,
+    ]
+}
+
+static NAMED_COLORS: &[(&str, Color)] = &[
+    ("aliceblue", Color { r: 0xf0, g: 0xf8, b: 0xff, a: 0xff }),
+    ("antiquewhite", Color { r: 0xfa, g: 0xeb, b: 0xd7, a: 0xff }),
+    ("aqua", Color { r: 0x00, g: 0xff, b: 0xff, a: 0xff }),
+    ("aquamarine", Color { r: 0x7f, g: 0xff, b: 0xd4, a: 0xff }),
+    ("azure", Color { r: 0xf0, g: 0xff, b: 0xff, a: 0xff }),
+    ("beige", Color { r: 0xf5, g: 0xf5, b: 0xdc, a: 0xff }),
+    ("bisque", Color { r: 0xff, g: 0xe4, b: 0xc4, a: 0xff }),
+    ("black", Color { r: 0x00, g: 0x00, b: 0x00, a: 0xff }),
+    ("blanchedalmond", Color { r: 0xff, g: 0xeb, b: 0xcd, a: 0xff }),
+    ("blue", Color { r: 0x00, g: 0x00, b: 0xff, a: 0xff }),
+    ("blueviolet", Color { r: 0x8a, g: 0x2b, b: 0xe2, a: 0xff }),
+    ("brown", Color { r: 0xa5, g: 0x2a, b: 0x2a, a: 0xff }),
+    ("burlywood", Color { r: 0xde, g: 0xb8, b: 0x87, a: 0xff }),
+    ("cadetblue", Color { r: 0x5f, g: 0x9e, b: 0xa0, a: 0xff }),
+    ("chartreuse", Color { r: 0x7f, g: 0xff, b: 0x00, a: 0xff }),
+    ("chocolate", Color { r: 0xd2, g: 0x69, b: 0x1e, a: 0xff }),
+    ("coral", Color { r: 0xff, g: 0x7f, b: 0x50, a: 0xff }),
+    ("cornflowerblue", Color { r: 0x64, g: 0x95, b: 0xed, a: 0xff }),
+    ("cornsilk", Color { r: 0xff, g: 0xf8, b: 0xdc, a: 0xff }),
+    ("crimson", Color { r: 0xdc, g: 0x14, b: 0x3c, a: 0xff }),
+    ("cyan", Color { r: 0x00, g: 0xff, b: 0xff, a: 0xff }),
+    ("darkblue", Color { r: 0x00, g: 0x00, b: 0x8b, a: 0xff }),
+    ("darkcyan", Color { r: 0x00, g: 0x8b, b: 0x8b, a: 0xff }),
+    ("darkgoldenrod", Color { r: 0xb8, g: 0x86, b: 0x0b, a: 0xff }),
+    ("darkgray", Color { r: 0xa9, g: 0xa9, b: 0xa9, a: 0xff }),
+    ("darkgreen", Color { r: 0x00, g: 0x64, b: 0x00, a: 0xff }),
+    ("darkgrey", Color { r: 0xa9, g: 0xa9, b: 0xa9, a: 0xff }),
+    ("darkkhaki", Color { r: 0xbd, g: 0xb7, b: 0x6b, a: 0xff }),
+    ("darkmagenta", Color { r: 0x8b, g: 0x00, b: 0x8b, a: 0xff }),
+    ("darkolivegreen", Color { r: 0x55, g: 0x6b, b: 0x2f, a: 0xff }),
+    ("darkorange", Color { r: 0xff, g: 0x8c, b: 0x00, a: 0xff }),
+    ("darkorchid", Color { r: 0x99, g: 0x32, b: 0xcc, a: 0xff }),
+    ("darkred", Color { r: 0x8b, g: 0x00, b: 0x00, a: 0xff }),
+    ("darksalmon", Color { r: 0xe9, g: 0x96, b: 0x7a, a: 0xff }),
+    ("darkseagreen", Color { r: 0x8f, g: 0xbc, b: 0x8f, a: 0xff }),
+    ("darkslateblue", Color { r: 0x48, g: 0x3d, b: 0x8b, a: 0xff }),
+    ("darkslategray", Color { r: 0x2f, g: 0x4f, b: 0x4f, a: 0xff }),
+    ("darkslategrey", Color { r: 0x2f, g: 0x4f, b: 0x4f, a: 0xff }),
+    ("darkturquoise", Color { r: 0x00, g: 0xce, b: 0xd1, a: 0xff }),
+    ("darkviolet", Color { r: 0x94, g: 0x00, b: 0xd3, a: 0xff }),
+    ("deeppink", Color { r: 0xff, g: 0x14, b: 0x93, a: 0xff }),
+    ("deepskyblue", Color { r: 0x00, g: 0xbf, b: 0xff, a: 0xff }),
+    ("dimgray", Color { r: 0x69, g: 0x69, b: 0x69, a: 0xff }),
+    ("dimgrey", Color { r: 0x69, g: 0x69, b: 0x69, a: 0xff }),
+    ("dodgerblue", Color { r: 0x1e, g: 0x90, b: 0xff, a: 0xff }),
+    ("firebrick", Color { r: 0xb2, g: 0x22, b: 0x22, a: 0xff }),
+    ("floralwhite", Color { r: 0xff, g: 0xfa, b: 0xf0, a: 0xff }),
+    ("forestgreen", Color { r: 0x22, g: 0x8b, b: 0x22, a: 0xff }),
+    ("fuchsia", Color { r: 0xff, g: 0x00, b: 0xff, a: 0xff }),
+    ("gainsboro", Color { r: 0xdc, g: 0xdc, b: 0xdc, a: 0xff }),
+    ("ghostwhite", Color { r: 0xf8, g: 0xf8, b: 0xff, a: 0xff }),
+    ("gold", Color { r: 0xff, g: 0xd7, b: 0x00, a: 0xff }),
+    ("goldenrod", Color { r: 0xda, g: 0xa5, b: 0x20, a: 0xff }),
+    ("gray", Color { r: 0x80, g: 0x80, b: 0x80, a: 0xff }),
+    ("green", Color { r: 0x00, g: 0x80, b: 0x00, a: 0xff }),
+    ("greenyellow", Color { r: 0xad, g: 0xff, b: 0x2f, a: 0xff }),
+    ("grey", Color { r: 0x80, g: 0x80, b: 0x80, a: 0xff }),
+    ("honeydew", Color { r: 0xf0, g: 0xff, b: 0xf0, a: 0xff }),
+    ("hotpink", Color { r: 0xff, g: 0x69, b: 0xb4, a: 0xff }),
+    ("indianred", Color { r: 0xcd, g: 0x5c, b: 0x5c, a: 0xff }),
+    ("indigo", Color { r: 0x4b, g: 0x00, b: 0x82, a: 0xff }),
+    ("ivory", Color { r: 0xff, g: 0xff, b: 0xf0, a: 0xff }),
+    ("khaki", Color { r: 0xf0, g: 0xe6, b: 0x8c, a: 0xff }),
+    ("lavender", Color { r: 0xe6, g: 0xe6, b: 0xfa, a: 0xff }),
+    ("lavenderblush", Color { r: 0xff, g: 0xf0, b: 0xf5, a: 0xff }),
+    ("lawngreen", Color { r: 0x7c, g: 0xfc, b: 0x00, a: 0xff }),
+    ("lemonchiffon", Color { r: 0xff, g: 0xfa, b: 0xcd, a: 0xff }),
+    ("lightblue", Color { r: 0xad, g: 0xd8, b: 0xe6, a: 0xff }),
+    ("lightcoral", Color { r: 0xf0, g: 0x80, b: 0x80, a: 0xff }),
+    ("lightcyan", Color { r: 0xe0, g: 0xff, b: 0xff, a: 0xff }),
+    ("lightgoldenrodyellow", Color { r: 0xfa, g: 0xfa, b: 0xd2, a: 0xff }),
+    ("lightgray", Color { r: 0xd3, g: 0xd3, b: 0xd3, a: 0xff }),
+    ("lightgreen", Color { r: 0x90, g: 0xee, b: 0x90, a: 0xff }),
+    ("lightgrey", Color { r: 0xd3, g: 0xd3, b: 0xd3, a: 0xff }),
+    ("lightpink", Color { r: 0xff, g: 0xb6, b: 0xc1, a: 0xff }),
+    ("lightsalmon", Color { r: 0xff, g: 0xa0, b: 0x7a, a: 0xff }),
+    ("lightseagreen", Color { r: 0x20, g: 0xb2, b: 0xaa, a: 0xff }),
+    ("lightskyblue", Color { r: 0x87, g: 0xce, b: 0xfa, a: 0xff }),
+    ("lightslategray", Color { r: 0x77, g: 0x88, b: 0x99, a: 0xff }),
+    ("lightslategrey", Color { r: 0x77, g: 0x88, b: 0x99, a: 0xff }),
+    ("lightsteelblue", Color { r: 0xb0, g: 0xc4, b: 0xde, a: 0xff }),
+    ("lightyellow", Color { r: 0xff, g: 0xff, b: 0xe0, a: 0xff }),
+    ("lime", Color { r: 0x00, g: 0xff, b: 0x00, a: 0xff }),
+    ("limegreen", Color { r: 0x32, g: 0xcd, b: 0x32, a: 0xff }),
+    ("linen", Color { r: 0xfa, g: 0xf0, b: 0xe6, a: 0xff }),
+    ("magenta", Color { r: 0xff, g: 0x00, b: 0xff, a: 0xff }),
+    ("maroon", Color { r: 0x80, g: 0x00, b: 0x00, a: 0xff }),
+    ("mediumaquamarine", Color { r: 0x66, g: 0xcd, b: 0xaa, a: 0xff }),
+    ("mediumblue", Color { r: 0x00, g: 0x00, b: 0xcd, a: 0xff }),
+    ("mediumorchid", Color { r: 0xba, g: 0x55, b: 0xd3, a: 0xff }),
+    ("mediumpurple", Color { r: 0x93, g: 0x70, b: 0xdb, a: 0xff }),
+    ("mediumseagreen", Color { r: 0x3c, g: 0xb3, b: 0x71, a: 0xff }),
+    ("mediumslateblue", Color { r: 0x7b, g: 0x68, b: 0xee, a: 0xff }),
+    ("mediumspringgreen", Color { r: 0x00, g: 0xfa, b: 0x9a, a: 0xff }),
+    ("mediumturquoise", Color { r: 0x48, g: 0xd1, b: 0xcc, a: 0xff }),
+    ("mediumvioletred", Color { r: 0xc7, g: 0x15, b: 0x85, a: 0xff }),
+    ("midnightblue", Color { r: 0x19, g: 0x19, b: 0x70, a: 0xff }),
+    ("mintcream", Color { r: 0xf5, g: 0xff, b: 0xfa, a: 0xff }),
+    ("mistyrose", Color { r: 0xff, g: 0xe4, b: 0xe1, a: 0xff }),
+    ("moccasin", Color { r: 0xff, g: 0xe4, b: 0xb5, a: 0xff }),
+    ("navajowhite", Color { r: 0xff, g: 0xde, b: 0xad, a: 0xff }),
+    ("navy", Color { r: 0x00, g: 0x00, b: 0x80, a: 0xff }),
+    ("oldlace", Color { r: 0xfd, g: 0xf5, b: 0xe6, a: 0xff }),
+    ("olive", Color { r: 0x80, g: 0x80, b: 0x00, a: 0xff }),
+    ("olivedrab", Color { r: 0x6b, g: 0x8e, b: 0x23, a: 0xff }),
+    ("orange", Color { r: 0xff, g: 0xa5, b: 0x00, a: 0xff }),
+    ("orangered", Color { r: 0xff, g: 0x45, b: 0x00, a: 0xff }),
+    ("orchid", Color { r: 0xda, g: 0x70, b: 0xd6, a: 0xff }),
+    ("palegoldenrod", Color { r: 0xee, g: 0xe8, b: 0xaa, a: 0xff }),
+    ("palegreen", Color { r: 0x98, g: 0xfb, b: 0x98, a: 0xff }),
+    ("paleturquoise", Color { r: 0xaf, g: 0xee, b: 0xee, a: 0xff }),
+    ("palevioletred", Color { r: 0xdb, g: 0x70, b: 0x93, a: 0xff }),
+    ("papayawhip", Color { r: 0xff, g: 0xef, b: 0xd5, a: 0xff }),
+    ("peru", Color { r: 0xcd, g: 0x85, b: 0x3f, a: 0xff }),
+    ("pink", Color { r: 0xff, g: 0xc0, b: 0xcb, a: 0xff }),
+    ("plum", Color { r: 0xdd, g: 0xa0, b: 0xdd, a: 0xff }),
+    ("powderblue", Color { r: 0xb0, g: 0xe0, b: 0xe6, a: 0xff }),
+    ("purple", Color { r: 0x80, g: 0x00, b: 0x80, a: 0xff }),
+    ("rebeccapurple", Color { r: 0x66, g: 0x33, b: 0x99, a: 0xff }),
+    ("red", Color { r: 0xff, g: 0x00, b: 0x00, a: 0xff }),
+    ("rosybrown", Color { r: 0xbc, g: 0x8f, b: 0x8f, a: 0xff }),
+    ("royalblue", Color { r: 0x41, g: 0x69, b: 0xe1, a: 0xff }),
+    ("saddlebrown", Color { r: 0x8b, g: 0x45, b: 0x13, a: 0xff }),
+    ("salmon", Color { r: 0xfa, g: 0x80, b: 0x72, a: 0xff }),
+    ("sandybrown", Color { r: 0xf4, g: 0xa4, b: 0x60, a: 0xff }),
+    ("seagreen", Color { r: 0x2e, g: 0x8b, b: 0x57, a: 0xff }),
+    ("seashell", Color { r: 0xff, g: 0xf5, b: 0xee, a: 0xff }),
+    ("sienna", Color { r: 0xa0, g: 0x52, b: 0x2d, a: 0xff }),
+    ("silver", Color { r: 0xc0, g: 0xc0, b: 0xc0, a: 0xff }),
+    ("skyblue", Color { r: 0x87, g: 0xce, b: 0xeb, a: 0xff }),
+    ("slateblue", Color { r: 0x6a, g: 0x5a, b: 0xcd, a: 0xff }),
+    ("slategray", Color { r: 0x70, g: 0x80, b: 0x90, a: 0xff }),
+    ("slategrey", Color { r: 0x70, g: 0x80, b: 0x90, a: 0xff }),
+    ("snow", Color { r: 0xff, g: 0xfa, b: 0xfa, a: 0xff }),
+    ("springgreen", Color { r: 0x00, g: 0xff, b: 0x7f, a: 0xff }),
+    ("steelblue", Color { r: 0x46, g: 0x82, b: 0xb4, a: 0xff }),
+    ("tan", Color { r: 0xd2, g: 0xb4, b: 0x8c, a: 0xff }),
+    ("teal", Color { r: 0x00, g: 0x80, b: 0x80, a: 0xff }),
+    ("thistle", Color { r: 0xd8, g: 0xbf, b: 0xd8, a: 0xff }),
+    ("tomato", Color { r: 0xff, g: 0x63, b: 0x47, a: 0xff }),
+    ("turquoise", Color { r: 0x40, g: 0xe0, b: 0xd0, a: 0xff }),
+    ("violet", Color { r: 0xee, g: 0x82, b: 0xee, a: 0xff }),
+    ("wheat", Color { r: 0xf5, g: 0xde, b: 0xb3, a: 0xff }),
+    ("white", Color { r: 0xff, g: 0xff, b: 0xff, a: 0xff }),
+    ("whitesmoke", Color { r: 0xf5, g: 0xf5, b: 0xf5, a: 0xff }),
+    ("yellow", Color { r: 0xff, g: 0xff, b: 0x00, a: 0xff }),
+    ("yellowgreen", Color { r: 0x9a, g: 0xcd, b: 0x32, a: 0xff }),
+];
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_from_hex() {
+        assert_eq!(Color::from_hex("#ff8000"), Ok(Color::rgb(255, 128, 0)));
+        assert_eq!(Color::from_hex("#ff800080"), Ok(Color::rgba(255, 128, 0, 128)));
+        assert_eq!(Color::from_hex("#f80"), Ok(Color::rgb(255, 136, 0)));
+        assert_eq!(Color::from_hex("ff8000"), Err(ParseColorError::MissingHash));
+        assert_eq!(Color::from_hex("#ff80"), Ok(Color::rgba(255, 255, 136, 0)));
+        assert_eq!(Color::from_hex("#ff800"), Err(ParseColorError::InvalidLength));
+        assert_eq!(Color::from_hex("#gg8000"), Err(ParseColorError::InvalidDigit));
+        assert_eq!("RebeccaPurple".parse(), Ok(Color::rgb(0x66, 0x33, 0x99)));
+    }
+
+    #[test]
+    fn test_hsl_hsv() {
+        assert_eq!(Color::hsl(0.0, 1.0, 0.5), Color::rgb(255, 0, 0));
+        assert_eq!(Color::hsl(1.0 / 3.0, 1.0, 0.5), Color::rgb(0, 255, 0));
+        assert_eq!(Color::hsl(0.0, 0.0, 0.5), Color::rgb(128, 128, 128));
+        assert_eq!(Color::hsv(2.0 / 3.0, 1.0, 1.0), Color::rgb(0, 0, 255));
+        assert_eq!(Color::hsv(0.5, 0.5, 1.0), Color::rgb(128, 255, 255));
+    }
+
+    #[test]
+    fn test_oklab_round_trip() {
+        let white = linear_srgb_to_oklab([1.0, 1.0, 1.0]);
+        assert!((white[0] - 1.0).abs() < 1e-3 && white[1].abs() < 1e-3 && white[2].abs() < 1e-3);
+
+        let color = [0.2, 0.5, 0.8];
+        let back = oklab_to_linear_srgb(linear_srgb_to_oklab(color));
+        for i in 0..3 {
+            assert!((back[i] - color[i]).abs() < 1e-3);
+        }
+        assert!((linear_to_srgb(srgb_to_linear(0.5)) - 0.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_lerp_premultiplied() {
+        let color = Color::rgba(0, 100, 200, 0).lerp(Color::rgba(100, 200, 0, 255), 0.5);
+        assert_eq!(color, Color::rgba(50, 150, 100, 128));
+        assert_eq!(Color::rgba(255, 128, 0, 128).premultiplied(), Color::rgba(128, 64, 0, 128));
+    }
+}
@@ -0,0 +1,655 @@
+use std::{
+    error::Error,
+    fmt,
+    fmt::Write as _,
+};
+
+use crate::*;
+
+/// Version of the command stream format written by `CommandWriter`.
+pub const COMMAND_STREAM_VERSION: u16 = 1;
+
+const MAGIC: &[u8; 4] = b"RDCS";
+
+const RECORD_STATE: u8 = 0x01;
+const RECORD_PATH: u8 = 0x02;
+const RECORD_FILL: u8 = 0x10;
+const RECORD_STROKE: u8 = 0x11;
+const RECORD_CLIP: u8 = 0x12;
+const RECORD_PUSH_LAYER: u8 = 0x13;
+const RECORD_POP_LAYER: u8 = 0x14;
+
+const PATH_MOVE_TO: u8 = 0;
+const PATH_LINE_TO: u8 = 1;
+const PATH_BEZIER_TO: u8 = 2;
+const PATH_CLOSE: u8 = 3;
+const PATH_WINDING: u8 = 4;
+
+const LINE_CAPS: [LineCap; 3] = [LineCap::Butt, LineCap::Round, LineCap::Square];
+const LINE_JOINS: [LineJoin; 3] = [LineJoin::Round, LineJoin::Bevel, LineJoin::Miter];
+pub(crate) const WINDINGS: [Winding; 2] = [Winding::CCW, Winding::CW];
+const SPREAD_MODES: [SpreadMode; 3] = [SpreadMode::Pad, SpreadMode::Repeat, SpreadMode::Reflect];
+const BLEND_FACTORS: [BlendFactor; 11] = [
+    BlendFactor::Zero,
+    BlendFactor::One,
+    BlendFactor::SrcColor,
+    BlendFactor::OneMinusSrcColor,
+    BlendFactor::DstColor,
+    BlendFactor::OneMinusDstColor,
+    BlendFactor::SrcAlpha,
+    BlendFactor::OneMinusSrcAlpha,
+    BlendFactor::DstAlpha,
+    BlendFactor::OneMinusDstAlpha,
+    BlendFactor::SrcAlphaSaturate,
+];
+pub(crate) const BLEND_MODES: [BlendMode; 12] = [
+    BlendMode::Normal,
+    BlendMode::Multiply,
+    BlendMode::Screen,
+    BlendMode::Overlay,
+    BlendMode::Darken,
+    BlendMode::Lighten,
+    BlendMode::ColorDodge,
+    BlendMode::ColorBurn,
+    BlendMode::HardLight,
+    BlendMode::SoftLight,
+    BlendMode::Difference,
+    BlendMode::Exclusion,
+];
+
+/// Captures the draws of a `Canvas` as a compact binary command stream, see
+/// `Canvas::begin_capture`.
+///
+/// Each fill, stroke, clip and layer is written with the path and state it uses. The state and
+/// the path are only written again when they changed since the previous draw.
+pub struct CommandWriter {
+    buf: Vec<u8>,
+    state: Vec<u8>,
+    path: Vec<u8>,
+}
+
+impl Default for CommandWriter {
+    fn default() -> Self {
+        CommandWriter::new()
+    }
+}
+
+impl CommandWriter {
+    pub fn new() -> CommandWriter {
+        let mut buf = MAGIC.to_vec();
+        buf.extend_from_slice(&COMMAND_STREAM_VERSION.to_le_bytes());
+        CommandWriter {
+            buf,
+            state: Vec::new(),
+            path: Vec::new(),
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buf
+    }
+
+    /// Formats the captured commands as JSON, as `CommandReader::to_debug_json` does. Fails if
+    /// the capture can't be read back, such as when a scissor isn't finite.
+    pub fn to_debug_json(&self) -> Result<String, CommandStreamError> {
+        Ok(CommandReader::new(&self.buf)?.to_debug_json())
+    }
+
+    fn record_state(&mut self, state: &State, pixels_per_point: Scalar) {
+        let mut encoded = Vec::new();
+        write_state(&mut encoded, state, pixels_per_point);
+        if encoded != self.state {
+            self.buf.push(RECORD_STATE);
+            self.buf.extend_from_slice(&encoded);
+            self.state = encoded;
+        }
+    }
+
+    fn record_path(&mut self, commands: &[Command]) {
+        let mut encoded = Vec::new();
+        write_path(&mut encoded, commands);
+        if encoded != self.path {
+            self.buf.push(RECORD_PATH);
+            self.buf.extend_from_slice(&encoded);
+            self.path = encoded;
+        }
+    }
+
+    fn record_draw(&mut self, record: u8, state: &State, pixels_per_point: Scalar, commands: &[Command]) {
+        self.record_state(state, pixels_per_point);
+        self.record_path(commands);
+        self.buf.push(record);
+    }
+
+    pub(crate) fn record_fill(&mut self, state: &State, pixels_per_point: Scalar, commands: &[Command]) {
+        self.record_draw(RECORD_FILL, state, pixels_per_point, commands);
+    }
+
+    pub(crate) fn record_stroke(&mut self, state: &State, pixels_per_point: Scalar, commands: &[Command]) {
+        self.record_draw(RECORD_STROKE, state, pixels_per_point, commands);
+    }
+
+    pub(crate) fn record_clip(&mut self, state: &State, pixels_per_point: Scalar, commands: &[Command]) {
+        self.record_draw(RECORD_CLIP, state, pixels_per_point, commands);
+    }
+
+    pub(crate) fn record_push_layer(&mut self, state: &State, pixels_per_point: Scalar, opacity: Scalar, blend: BlendMode) {
+        self.record_state(state, pixels_per_point);
+        self.buf.push(RECORD_PUSH_LAYER);
+        write_f32(&mut self.buf, opacity);
+        self.buf.push(index_of(&BLEND_MODES, blend));
+    }
+
+    pub(crate) fn record_pop_layer(&mut self) {
+        self.buf.push(RECORD_POP_LAYER);
+    }
+}
+
+/// Errors reading a command stream.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum CommandStreamError {
+    InvalidMagic,
+    UnsupportedVersion(u16),
+    UnexpectedEnd,
+    /// A record or an enum value is unknown.
+    InvalidValue,
+}
+
+impl fmt::Display for CommandStreamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandStreamError::InvalidMagic => f.write_str("not a command stream"),
+            CommandStreamError::UnsupportedVersion(version) => write!(f, "unsupported command stream version {}", version),
+            CommandStreamError::UnexpectedEnd => f.write_str("command stream ends unexpectedly"),
+            CommandStreamError::InvalidValue => f.write_str("command stream contains an invalid value"),
+        }
+    }
+}
+
+impl Error for CommandStreamError {}
+
+enum Record {
+    State(Box<State>, Scalar),
+    Path(Vec<Command>),
+    Fill,
+    Stroke,
+    Clip,
+    PushLayer(Scalar, BlendMode),
+    PopLayer,
+}
+
+/// Decoded command stream, which can be replayed onto a `Canvas`.
+pub struct CommandReader {
+    version: u16,
+    records: Vec<Record>,
+}
+
+impl CommandReader {
+    pub fn new(bytes: &[u8]) -> Result<CommandReader, CommandStreamError> {
+        let mut input = Input { bytes };
+        if input.take(4)? != MAGIC {
+            return Err(CommandStreamError::InvalidMagic);
+        }
+        let version = u16::from_le_bytes([input.u8()?, input.u8()?]);
+        if version != COMMAND_STREAM_VERSION {
+            return Err(CommandStreamError::UnsupportedVersion(version));
+        }
+
+        let mut records = Vec::new();
+        while !input.bytes.is_empty() {
+            let record = match input.u8()? {
+                RECORD_STATE => {
+                    let (state, pixels_per_point) = read_state(&mut input)?;
+                    Record::State(Box::new(state), pixels_per_point)
+                }
+                RECORD_PATH => Record::Path(read_path(&mut input)?),
+                RECORD_FILL => Record::Fill,
+                RECORD_STROKE => Record::Stroke,
+                RECORD_CLIP => Record::Clip,
+                RECORD_PUSH_LAYER => Record::PushLayer(input.f32()?, input.value(&BLEND_MODES)?),
+                RECORD_POP_LAYER => Record::PopLayer,
+                _ => return Err(CommandStreamError::InvalidValue),
+            };
+            records.push(record);
+        }
+
+        Ok(CommandReader { version, records })
+    }
+
+    /// Draws the commands with `canvas` into `renderer`. The state and path of `canvas` are
+    /// replaced.
+    pub fn replay<R>(&self, canvas: &mut Canvas, renderer: &mut R) where R: CanvasRenderer {
+        for record in self.records.iter() {
+            match record {
+                Record::State(state, pixels_per_point) => {
+                    canvas.state = (**state).clone();
+                    canvas.set_pixels_per_point(*pixels_per_point);
+                }
+                Record::Path(commands) => {
+                    canvas.begin_path();
+                    canvas.commands = commands.clone();
+                }
+                Record::Fill => canvas.fill(renderer),
+                Record::Stroke => canvas.stroke(renderer),
+                Record::Clip => canvas.clip(renderer),
+                Record::PushLayer(opacity, blend) => canvas.push_layer(renderer, *opacity, *blend),
+                Record::PopLayer => canvas.pop_layer(renderer),
+            }
+        }
+    }
+
+    /// Formats the commands as JSON, to inspect a capture while debugging. The dump can't be
+    /// read back: only the binary stream is an encoding that can be replayed.
+    pub fn to_debug_json(&self) -> String {
+        let mut json = format!("{{\"version\":{},\"records\":[", self.version);
+        for (i, record) in self.records.iter().enumerate() {
+            if i > 0 {
+                json.push(',');
+            }
+            match record {
+                Record::State(state, pixels_per_point) => json_state(&mut json, state, *pixels_per_point),
+                Record::Path(commands) => json_path(&mut json, commands),
+                Record::Fill => json.push_str("\"fill\""),
+                Record::Stroke => json.push_str("\"stroke\""),
+                Record::Clip => json.push_str("\"clip\""),
+                Record::PushLayer(opacity, blend) => {
+                    let _ = write!(json, "{{\"push_layer\":{{\"opacity\":{},\"blend\":{}}}}}", json_f32(*opacity), index_of(&BLEND_MODES, *blend));
+                }
+                Record::PopLayer => json.push_str("\"pop_layer\""),
+            }
+        }
+        json.push_str("]}");
+        json
+    }
+}
+
+pub(crate) fn index_of<T: Copy + PartialEq>(values: &[T], value: T) -> u8 {
+    values.iter().position(|&v| v == value).unwrap() as u8
+}
+
+pub(crate) fn write_f32(buf: &mut Vec<u8>, value: f32) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+pub(crate) fn write_f32s(buf: &mut Vec<u8>, values: &[f32]) {
+    for &value in values.iter() {
+        write_f32(buf, value);
+    }
+}
+
+pub(crate) fn write_u32(buf: &mut Vec<u8>, value: u32) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+pub(crate) fn write_paint(buf: &mut Vec<u8>, paint: &Paint) {
+    write_f32s(buf, &paint.transform.e);
+    write_f32s(buf, &paint.extent);
+    write_f32(buf, paint.radius);
+    write_f32(buf, paint.feather);
+    write_f32s(buf, &paint.inner_color);
+    write_f32s(buf, &paint.outer_color);
+    buf.extend_from_slice(&paint.image.to_le_bytes());
+    write_u32(buf, paint.stops.len() as u32);
+    for (offset, color) in paint.stops.iter() {
+        write_f32(buf, *offset);
+        write_f32s(buf, color);
+    }
+    match paint.shape {
+        GradientShape::Box => buf.push(0),
+        GradientShape::Sweep { start_angle, end_angle } => {
+            buf.push(1);
+            write_f32(buf, start_angle);
+            write_f32(buf, end_angle);
+        }
+    }
+    buf.push(index_of(&SPREAD_MODES, paint.spread));
+}
+
+fn write_state(buf: &mut Vec<u8>, state: &State, pixels_per_point: Scalar) {
+    write_f32(buf, pixels_per_point);
+    write_f32(buf, state.line_width);
+    buf.push(index_of(&LINE_CAPS, state.line_cap));
+    buf.push(index_of(&LINE_JOINS, state.line_join));
+    write_f32(buf, state.miter_limit);
+    write_paint(buf, &state.stroke);
+    write_paint(buf, &state.fill);
+    buf.push(state.shape_anti_alias as u8);
+    write_scissor(buf, &state.scissor);
+    write_composite(buf, &state.composite);
+    write_u32(buf, state.clip_depth as u32);
+    write_f32s(buf, &state.transform.e);
+}
+
+pub(crate) fn write_scissor(buf: &mut Vec<u8>, scissor: &Scissor) {
+    write_f32s(buf, &scissor.transform.e);
+    write_f32s(buf, &scissor.extent);
+}
+
+pub(crate) fn write_composite(buf: &mut Vec<u8>, composite: &CompositeState) {
+    for &factor in [composite.src_rgb, composite.dst_rgb, composite.src_alpha, composite.dst_alpha].iter() {
+        buf.push(index_of(&BLEND_FACTORS, factor));
+    }
+    buf.push(index_of(&BLEND_MODES, composite.blend_mode));
+}
+
+fn write_path(buf: &mut Vec<u8>, commands: &[Command]) {
+    write_u32(buf, commands.len() as u32);
+    for command in commands.iter() {
+        match *command {
+            Command::MoveTo(x, y) => {
+                buf.push(PATH_MOVE_TO);
+                write_f32s(buf, &[x, y]);
+            }
+            Command::LineTo(x, y) => {
+                buf.push(PATH_LINE_TO);
+                write_f32s(buf, &[x, y]);
+            }
+            Command::BezierTo(cp1x, cp1y, cp2x, cp2y, x, y) => {
+                buf.push(PATH_BEZIER_TO);
+                write_f32s(buf, &[cp1x, cp1y, cp2x, cp2y, x, y]);
+            }
+            Command::Close => buf.push(PATH_CLOSE),
+            Command::Winding(winding) => {
+                buf.push(PATH_WINDING);
+                buf.push(index_of(&WINDINGS, winding));
+            }
+        }
+    }
+}
+
+pub(crate) struct Input<'a> {
+    pub(crate) bytes: &'a [u8],
+}
+
+impl<'a> Input<'a> {
+    pub(crate) fn take(&mut self, len: usize) -> Result<&'a [u8], CommandStreamError> {
+        if self.bytes.len() < len {
+            return Err(CommandStreamError::UnexpectedEnd);
+        }
+        let (bytes, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(bytes)
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, CommandStreamError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32, CommandStreamError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    pub(crate) fn f32(&mut self) -> Result<f32, CommandStreamError> {
+        Ok(f32::from_bits(self.u32()?))
+    }
+
+    pub(crate) fn f32s<const N: usize>(&mut self) -> Result<[f32; N], CommandStreamError> {
+        let mut values = [0.0; N];
+        for value in values.iter_mut() {
+            *value = self.f32()?;
+        }
+        Ok(values)
+    }
+
+    pub(crate) fn value<T: Copy>(&mut self, values: &[T]) -> Result<T, CommandStreamError> {
+        let index = self.u8()? as usize;
+        values.get(index).copied().ok_or(CommandStreamError::InvalidValue)
+    }
+}
+
+pub(crate) fn read_paint(input: &mut Input) -> Result<Paint, CommandStreamError> {
+    let transform = Transform { e: input.f32s()? };
+    let extent = input.f32s()?;
+    let radius = input.f32()?;
+    let feather = input.f32()?;
+    let inner_color = input.f32s()?;
+    let outer_color = input.f32s()?;
+    let image = input.u32()? as i32;
+    let nstops = input.u32()? as usize;
+    // Each stop takes 20 bytes, which bounds the allocation for corrupted counts.
+    let mut stops = Vec::with_capacity(nstops.min(input.bytes.len() / 20));
+    for _ in 0..nstops {
+        stops.push((input.f32()?, input.f32s()?));
+    }
+    let shape = match input.u8()? {
+        0 => GradientShape::Box,
+        1 => GradientShape::Sweep { start_angle: input.f32()?, end_angle: input.f32()? },
+        _ => return Err(CommandStreamError::InvalidValue),
+    };
+    let spread = input.value(&SPREAD_MODES)?;
+
+    Ok(Paint {
+        transform,
+        extent,
+        radius,
+        feather,
+        inner_color,
+        outer_color,
+        image,
+        stops,
+        shape,
+        spread,
+    })
+}
+
+fn read_state(input: &mut Input) -> Result<(State, Scalar), CommandStreamError> {
+    let pixels_per_point = input.f32()?;
+    let state = State {
+        line_width: input.f32()?,
+        line_cap: input.value(&LINE_CAPS)?,
+        line_join: input.value(&LINE_JOINS)?,
+        miter_limit: input.f32()?,
+        stroke: read_paint(input)?,
+        fill: read_paint(input)?,
+        shape_anti_alias: input.u8()? != 0,
+        scissor: read_scissor(input)?,
+        composite: read_composite(input)?,
+        clip_depth: input.u32()? as usize,
+        transform: Transform { e: input.f32s()? },
+    };
+    Ok((state, pixels_per_point))
+}
+
+/// Reads a scissor, rejecting non-finite values which renderers can't clip with.
+pub(crate) fn read_scissor(input: &mut Input) -> Result<Scissor, CommandStreamError> {
+    let scissor = Scissor {
+        transform: Transform { e: input.f32s()? },
+        extent: input.f32s()?,
+    };
+    if !scissor.transform.e.iter().chain(scissor.extent.iter()).all(|value| value.is_finite()) {
+        return Err(CommandStreamError::InvalidValue);
+    }
+    Ok(scissor)
+}
+
+pub(crate) fn read_composite(input: &mut Input) -> Result<CompositeState, CommandStreamError> {
+    Ok(CompositeState {
+        src_rgb: input.value(&BLEND_FACTORS)?,
+        dst_rgb: input.value(&BLEND_FACTORS)?,
+        src_alpha: input.value(&BLEND_FACTORS)?,
+        dst_alpha: input.value(&BLEND_FACTORS)?,
+        blend_mode: input.value(&BLEND_MODES)?,
+    })
+}
+
+fn read_path(input: &mut Input) -> Result<Vec<Command>, CommandStreamError> {
+    let count = input.u32()? as usize;
+    let mut commands = Vec::with_capacity(count.min(input.bytes.len()));
+    for _ in 0..count {
+        let command = match input.u8()? {
+            PATH_MOVE_TO => Command::MoveTo(input.f32()?, input.f32()?),
+            PATH_LINE_TO => Command::LineTo(input.f32()?, input.f32()?),
+            PATH_BEZIER_TO => Command::BezierTo(input.f32()?, input.f32()?, input.f32()?, input.f32()?, input.f32()?, input.f32()?),
+            PATH_CLOSE => Command::Close,
+            PATH_WINDING => Command::Winding(input.value(&WINDINGS)?),
+            _ => return Err(CommandStreamError::InvalidValue),
+        };
+        commands.push(command);
+    }
+    Ok(commands)
+}
+
+/// Formats a float, with `null` for the values JSON can't represent.
+fn json_f32(value: f32) -> String {
+    if value.is_finite() {
+        format!("{}", value)
+    } else {
+        "null".to_string()
+    }
+}
+
+fn json_f32s(values: &[f32]) -> String {
+    let values: Vec<String> = values.iter().map(|&value| json_f32(value)).collect();
+    format!("[{}]", values.join(","))
+}
+
+fn json_paint(json: &mut String, paint: &Paint) {
+    let stops: Vec<String> = paint.stops.iter()
+        .map(|(offset, color)| format!("[{},{}]", json_f32(*offset), json_f32s(color)))
+        .collect();
+    let shape = match paint.shape {
+        GradientShape::Box => "\"box\"".to_string(),
+        GradientShape::Sweep { start_angle, end_angle } => format!("{{\"sweep\":[{},{}]}}", json_f32(start_angle), json_f32(end_angle)),
+    };
+    let _ = write!(
+        json,
+        "{{\"transform\":{},\"extent\":{},\"radius\":{},\"feather\":{},\"inner_color\":{},\"outer_color\":{},\"image\":{},\"stops\":[{}],\"shape\":{},\"spread\":{}}}",
+        json_f32s(&paint.transform.e),
+        json_f32s(&paint.extent),
+        json_f32(paint.radius),
+        json_f32(paint.feather),
+        json_f32s(&paint.inner_color),
+        json_f32s(&paint.outer_color),
+        paint.image,
+        stops.join(","),
+        shape,
+        index_of(&SPREAD_MODES, paint.spread),
+    );
+}
+
+fn json_state(json: &mut String, state: &State, pixels_per_point: Scalar) {
+    let composite = &state.composite;
+    let _ = write!(
+        json,
+        "{{\"state\":{{\"pixels_per_point\":{},\"line_width\":{},\"line_cap\":{},\"line_join\":{},\"miter_limit\":{},\"stroke\":",
+        json_f32(pixels_per_point),
+        json_f32(state.line_width),
+        index_of(&LINE_CAPS, state.line_cap),
+        index_of(&LINE_JOINS, state.line_join),
+        json_f32(state.miter_limit),
+    );
+    json_paint(json, &state.stroke);
+    json.push_str(",\"fill\":");
+    json_paint(json, &state.fill);
+    let _ = write!(
+        json,
+        ",\"shape_anti_alias\":{},\"scissor\":{{\"transform\":{},\"extent\":{}}},\"composite\":[{},{},{},{},{}],\"clip_depth\":{},\"transform\":{}}}}}",
+        state.shape_anti_alias,
+        json_f32s(&state.scissor.transform.e),
+        json_f32s(&state.scissor.extent),
+        index_of(&BLEND_FACTORS, composite.src_rgb),
+        index_of(&BLEND_FACTORS, composite.dst_rgb),
+        index_of(&BLEND_FACTORS, composite.src_alpha),
+        index_of(&BLEND_FACTORS, composite.dst_alpha),
+        index_of(&BLEND_MODES, composite.blend_mode),
+        state.clip_depth,
+        json_f32s(&state.transform.e),
+    );
+}
+
+fn json_path(json: &mut String, commands: &[Command]) {
+    let commands: Vec<String> = commands.iter()
+        .map(|command| match *command {
+            Command::MoveTo(x, y) => format!("[\"M\",{},{}]", json_f32(x), json_f32(y)),
+            Command::LineTo(x, y) => format!("[\"L\",{},{}]", json_f32(x), json_f32(y)),
+            Command::BezierTo(cp1x, cp1y, cp2x, cp2y, x, y) => format!("[\"C\",{}]", json_f32s(&[cp1x, cp1y, cp2x, cp2y, x, y]).trim_matches(|c| c == '[' || c == ']')),
+            Command::Close => "[\"Z\"]".to_string(),
+            Command::Winding(winding) => format!("[\"W\",{}]", index_of(&WINDINGS, winding)),
+        })
+        .collect();
+    let _ = write!(json, "{{\"path\":[{}]}}", commands.join(","));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn draw_frame(canvas: &mut Canvas, list: &mut DisplayList) {
+        canvas.set_pixels_per_point(2.0);
+        canvas.begin_path();
+        canvas.rect(10.0, 10.0, 100.0, 50.0);
+        canvas.set_fill_color(Color::rgb(255, 0, 0));
+        canvas.fill(list);
+        canvas.set_stroke_width(3.0);
+        canvas.set_line_join(LineJoin::Round);
+        canvas.begin_path();
+        canvas.move_to(10.0, 10.0).line_to(110.0, 10.0).line_to(110.0, 60.0);
+        canvas.stroke(list);
+
+        canvas.save();
+        canvas.set_transform(Transform::scale(0.5, 0.5) * Transform::translate(25.0, 25.0));
+        canvas.begin_path();
+        canvas.circle(50.0, 50.0, 40.0);
+        canvas.clip(list);
+        canvas.push_layer(list, 0.5, BlendMode::Multiply);
+        let paint = canvas.sweep_gradient(50.0, 50.0, 0.0, 3.0, &[(0.0, Color::rgb(0, 0, 255)), (0.5, Color::rgb(0, 255, 0)), (1.0, Color::rgb(255, 0, 0))]);
+        canvas.set_fill_paint(&paint);
+        canvas.begin_path();
+        canvas.move_to(0.0, 0.0).bezier_to(10.0, 0.0, 20.0, 10.0, 20.0, 20.0).line_to(0.0, 20.0);
+        canvas.path_winding(Winding::CW);
+        canvas.fill(list);
+        canvas.pop_layer(list);
+        canvas.restore();
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut canvas = Canvas::new();
+        let mut list = DisplayList::new();
+        canvas.begin_capture();
+        draw_frame(&mut canvas, &mut list);
+        let writer = canvas.end_capture().unwrap();
+
+        let reader = CommandReader::new(writer.as_bytes()).unwrap();
+        let mut replayed = DisplayList::new();
+        let mut canvas = Canvas::new();
+        canvas.begin_capture();
+        reader.replay(&mut canvas, &mut replayed);
+        let rewritten = canvas.end_capture().unwrap();
+
+        assert_eq!(rewritten.as_bytes(), writer.as_bytes());
+        assert_eq!(replayed.len(), list.len());
+        assert_eq!(rewritten.to_debug_json(), writer.to_debug_json());
+        assert!(writer.to_debug_json().unwrap().starts_with("{\"version\":1,\"records\":[{\"state\":"));
+    }
+
+    #[test]
+    fn test_invalid_streams() {
+        let mut canvas = Canvas::new();
+        let mut list = DisplayList::new();
+        canvas.begin_capture();
+        draw_frame(&mut canvas, &mut list);
+        let bytes = canvas.end_capture().unwrap().into_bytes();
+
+        assert_eq!(CommandReader::new(b"nope").err(), Some(CommandStreamError::InvalidMagic));
+        assert_eq!(CommandReader::new(b"RDCS\x02\x00").err(), Some(CommandStreamError::UnsupportedVersion(2)));
+        assert_eq!(CommandReader::new(&bytes[..bytes.len() - 10]).err(), Some(CommandStreamError::UnexpectedEnd));
+        assert_eq!(CommandReader::new(b"RDCS\x01\x00\xff").err(), Some(CommandStreamError::InvalidValue));
+
+        // A scissor with NaN extents would otherwise reach the renderers.
+        canvas.begin_capture();
+        canvas.state.scissor = Scissor {
+            transform: Transform::identity(),
+            extent: [f32::NAN, 10.0],
+        };
+        canvas.fill(&mut list);
+        let writer = canvas.end_capture().unwrap();
+        assert_eq!(writer.to_debug_json().err(), Some(CommandStreamError::InvalidValue));
+        assert_eq!(CommandReader::new(writer.as_bytes()).err(), Some(CommandStreamError::InvalidValue));
+    }
+}
@@ -0,0 +1,210 @@
+use std::borrow::Cow;
+
+use crate::*;
+
+/// Recorded renderer calls that can be replayed into any `CanvasRenderer`, so drawing which
+/// didn't change skips path flattening and tessellation.
+///
+/// A `DisplayList` records by being passed to the `Canvas` drawing methods as the renderer.
+/// Clip depths are replayed as they were recorded.
+#[derive(Clone, Default)]
+pub struct DisplayList {
+    commands: Vec<DisplayCommand>,
+}
+
+#[derive(Clone)]
+enum DisplayCommand {
+    Fill {
+        paint: Paint,
+        composite: CompositeState,
+        scissor: Scissor,
+        clip_depth: usize,
+        fringe: Scalar,
+        bounds: Rect,
+        paths: PathCache,
+    },
+    Stroke {
+        paint: Paint,
+        composite: CompositeState,
+        scissor: Scissor,
+        clip_depth: usize,
+        fringe: Scalar,
+        line_width: Scalar,
+        paths: PathCache,
+    },
+    Clip {
+        depth: usize,
+        paths: PathCache,
+    },
+    PushLayer {
+        opacity: Scalar,
+        composite: CompositeState,
+        clip_depth: usize,
+    },
+    PopLayer,
+}
+
+impl DisplayList {
+    pub fn new() -> DisplayList {
+        DisplayList::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.commands.clear();
+    }
+
+    /// Replays the recorded calls into `renderer`, moved by `dx`, `dy`.
+    pub fn replay<R>(&self, renderer: &mut R, dx: Scalar, dy: Scalar) where R: CanvasRenderer {
+        let moved = dx != 0.0 || dy != 0.0;
+        let translated = |paths| {
+            if moved {
+                Cow::Owned(PathCache::translated(paths, dx, dy))
+            } else {
+                Cow::Borrowed(paths)
+            }
+        };
+
+        for command in self.commands.iter() {
+            match command {
+                DisplayCommand::Fill { paint, composite, scissor, clip_depth, fringe, bounds, paths } => {
+                    let paint = translate_paint(paint, dx, dy);
+                    let scissor = translate_scissor(scissor, dx, dy);
+                    let bounds = bounds.translate(Vector::new(dx, dy));
+                    let cache = translated(paths);
+                    renderer.fill(&paint, composite, &scissor, *clip_depth, *fringe, bounds, Paths { cache: &cache });
+                }
+                DisplayCommand::Stroke { paint, composite, scissor, clip_depth, fringe, line_width, paths } => {
+                    let paint = translate_paint(paint, dx, dy);
+                    let scissor = translate_scissor(scissor, dx, dy);
+                    let cache = translated(paths);
+                    renderer.stroke(&paint, composite, &scissor, *clip_depth, *fringe, *line_width, Paths { cache: &cache });
+                }
+                DisplayCommand::Clip { depth, paths } => {
+                    let cache = translated(paths);
+                    renderer.clip(*depth, Paths { cache: &cache });
+                }
+                DisplayCommand::PushLayer { opacity, composite, clip_depth } => {
+                    renderer.push_layer(*opacity, composite, *clip_depth);
+                }
+                DisplayCommand::PopLayer => renderer.pop_layer(),
+            }
+        }
+    }
+}
+
+impl CanvasRenderer for DisplayList {
+    fn stroke(&mut self, paint: &Paint, composite: &CompositeState, scissor: &Scissor, clip_depth: usize, fringe: Scalar, line_width: Scalar, paths: Paths) {
+        self.commands.push(DisplayCommand::Stroke {
+            paint: paint.clone(),
+            composite: *composite,
+            scissor: scissor.clone(),
+            clip_depth,
+            fringe,
+            line_width,
+            paths: paths.cache.tessellation(),
+        });
+    }
+
+    fn fill(&mut self, paint: &Paint, composite: &CompositeState, scissor: &Scissor, clip_depth: usize, fringe: Scalar, bounds: Rect, paths: Paths) {
+        self.commands.push(DisplayCommand::Fill {
+            paint: paint.clone(),
+            composite: *composite,
+            scissor: scissor.clone(),
+            clip_depth,
+            fringe,
+            bounds,
+            paths: paths.cache.tessellation(),
+        });
+    }
+
+    fn clip(&mut self, depth: usize, paths: Paths) {
+        self.commands.push(DisplayCommand::Clip {
+            depth,
+            paths: paths.cache.tessellation(),
+        });
+    }
+
+    fn push_layer(&mut self, opacity: Scalar, composite: &CompositeState, clip_depth: usize) {
+        self.commands.push(DisplayCommand::PushLayer {
+            opacity,
+            composite: *composite,
+            clip_depth,
+        });
+    }
+
+    fn pop_layer(&mut self) {
+        self.commands.push(DisplayCommand::PopLayer);
+    }
+}
+
+fn translate_paint(paint: &Paint, dx: Scalar, dy: Scalar) -> Paint {
+    let mut paint = paint.clone();
+    paint.transform.e[4] += dx;
+    paint.transform.e[5] += dy;
+    paint
+}
+
+fn translate_scissor(scissor: &Scissor, dx: Scalar, dy: Scalar) -> Scissor {
+    let mut scissor = scissor.clone();
+    scissor.transform.e[4] += dx;
+    scissor.transform.e[5] += dy;
+    scissor
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn stroke_verts(list: &DisplayList) -> Vec<(Scalar, Scalar)> {
+        let mut verts = Vec::new();
+        for command in list.commands.iter() {
+            if let DisplayCommand::Stroke { paths, .. } = command {
+                let paths = Paths { cache: paths };
+                for path in paths.iter() {
+                    verts.extend(path.stroke().unwrap().iter().map(|vert| (vert.x, vert.y)));
+                }
+            }
+        }
+        verts
+    }
+
+    #[test]
+    fn test_replay_translated() {
+        let mut canvas = Canvas::new();
+        let mut list = DisplayList::new();
+        canvas.begin_path();
+        canvas.move_to(10.0, 10.0).line_to(50.0, 20.0);
+        canvas.stroke(&mut list);
+        canvas.begin_path();
+        canvas.rect(0.0, 0.0, 20.0, 20.0);
+        canvas.fill(&mut list);
+        assert_eq!(list.len(), 2);
+
+        let mut replayed = DisplayList::new();
+        list.replay(&mut replayed, 100.0, -5.0);
+        assert_eq!(replayed.len(), 2);
+
+        let original = stroke_verts(&list);
+        let moved = stroke_verts(&replayed);
+        assert!(!original.is_empty());
+        assert_eq!(original.len(), moved.len());
+        for (a, b) in original.iter().zip(moved.iter()) {
+            assert_eq!((a.0 + 100.0, a.1 - 5.0), *b);
+        }
+
+        if let DisplayCommand::Fill { bounds, paint, .. } = &replayed.commands[1] {
+            assert_eq!(*bounds, Rect::new(100.0, -5.0, 20.0, 20.0));
+            assert_eq!([paint.transform.e[4], paint.transform.e[5]], [100.0, -5.0]);
+        } else {
+            panic!("expected a fill");
+        }
+    }
+}
@@ -0,0 +1,355 @@
+use std::ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign};
+
+use crate::*;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Point {
+    pub x: Scalar,
+    pub y: Scalar,
+}
+
+impl Point {
+    pub fn new(x: Scalar, y: Scalar) -> Point {
+        Point { x, y }
+    }
+
+    pub fn origin() -> Point {
+        Point::default()
+    }
+
+    /// Returns the vector from the origin to this point.
+    pub fn to_vector(self) -> Vector {
+        Vector::new(self.x, self.y)
+    }
+
+    pub fn transform(self, transform: &Transform) -> Point {
+        let (x, y) = transform.transform_point(self.x, self.y);
+        Point::new(x, y)
+    }
+
+    pub fn lerp(self, other: Point, t: Scalar) -> Point {
+        self + (other - self) * t
+    }
+}
+
+impl Add<Vector> for Point {
+    type Output = Point;
+
+    fn add(self, rhs: Vector) -> Point {
+        Point::new(self.x + rhs.x, self.y + rhs.y)
+    }
+}
+
+impl AddAssign<Vector> for Point {
+    fn add_assign(&mut self, rhs: Vector) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub<Vector> for Point {
+    type Output = Point;
+
+    fn sub(self, rhs: Vector) -> Point {
+        Point::new(self.x - rhs.x, self.y - rhs.y)
+    }
+}
+
+impl SubAssign<Vector> for Point {
+    fn sub_assign(&mut self, rhs: Vector) {
+        *self = *self - rhs;
+    }
+}
+
+impl Sub<Point> for Point {
+    type Output = Vector;
+
+    fn sub(self, rhs: Point) -> Vector {
+        Vector::new(self.x - rhs.x, self.y - rhs.y)
+    }
+}
+
+/// A displacement, unlike `Point` it isn't moved by the translation of a transform.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Vector {
+    pub x: Scalar,
+    pub y: Scalar,
+}
+
+impl Vector {
+    pub fn new(x: Scalar, y: Scalar) -> Vector {
+        Vector { x, y }
+    }
+
+    pub fn zero() -> Vector {
+        Vector::default()
+    }
+
+    pub fn length(self) -> Scalar {
+        self.dot(self).sqrt()
+    }
+
+    pub fn dot(self, other: Vector) -> Scalar {
+        self.x * other.x + self.y * other.y
+    }
+
+    /// Returns the z component of the cross product, positive when `other` turns clockwise
+    /// from `self` with the y axis pointing down.
+    pub fn cross(self, other: Vector) -> Scalar {
+        self.x * other.y - self.y * other.x
+    }
+
+    /// Returns the vector scaled to a length of 1, or unchanged when it has no length.
+    pub fn normalize(self) -> Vector {
+        let (x, y, _) = normalize(self.x, self.y);
+        Vector::new(x, y)
+    }
+
+    pub fn transform(self, transform: &Transform) -> Vector {
+        let (x, y) = transform.transform_vector(self.x, self.y);
+        Vector::new(x, y)
+    }
+}
+
+impl Add for Vector {
+    type Output = Vector;
+
+    fn add(self, rhs: Vector) -> Vector {
+        Vector::new(self.x + rhs.x, self.y + rhs.y)
+    }
+}
+
+impl Sub for Vector {
+    type Output = Vector;
+
+    fn sub(self, rhs: Vector) -> Vector {
+        Vector::new(self.x - rhs.x, self.y - rhs.y)
+    }
+}
+
+impl Mul<Scalar> for Vector {
+    type Output = Vector;
+
+    fn mul(self, rhs: Scalar) -> Vector {
+        Vector::new(self.x * rhs, self.y * rhs)
+    }
+}
+
+impl Neg for Vector {
+    type Output = Vector;
+
+    fn neg(self) -> Vector {
+        Vector::new(-self.x, -self.y)
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Size {
+    pub width: Scalar,
+    pub height: Scalar,
+}
+
+impl Size {
+    pub fn new(width: Scalar, height: Scalar) -> Size {
+        Size { width, height }
+    }
+
+    pub fn zero() -> Size {
+        Size::default()
+    }
+
+    /// Returns whether the size has no area, which includes negative sizes.
+    pub fn is_empty(self) -> bool {
+        !(self.width > 0.0 && self.height > 0.0)
+    }
+
+    pub fn area(self) -> Scalar {
+        self.width * self.height
+    }
+
+    pub fn to_vector(self) -> Vector {
+        Vector::new(self.width, self.height)
+    }
+}
+
+/// Axis aligned rectangle from `origin` at the top-left to `origin + size`.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Rect {
+    pub origin: Point,
+    pub size: Size,
+}
+
+impl Rect {
+    pub fn new(x: Scalar, y: Scalar, width: Scalar, height: Scalar) -> Rect {
+        Rect {
+            origin: Point::new(x, y),
+            size: Size::new(width, height),
+        }
+    }
+
+    pub fn from_origin_size(origin: Point, size: Size) -> Rect {
+        Rect { origin, size }
+    }
+
+    /// Returns the smallest rectangle containing both points.
+    pub fn from_points(a: Point, b: Point) -> Rect {
+        Rect::from_min_max(a.x.min(b.x), a.y.min(b.y), a.x.max(b.x), a.y.max(b.y))
+    }
+
+    pub fn from_min_max(min_x: Scalar, min_y: Scalar, max_x: Scalar, max_y: Scalar) -> Rect {
+        Rect::new(min_x, min_y, max_x - min_x, max_y - min_y)
+    }
+
+    pub fn zero() -> Rect {
+        Rect::default()
+    }
+
+    pub fn min_x(&self) -> Scalar {
+        self.origin.x
+    }
+
+    pub fn min_y(&self) -> Scalar {
+        self.origin.y
+    }
+
+    pub fn max_x(&self) -> Scalar {
+        self.origin.x + self.size.width
+    }
+
+    pub fn max_y(&self) -> Scalar {
+        self.origin.y + self.size.height
+    }
+
+    pub fn min(&self) -> Point {
+        self.origin
+    }
+
+    pub fn max(&self) -> Point {
+        Point::new(self.max_x(), self.max_y())
+    }
+
+    pub fn center(&self) -> Point {
+        self.origin + self.size.to_vector() * 0.5
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size.is_empty()
+    }
+
+    /// Returns whether `point` is inside, including the top and left edges but not the bottom
+    /// and right ones, so that adjacent rectangles don't both contain a point.
+    pub fn contains(&self, point: Point) -> bool {
+        point.x >= self.min_x() && point.x < self.max_x() && point.y >= self.min_y() && point.y < self.max_y()
+    }
+
+    /// Returns whether `other` is entirely inside. Empty rectangles aren't inside any.
+    pub fn contains_rect(&self, other: &Rect) -> bool {
+        !other.is_empty()
+            && other.min_x() >= self.min_x() && other.max_x() <= self.max_x()
+            && other.min_y() >= self.min_y() && other.max_y() <= self.max_y()
+    }
+
+    pub fn intersects(&self, other: &Rect) -> bool {
+        self.intersection(other).is_some()
+    }
+
+    /// Returns the overlap of both rectangles, if it has an area.
+    pub fn intersection(&self, other: &Rect) -> Option<Rect> {
+        let rect = Rect::from_min_max(
+            self.min_x().max(other.min_x()),
+            self.min_y().max(other.min_y()),
+            self.max_x().min(other.max_x()),
+            self.max_y().min(other.max_y()),
+        );
+        if rect.is_empty() {
+            None
+        } else {
+            Some(rect)
+        }
+    }
+
+    /// Returns the smallest rectangle containing both, ignoring empty rectangles.
+    pub fn union(&self, other: &Rect) -> Rect {
+        if other.is_empty() {
+            return *self;
+        }
+        if self.is_empty() {
+            return *other;
+        }
+        Rect::from_min_max(
+            self.min_x().min(other.min_x()),
+            self.min_y().min(other.min_y()),
+            self.max_x().max(other.max_x()),
+            self.max_y().max(other.max_y()),
+        )
+    }
+
+    /// Grows the rectangle by `dx` on the left and the right, and `dy` on the top and the
+    /// bottom. Negative values shrink it.
+    pub fn inflate(&self, dx: Scalar, dy: Scalar) -> Rect {
+        Rect::new(self.origin.x - dx, self.origin.y - dy, self.size.width + dx * 2.0, self.size.height + dy * 2.0)
+    }
+
+    pub fn translate(&self, offset: Vector) -> Rect {
+        Rect::from_origin_size(self.origin + offset, self.size)
+    }
+
+    /// Returns the bounds of the transformed corners.
+    pub fn transform(&self, transform: &Transform) -> Rect {
+        let corners = [
+            self.origin.transform(transform),
+            Point::new(self.max_x(), self.min_y()).transform(transform),
+            self.max().transform(transform),
+            Point::new(self.min_x(), self.max_y()).transform(transform),
+        ];
+        corners[1..].iter().fold(Rect::from_points(corners[0], corners[0]), |rect, &corner| {
+            Rect::from_min_max(rect.min_x().min(corner.x), rect.min_y().min(corner.y), rect.max_x().max(corner.x), rect.max_y().max(corner.y))
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_rect() {
+        let a = Rect::new(0.0, 0.0, 10.0, 10.0);
+        let b = Rect::new(5.0, -5.0, 10.0, 10.0);
+        assert_eq!(a.intersection(&b), Some(Rect::new(5.0, 0.0, 5.0, 5.0)));
+        assert_eq!(a.intersection(&Rect::new(10.0, 0.0, 5.0, 5.0)), None);
+        assert!(!a.intersects(&Rect::new(10.0, 0.0, 5.0, 5.0)));
+        assert_eq!(a.union(&b), Rect::new(0.0, -5.0, 15.0, 15.0));
+        assert_eq!(a.union(&Rect::new(100.0, 100.0, 0.0, 0.0)), a);
+        assert_eq!(Rect::zero().union(&b), b);
+
+        assert!(a.contains(Point::new(0.0, 0.0)));
+        assert!(!a.contains(Point::new(10.0, 5.0)));
+        assert!(a.contains_rect(&Rect::new(2.0, 2.0, 8.0, 8.0)));
+        assert!(!a.contains_rect(&b));
+
+        assert_eq!(a.inflate(1.0, 2.0), Rect::new(-1.0, -2.0, 12.0, 14.0));
+        assert!(a.inflate(-5.0, -5.0).is_empty());
+        assert_eq!(a.center(), Point::new(5.0, 5.0));
+        assert_eq!(a.translate(Vector::new(1.0, 2.0)).max(), Point::new(11.0, 12.0));
+        assert_eq!(Rect::from_points(Point::new(3.0, 1.0), Point::new(1.0, 4.0)), Rect::new(1.0, 1.0, 2.0, 3.0));
+
+        let t = Transform::rotate(PI * 0.5) * Transform::translate(100.0, 0.0);
+        let rotated = a.transform(&t);
+        assert!((rotated.min_x() - 90.0).abs() < 1e-4 && (rotated.max_y() - 10.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_point_vector() {
+        let p = Point::new(1.0, 2.0);
+        let q = Point::new(4.0, 6.0);
+        assert_eq!(q - p, Vector::new(3.0, 4.0));
+        assert_eq!((q - p).length(), 5.0);
+        assert_eq!(p + (q - p) * 2.0, Point::new(7.0, 10.0));
+        assert_eq!(p.lerp(q, 0.5), Point::new(2.5, 4.0));
+        assert_eq!(Vector::new(1.0, 0.0).cross(Vector::new(0.0, 1.0)), 1.0);
+        assert_eq!(Vector::new(0.0, 2.0).normalize(), Vector::new(0.0, 1.0));
+        assert_eq!(p.transform(&Transform::translate(1.0, 1.0)), Point::new(2.0, 3.0));
+        assert_eq!(Vector::new(1.0, 2.0).transform(&Transform::translate(1.0, 1.0)), Vector::new(1.0, 2.0));
+        assert!(Size::new(0.0, 5.0).is_empty());
+    }
+}
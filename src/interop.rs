@@ -0,0 +1,9 @@
+//! Conversions between rdraw types and the types of other math crates, each enabled by the
+//! cargo feature of the same name.
+
+#[cfg(feature = "euclid")]
+mod euclid;
+#[cfg(feature = "glam")]
+mod glam;
+#[cfg(feature = "mint")]
+mod mint;
@@ -0,0 +1,87 @@
+use euclid::{Point2D, Rect as EuclidRect, Size2D, Transform2D, Vector2D};
+
+use crate::*;
+
+impl<U> From<Point2D<Scalar, U>> for Point {
+    fn from(point: Point2D<Scalar, U>) -> Self {
+        Point::new(point.x, point.y)
+    }
+}
+
+impl<U> From<Point> for Point2D<Scalar, U> {
+    fn from(point: Point) -> Self {
+        Point2D::new(point.x, point.y)
+    }
+}
+
+impl<U> From<Vector2D<Scalar, U>> for Vector {
+    fn from(vector: Vector2D<Scalar, U>) -> Self {
+        Vector::new(vector.x, vector.y)
+    }
+}
+
+impl<U> From<Vector> for Vector2D<Scalar, U> {
+    fn from(vector: Vector) -> Self {
+        Vector2D::new(vector.x, vector.y)
+    }
+}
+
+impl<U> From<Size2D<Scalar, U>> for Size {
+    fn from(size: Size2D<Scalar, U>) -> Self {
+        Size::new(size.width, size.height)
+    }
+}
+
+impl<U> From<Size> for Size2D<Scalar, U> {
+    fn from(size: Size) -> Self {
+        Size2D::new(size.width, size.height)
+    }
+}
+
+impl<U> From<EuclidRect<Scalar, U>> for Rect {
+    fn from(rect: EuclidRect<Scalar, U>) -> Self {
+        Rect::from_origin_size(rect.origin.into(), rect.size.into())
+    }
+}
+
+impl<U> From<Rect> for EuclidRect<Scalar, U> {
+    fn from(rect: Rect) -> Self {
+        EuclidRect::new(rect.origin.into(), rect.size.into())
+    }
+}
+
+/// euclid uses row vectors, so `m31` and `m32` are the translation. Both crates compose in the
+/// same order: `a.then(&b)` and `a * b` apply `a` first.
+impl<Src, Dst> From<Transform2D<Scalar, Src, Dst>> for Transform {
+    fn from(m: Transform2D<Scalar, Src, Dst>) -> Self {
+        Transform { e: [m.m11, m.m12, m.m21, m.m22, m.m31, m.m32] }
+    }
+}
+
+impl<Src, Dst> From<Transform> for Transform2D<Scalar, Src, Dst> {
+    fn from(transform: Transform) -> Self {
+        let e = transform.e;
+        Transform2D::new(e[0], e[1], e[2], e[3], e[4], e[5])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use euclid::default;
+
+    use super::*;
+
+    #[test]
+    fn test_euclid() {
+        let m = default::Transform2D::rotation(euclid::Angle::radians(0.5)).then_translate(default::Vector2D::new(3.0, 4.0));
+        let t = Transform::from(m);
+        let p = default::Point2D::new(1.0, 2.0);
+        assert_eq!(Point::from(m.transform_point(p)), Point::from(p).transform(&t));
+        assert_eq!(default::Transform2D::from(t), m);
+
+        let rect = Rect::new(1.0, 2.0, 3.0, 4.0);
+        let converted: default::Rect<Scalar> = rect.into();
+        assert_eq!(converted.max(), default::Point2D::new(4.0, 6.0));
+        assert_eq!(Rect::from(converted), rect);
+    }
+}
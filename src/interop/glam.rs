@@ -0,0 +1,92 @@
+use glam::{Affine2, Mat2, Vec2, Vec4};
+
+use crate::*;
+
+impl From<Vec2> for Point {
+    fn from(point: Vec2) -> Self {
+        Point::new(point.x, point.y)
+    }
+}
+
+impl From<Point> for Vec2 {
+    fn from(point: Point) -> Self {
+        Vec2::new(point.x, point.y)
+    }
+}
+
+impl From<Vec2> for Vector {
+    fn from(vector: Vec2) -> Self {
+        Vector::new(vector.x, vector.y)
+    }
+}
+
+impl From<Vector> for Vec2 {
+    fn from(vector: Vector) -> Self {
+        Vec2::new(vector.x, vector.y)
+    }
+}
+
+impl From<Vec2> for Size {
+    fn from(size: Vec2) -> Self {
+        Size::new(size.x, size.y)
+    }
+}
+
+impl From<Size> for Vec2 {
+    fn from(size: Size) -> Self {
+        Vec2::new(size.width, size.height)
+    }
+}
+
+/// Note that the composition orders differ: `a * b` applies `b` first for `Affine2`, but `a`
+/// first for `Transform`.
+impl From<Affine2> for Transform {
+    fn from(affine: Affine2) -> Self {
+        let Affine2 { matrix2, translation } = affine;
+        Transform {
+            e: [matrix2.x_axis.x, matrix2.x_axis.y, matrix2.y_axis.x, matrix2.y_axis.y, translation.x, translation.y],
+        }
+    }
+}
+
+impl From<Transform> for Affine2 {
+    fn from(transform: Transform) -> Self {
+        let e = transform.e;
+        Affine2::from_mat2_translation(Mat2::from_cols_array(&[e[0], e[1], e[2], e[3]]), Vec2::new(e[4], e[5]))
+    }
+}
+
+/// Components in the range [0, 1], in RGBA order.
+impl From<Vec4> for Color {
+    fn from(color: Vec4) -> Self {
+        color.to_array().into()
+    }
+}
+
+impl From<Color> for Vec4 {
+    fn from(color: Color) -> Self {
+        Vec4::from_array(color.into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_glam() {
+        let affine = Affine2::from_translation(Vec2::new(3.0, 4.0)) * Affine2::from_angle(0.5);
+        let t = Transform::from(affine);
+        let p = Vec2::new(1.0, 2.0);
+        let (x, y) = t.transform_point(p.x, p.y);
+        assert!(affine.transform_point2(p).abs_diff_eq(Vec2::new(x, y), 1e-5));
+        assert_eq!(Affine2::from(t), affine);
+
+        let mut canvas = Canvas::new();
+        canvas.set_transform(affine);
+        assert_eq!(canvas.current_transform(), t);
+
+        assert_eq!(Vector::from(Vec2::from(Vector::new(1.0, 2.0))), Vector::new(1.0, 2.0));
+        assert_eq!(Color::from(Vec4::new(1.0, 0.0, 0.2, 0.4)), Color::rgba(255, 0, 51, 102));
+    }
+}
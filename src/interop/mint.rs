@@ -0,0 +1,108 @@
+use mint::{ColumnMatrix2x3, Point2, RowMatrix3x2, Vector2, Vector4};
+
+use crate::*;
+
+impl From<Point2<Scalar>> for Point {
+    fn from(point: Point2<Scalar>) -> Self {
+        Point::new(point.x, point.y)
+    }
+}
+
+impl From<Point> for Point2<Scalar> {
+    fn from(point: Point) -> Self {
+        Point2 { x: point.x, y: point.y }
+    }
+}
+
+impl From<Vector2<Scalar>> for Vector {
+    fn from(vector: Vector2<Scalar>) -> Self {
+        Vector::new(vector.x, vector.y)
+    }
+}
+
+impl From<Vector> for Vector2<Scalar> {
+    fn from(vector: Vector) -> Self {
+        Vector2 { x: vector.x, y: vector.y }
+    }
+}
+
+impl From<Vector2<Scalar>> for Size {
+    fn from(size: Vector2<Scalar>) -> Self {
+        Size::new(size.x, size.y)
+    }
+}
+
+impl From<Size> for Vector2<Scalar> {
+    fn from(size: Size) -> Self {
+        Vector2 { x: size.width, y: size.height }
+    }
+}
+
+/// The columns are the images of the x and y axes followed by the translation.
+impl From<ColumnMatrix2x3<Scalar>> for Transform {
+    fn from(m: ColumnMatrix2x3<Scalar>) -> Self {
+        Transform { e: [m.x.x, m.x.y, m.y.x, m.y.y, m.z.x, m.z.y] }
+    }
+}
+
+impl From<Transform> for ColumnMatrix2x3<Scalar> {
+    fn from(transform: Transform) -> Self {
+        let e = transform.e;
+        ColumnMatrix2x3 {
+            x: Vector2 { x: e[0], y: e[1] },
+            y: Vector2 { x: e[2], y: e[3] },
+            z: Vector2 { x: e[4], y: e[5] },
+        }
+    }
+}
+
+/// For row vectors multiplied on the left, the rows are the images of the x and y axes
+/// followed by the translation.
+impl From<RowMatrix3x2<Scalar>> for Transform {
+    fn from(m: RowMatrix3x2<Scalar>) -> Self {
+        Transform { e: [m.x.x, m.x.y, m.y.x, m.y.y, m.z.x, m.z.y] }
+    }
+}
+
+impl From<Transform> for RowMatrix3x2<Scalar> {
+    fn from(transform: Transform) -> Self {
+        let e = transform.e;
+        RowMatrix3x2 {
+            x: Vector2 { x: e[0], y: e[1] },
+            y: Vector2 { x: e[2], y: e[3] },
+            z: Vector2 { x: e[4], y: e[5] },
+        }
+    }
+}
+
+/// Components in the range [0, 1], in RGBA order.
+impl From<Vector4<Scalar>> for Color {
+    fn from(color: Vector4<Scalar>) -> Self {
+        Color::rgbaf(color.x, color.y, color.z, color.w)
+    }
+}
+
+impl From<Color> for Vector4<Scalar> {
+    fn from(color: Color) -> Self {
+        let [x, y, z, w]: [Scalar; 4] = color.into();
+        Vector4 { x, y, z, w }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_mint() {
+        let t = Transform::scale(2.0, 3.0) * Transform::translate(10.0, 20.0);
+        let m: ColumnMatrix2x3<Scalar> = t.into();
+        assert_eq!(m.z, Vector2 { x: 10.0, y: 20.0 });
+        assert_eq!(Transform::from(m), t);
+        assert_eq!(Transform::from(RowMatrix3x2::from(t)), t);
+
+        let p: Point2<Scalar> = Point::new(1.0, 2.0).into();
+        assert_eq!(Point::from(p), Point::new(1.0, 2.0));
+        assert_eq!(Color::from(Vector4::from(Color::rgba(255, 0, 51, 102))), Color::rgba(255, 0, 51, 102));
+    }
+}
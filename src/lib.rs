@@ -1,12 +1,61 @@
+#![allow(clippy::too_many_arguments)]
+
+mod color;
+mod command_stream;
+mod display_list;
+mod geometry;
+mod interop;
+mod pdf;
+mod remote;
 mod renderer;
 
-use std::ops::Mul;
-
+use std::{
+    ops::Mul,
+    time::{Duration, Instant},
+};
+
+pub use color::*;
+pub use command_stream::*;
+pub use display_list::*;
+pub use geometry::*;
+pub use pdf::*;
+pub use remote::*;
 pub use renderer::gl::*;
 
 pub trait CanvasRenderer {
-    fn stroke(&mut self, paint: &Paint, scissor: &Scissor, fringe: Scalar, line_width: Scalar, paths: Paths);
-    fn fill(&mut self, paint: &Paint, scissor: &Scissor, fringe: Scalar, bounds: [Scalar; 4], paths: Paths);
+    fn stroke(&mut self, paint: &Paint, composite: &CompositeState, scissor: &Scissor, clip_depth: usize, fringe: Scalar, line_width: Scalar, paths: Paths);
+    fn fill(&mut self, paint: &Paint, composite: &CompositeState, scissor: &Scissor, clip_depth: usize, fringe: Scalar, bounds: Rect, paths: Paths);
+    /// Intersects the fill of `paths` with the clip paths below `depth`, to be used by
+    /// calls with a larger `clip_depth`.
+    fn clip(&mut self, depth: usize, paths: Paths);
+    /// Redirects the following calls into a new layer until the matching `pop_layer`.
+    fn push_layer(&mut self, opacity: Scalar, composite: &CompositeState, clip_depth: usize);
+    /// Composites the innermost layer onto its parent as a whole.
+    fn pop_layer(&mut self);
+    /// Statistics of the frame being recorded, which `Canvas` adds its tessellation work to.
+    fn frame_stats_mut(&mut self) -> Option<&mut FrameStats> {
+        None
+    }
+}
+
+/// Counters of the work done for a frame, to find out why it is slow.
+#[derive(Clone, Debug, Default)]
+pub struct FrameStats {
+    pub fill_calls: usize,
+    pub stroke_calls: usize,
+    pub layer_calls: usize,
+    /// GL draws saved by merging draw calls.
+    pub draws_saved: usize,
+    pub vertices: usize,
+    pub uniform_bytes: usize,
+    /// Passes writing the stencil buffer, for fills and clips.
+    pub stencil_passes: usize,
+    pub paths: usize,
+    /// Points of the paths after flattening curves.
+    pub points: usize,
+    pub flatten_time: Duration,
+    pub expand_stroke_time: Duration,
+    pub expand_fill_time: Duration,
 }
 
 #[derive(Clone)]
@@ -24,6 +73,51 @@ pub struct Paint {
     pub inner_color: [f32; 4],
     pub outer_color: [f32; 4],
     pub image: i32,
+    /// Color stops sorted by offset for gradients with more than two colors. Empty when the
+    /// gradient only goes from `inner_color` to `outer_color`.
+    pub stops: Vec<(Scalar, [f32; 4])>,
+    pub shape: GradientShape,
+    pub spread: SpreadMode,
+}
+
+/// Color space in which renderers blend colors.
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub enum ColorSpace {
+    /// Blend the sRGB encoded values directly, which is what most 2D APIs do.
+    Srgb,
+    /// Convert colors to linear light and blend into an sRGB framebuffer, which avoids dark
+    /// fringes and muddy gradient midpoints.
+    LinearSrgb,
+}
+
+/// Color space in which gradient colors are interpolated.
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub enum GradientInterpolation {
+    /// Interpolate in the color space used for blending.
+    ColorSpace,
+    /// Interpolate in the perceptual OKLab color space.
+    Oklab,
+}
+
+/// How gradients are extended past their end offsets.
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub enum SpreadMode {
+    /// Use the end colors.
+    Pad,
+    /// Restart the gradient from its start.
+    Repeat,
+    /// Mirror the gradient back and forth.
+    Reflect,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum GradientShape {
+    /// Distance to the rounded rect described by `extent`, `radius` and `feather`, which is how
+    /// linear and radial gradients are expressed.
+    Box,
+    /// Angle around the origin of the paint transform, from `start_angle` at offset 0 to
+    /// `end_angle` at offset 1.
+    Sweep { start_angle: Scalar, end_angle: Scalar },
 }
 
 impl Paint {
@@ -36,6 +130,9 @@ impl Paint {
             inner_color: color,
             outer_color: color,
             image: 0,
+            stops: Vec::new(),
+            shape: GradientShape::Box,
+            spread: SpreadMode::Pad,
         }
     }
 }
@@ -45,7 +142,7 @@ pub struct Paths<'a> {
 }
 
 impl<'a> Paths<'a> {
-    pub fn iter(&self) -> PathIter {
+    pub fn iter(&self) -> PathIter<'_> {
         PathIter {
             cache: self.cache,
             index: 0,
@@ -53,20 +150,6 @@ impl<'a> Paths<'a> {
     }
 }
 
-#[derive(Copy, Clone)]
-pub struct Color {
-    pub r: u8,
-    pub g: u8,
-    pub b: u8,
-    pub a: u8,
-}
-
-impl Color {
-    pub fn rgba(r: u8, g: u8, b: u8, a: u8) -> Color {
-        Color { r, g, b, a }
-    }
-}
-
 pub struct PathIter<'a> {
     cache: &'a PathCache,
     index: usize,
@@ -143,15 +226,110 @@ pub enum LineJoin {
     Miter,
 }
 
+/// Porter-Duff composite operations, named after the HTML5 canvas ones.
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub enum CompositeOp {
+    SourceOver,
+    SourceIn,
+    SourceOut,
+    Atop,
+    DestinationOver,
+    DestinationIn,
+    DestinationOut,
+    DestinationAtop,
+    Lighter,
+    Copy,
+    Xor,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub enum BlendFactor {
+    Zero,
+    One,
+    SrcColor,
+    OneMinusSrcColor,
+    DstColor,
+    OneMinusDstColor,
+    SrcAlpha,
+    OneMinusSrcAlpha,
+    DstAlpha,
+    OneMinusDstAlpha,
+    SrcAlphaSaturate,
+}
+
+/// Separable blend modes, with the same formulas as CSS `mix-blend-mode`.
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub enum BlendMode {
+    Normal,
+    Multiply,
+    Screen,
+    Overlay,
+    Darken,
+    Lighten,
+    ColorDodge,
+    ColorBurn,
+    HardLight,
+    SoftLight,
+    Difference,
+    Exclusion,
+}
+
+/// Blend factors applied to premultiplied colors when a path is drawn.
+///
+/// `blend_mode` mixes the source with the backdrop before the blend factors are applied.
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub struct CompositeState {
+    pub src_rgb: BlendFactor,
+    pub dst_rgb: BlendFactor,
+    pub src_alpha: BlendFactor,
+    pub dst_alpha: BlendFactor,
+    pub blend_mode: BlendMode,
+}
+
+impl CompositeState {
+    pub fn from_op(op: CompositeOp) -> Self {
+        let (src, dst) = match op {
+            CompositeOp::SourceOver => (BlendFactor::One, BlendFactor::OneMinusSrcAlpha),
+            CompositeOp::SourceIn => (BlendFactor::DstAlpha, BlendFactor::Zero),
+            CompositeOp::SourceOut => (BlendFactor::OneMinusDstAlpha, BlendFactor::Zero),
+            CompositeOp::Atop => (BlendFactor::DstAlpha, BlendFactor::OneMinusSrcAlpha),
+            CompositeOp::DestinationOver => (BlendFactor::OneMinusDstAlpha, BlendFactor::One),
+            CompositeOp::DestinationIn => (BlendFactor::Zero, BlendFactor::SrcAlpha),
+            CompositeOp::DestinationOut => (BlendFactor::Zero, BlendFactor::OneMinusSrcAlpha),
+            CompositeOp::DestinationAtop => (BlendFactor::OneMinusDstAlpha, BlendFactor::SrcAlpha),
+            CompositeOp::Lighter => (BlendFactor::One, BlendFactor::One),
+            CompositeOp::Copy => (BlendFactor::One, BlendFactor::Zero),
+            CompositeOp::Xor => (BlendFactor::OneMinusDstAlpha, BlendFactor::OneMinusSrcAlpha),
+        };
+
+        CompositeState {
+            src_rgb: src,
+            dst_rgb: dst,
+            src_alpha: src,
+            dst_alpha: dst,
+            blend_mode: BlendMode::Normal,
+        }
+    }
+}
+
 pub struct Canvas {
     // TODO: Use a more memory efficient way to store commands?
     commands: Vec<Command>,
     state: State,
+    saved_states: Vec<State>,
     cache: PathCache,
     pixels_per_point: Scalar,
+    tessellation_tolerance: Scalar,
     tess_tol: Scalar,
     dist_tol: Scalar,
     fringe: Scalar,
+    capture: Option<CommandWriter>,
+}
+
+impl Default for Canvas {
+    fn default() -> Self {
+        Canvas::new()
+    }
 }
 
 impl Canvas {
@@ -159,11 +337,14 @@ impl Canvas {
         let mut canvas = Canvas {
             commands: Vec::new(),
             state: State::default(),
+            saved_states: Vec::new(),
             cache: PathCache::new(),
             pixels_per_point: 0.0,
+            tessellation_tolerance: 0.25,
             tess_tol: 0.0,
             dist_tol: 0.0,
             fringe: 0.0,
+            capture: None,
         };
 
         canvas.set_pixels_per_point(1.0);
@@ -173,13 +354,58 @@ impl Canvas {
 
     pub fn reset(&mut self) {
         self.state = State::default();
+        self.saved_states.clear();
+    }
+
+    pub fn save(&mut self) {
+        self.saved_states.push(self.state.clone());
+    }
+
+    pub fn restore(&mut self) {
+        if let Some(state) = self.saved_states.pop() {
+            self.state = state;
+        }
+    }
+
+    /// Starts recording the draws into a `CommandWriter`, which `end_capture` returns.
+    pub fn begin_capture(&mut self) {
+        self.capture = Some(CommandWriter::new());
+    }
+
+    pub fn end_capture(&mut self) -> Option<CommandWriter> {
+        self.capture.take()
     }
 
     pub fn set_pixels_per_point(&mut self, pixels_per_point: Scalar) {
         self.pixels_per_point = pixels_per_point;
-        self.tess_tol = 0.25 / pixels_per_point;
+        self.tess_tol = self.tessellation_tolerance / pixels_per_point;
         self.dist_tol = 0.01 / pixels_per_point;
         self.fringe = 1.0 / pixels_per_point;
+        // The tolerances changed, so the current path has to be flattened again.
+        self.cache.clear();
+    }
+
+    /// Sets the most curves and round joins may deviate from their flattened lines, in pixels.
+    /// Larger tolerances tessellate faster into fewer vertices. The default is 0.25.
+    pub fn set_tessellation_tolerance(&mut self, tolerance: Scalar) {
+        // Keep the tolerance from making the number of lines explode.
+        self.tessellation_tolerance = tolerance.max(1e-3);
+        self.set_pixels_per_point(self.pixels_per_point);
+    }
+
+    /// Sets the transform applied to the points of the paths added after it, to paints set
+    /// after it and to the stroke width. Accepts the `Transform` conversions of the interop
+    /// features, such as `glam::Affine2`.
+    pub fn set_transform(&mut self, transform: impl Into<Transform>) {
+        self.state.transform = transform.into();
+    }
+
+    pub fn reset_transform(&mut self) {
+        self.state.transform = Transform::identity();
+    }
+
+    pub fn current_transform(&self) -> Transform {
+        self.state.transform
     }
 
     pub fn set_stroke_width(&mut self, line_width: Scalar) {
@@ -195,24 +421,57 @@ impl Canvas {
     }
 
     pub fn set_stroke_color(&mut self, color: Color) {
-        self.state.stroke = Paint::color(self.convert_color(color));
-    }
-
-    fn convert_color(&self, color: Color) -> [f32; 4] {
-        [
-            color.r as f32 / 255.0,
-            color.g as f32 / 255.0,
-            color.b as f32 / 255.0,
-            color.a as f32 / 255.0,
-        ]
+        self.state.stroke = Paint::color(color.into());
     }
 
     pub fn set_shape_anti_alias(&mut self, enabled: bool) {
         self.state.shape_anti_alias = enabled;
     }
 
+    /// Limits the following draws to the `w` by `h` rectangle at `(x, y)`, transformed by the
+    /// current transform. The scissor is part of the state, so it is undone by `restore`.
+    pub fn scissor(&mut self, x: Scalar, y: Scalar, w: Scalar, h: Scalar) {
+        let w = w.max(0.0);
+        let h = h.max(0.0);
+        self.state.scissor = Scissor {
+            transform: Transform::translate(x + w * 0.5, y + h * 0.5) * self.state.transform,
+            extent: [w * 0.5, h * 0.5],
+        };
+    }
+
+    pub fn reset_scissor(&mut self) {
+        self.state.scissor = Scissor {
+            transform: Transform::identity(),
+            extent: [-1.0; 2],
+        };
+    }
+
+    pub fn global_composite_operation(&mut self, op: CompositeOp) {
+        self.state.composite = CompositeState {
+            blend_mode: self.state.composite.blend_mode,
+            ..CompositeState::from_op(op)
+        };
+    }
+
+    pub fn global_composite_blend_func(&mut self, sfactor: BlendFactor, dfactor: BlendFactor) {
+        self.global_composite_blend_func_separate(sfactor, dfactor, sfactor, dfactor);
+    }
+
+    pub fn global_composite_blend_func_separate(&mut self, src_rgb: BlendFactor, dst_rgb: BlendFactor, src_alpha: BlendFactor, dst_alpha: BlendFactor) {
+        self.state.composite = CompositeState {
+            src_rgb,
+            dst_rgb,
+            src_alpha,
+            dst_alpha,
+            blend_mode: self.state.composite.blend_mode,
+        };
+    }
+
+    pub fn set_blend_mode(&mut self, blend_mode: BlendMode) {
+        self.state.composite.blend_mode = blend_mode;
+    }
+
     pub fn linear_gradient(&self, sx: Scalar, sy: Scalar, ex: Scalar, ey: Scalar, inner_col: Color, outer_col: Color) -> Paint {
-        const LARGE: Scalar = 1e5;
         // Calculate transform aligned to the line
         let mut dx = ex - sx;
         let mut dy = ey - sy;
@@ -230,65 +489,176 @@ impl Canvas {
                 e: [
                     dy, -dx,
                     dx, dy,
-                    sx - dx * LARGE, sy - dy * LARGE,
+                    sx - dx * LINEAR_GRADIENT_EXTENT, sy - dy * LINEAR_GRADIENT_EXTENT,
                 ]
             },
-            extent: [LARGE, LARGE + d * 0.5],
+            extent: [LINEAR_GRADIENT_EXTENT, LINEAR_GRADIENT_EXTENT + d * 0.5],
             radius: 0.0,
             feather: d.max(1.0),
-            inner_color: self.convert_color(inner_col),
-            outer_color: self.convert_color(outer_col),
+            inner_color: inner_col.into(),
+            outer_color: outer_col.into(),
             image: 0,
+            stops: Vec::new(),
+            shape: GradientShape::Box,
+            spread: SpreadMode::Pad,
         }
     }
 
+    pub fn linear_gradient_p(&self, start: Point, end: Point, inner_col: Color, outer_col: Color) -> Paint {
+        self.linear_gradient(start.x, start.y, end.x, end.y, inner_col, outer_col)
+    }
+
     pub fn radial_gradient(&self, cx: Scalar, cy: Scalar, inr: Scalar, outr: Scalar, icol: Color, ocol: Color) -> Paint {
         let r = (inr + outr) * 0.5;
         let f = outr - inr;
 
-        let mut t = Transform::identity();
-        t.e[4] = cx;
-        t.e[5] = cy;
-
         Paint {
-            transform: t,
+            transform: Transform::translate(cx, cy),
             extent: [r, r],
             radius: r,
             feather: f.max(1.0),
-            inner_color: self.convert_color(icol),
-            outer_color: self.convert_color(ocol),
+            inner_color: icol.into(),
+            outer_color: ocol.into(),
+            image: 0,
+            stops: Vec::new(),
+            shape: GradientShape::Box,
+            spread: SpreadMode::Pad,
+        }
+    }
+
+    /// Creates a linear gradient from `(sx, sy)` to `(ex, ey)` through `stops`, each one being
+    /// an offset between 0 and 1 along the line and its color.
+    pub fn linear_gradient_stops(&self, sx: Scalar, sy: Scalar, ex: Scalar, ey: Scalar, stops: &[(Scalar, Color)]) -> Paint {
+        let (first, last) = self.end_colors(stops);
+        let mut paint = self.linear_gradient(sx, sy, ex, ey, first, last);
+        paint.stops = self.convert_stops(stops);
+        paint
+    }
+
+    /// Creates a radial gradient through `stops`, where offset 0 is at the inner radius and
+    /// offset 1 at the outer radius.
+    pub fn radial_gradient_stops(&self, cx: Scalar, cy: Scalar, inr: Scalar, outr: Scalar, stops: &[(Scalar, Color)]) -> Paint {
+        let (first, last) = self.end_colors(stops);
+        let mut paint = self.radial_gradient(cx, cy, inr, outr, first, last);
+        paint.stops = self.convert_stops(stops);
+        paint
+    }
+
+    /// Creates a sweep gradient around `(cx, cy)` going from `start_angle` to `end_angle` in
+    /// radians through `stops`.
+    pub fn sweep_gradient(&self, cx: Scalar, cy: Scalar, start_angle: Scalar, end_angle: Scalar, stops: &[(Scalar, Color)]) -> Paint {
+        let (first, last) = self.end_colors(stops);
+
+        Paint {
+            transform: Transform::translate(cx, cy),
+            extent: [0.0, 0.0],
+            radius: 0.0,
+            feather: 1.0,
+            inner_color: first.into(),
+            outer_color: last.into(),
             image: 0,
+            stops: self.convert_stops(stops),
+            shape: GradientShape::Sweep { start_angle, end_angle },
+            spread: SpreadMode::Pad,
         }
     }
 
+    fn end_colors(&self, stops: &[(Scalar, Color)]) -> (Color, Color) {
+        let transparent = Color::rgba(0, 0, 0, 0);
+        let first = stops.iter().min_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+        let last = stops.iter().max_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+        (first.map(|stop| stop.1).unwrap_or(transparent), last.map(|stop| stop.1).unwrap_or(transparent))
+    }
+
+    fn convert_stops(&self, stops: &[(Scalar, Color)]) -> Vec<(Scalar, [f32; 4])> {
+        // Two stops at the ends are exactly what inner_color and outer_color already describe.
+        if stops.len() < 2 || (stops.len() == 2 && stops[0].0 <= 0.0 && stops[1].0 >= 1.0) {
+            return Vec::new();
+        }
+
+        let mut stops = stops.iter()
+            .map(|&(offset, color)| (clamp(offset, 0.0, 1.0), color.into()))
+            .collect::<Vec<_>>();
+        stops.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+        stops
+    }
+
+    /// Creates an image paint where `(ox, oy)` is the top-left of the image and `(ex, ey)` its
+    /// size, rotated by `angle` radians around the top-left.
+    pub fn image_pattern(&self, ox: Scalar, oy: Scalar, ex: Scalar, ey: Scalar, angle: Scalar, image: i32, alpha: Scalar) -> Paint {
+        Paint {
+            transform: Transform::rotate(angle) * Transform::translate(ox, oy),
+            extent: [ex, ey],
+            radius: 0.0,
+            feather: 0.0,
+            inner_color: [1.0, 1.0, 1.0, alpha],
+            outer_color: [1.0, 1.0, 1.0, alpha],
+            image,
+            stops: Vec::new(),
+            shape: GradientShape::Box,
+            spread: SpreadMode::Pad,
+        }
+    }
+
+    /// Creates an image paint filling `rect` with the image, rotated by `angle` radians around
+    /// its top-left.
+    pub fn image_pattern_r(&self, rect: Rect, angle: Scalar, image: i32, alpha: Scalar) -> Paint {
+        self.image_pattern(rect.origin.x, rect.origin.y, rect.size.width, rect.size.height, angle, image, alpha)
+    }
+
     pub fn begin_path(&mut self) -> &mut Self {
         self.commands.clear();
         self.cache.clear();
         self
     }
 
+    /// Adds a command to the current path with its points transformed, dropping the paths
+    /// flattened before it.
+    fn append_command(&mut self, command: Command) {
+        let t = &self.state.transform;
+        let command = match command {
+            Command::MoveTo(x, y) => {
+                let (x, y) = t.transform_point(x, y);
+                Command::MoveTo(x, y)
+            }
+            Command::LineTo(x, y) => {
+                let (x, y) = t.transform_point(x, y);
+                Command::LineTo(x, y)
+            }
+            Command::BezierTo(cp1x, cp1y, cp2x, cp2y, x, y) => {
+                let (cp1x, cp1y) = t.transform_point(cp1x, cp1y);
+                let (cp2x, cp2y) = t.transform_point(cp2x, cp2y);
+                let (x, y) = t.transform_point(x, y);
+                Command::BezierTo(cp1x, cp1y, cp2x, cp2y, x, y)
+            }
+            command => command,
+        };
+        self.commands.push(command);
+        self.cache.clear();
+    }
+
     pub fn move_to(&mut self, x: Scalar, y: Scalar) -> &mut Self {
-        self.commands.push(Command::MoveTo(x, y));
+        self.append_command(Command::MoveTo(x, y));
         self
     }
 
     pub fn line_to(&mut self, x: Scalar, y: Scalar) -> &mut Self {
-        self.commands.push(Command::LineTo(x, y));
+        self.append_command(Command::LineTo(x, y));
         self
     }
 
     pub fn bezier_to(&mut self, cp1x: Scalar, cp1y: Scalar, cp2x: Scalar, cp2y: Scalar, x: Scalar, y: Scalar) -> &mut Self {
-        self.commands.push(Command::BezierTo(cp1x, cp1y, cp2x, cp2y, x, y));
+        self.append_command(Command::BezierTo(cp1x, cp1y, cp2x, cp2y, x, y));
         self
     }
 
     pub fn close_path(&mut self) -> &mut Self {
-        self.commands.push(Command::Close);
+        self.append_command(Command::Close);
         self
     }
 
     pub fn path_winding(&mut self, winding: Winding) -> &mut Self {
-        self.commands.push(Command::Winding(winding));
+        self.append_command(Command::Winding(winding));
         self
     }
 
@@ -301,7 +671,7 @@ impl Canvas {
     }
 
     pub fn ellipse(&mut self, cx: Scalar, cy: Scalar, rx: Scalar, ry: Scalar) -> &mut Self {
-        const NVG_KAPPA90: Scalar = 0.5522847493;    // Length proportional to radius of a cubic bezier handle for 90deg arcs.
+        const NVG_KAPPA90: Scalar = 0.552_284_8;    // Length proportional to radius of a cubic bezier handle for 90deg arcs.
         self.move_to(cx - rx, cy)
             .bezier_to(cx - rx, cy + ry * NVG_KAPPA90, cx - rx * NVG_KAPPA90, cy + ry, cx, cy + ry)
             .bezier_to(cx + rx * NVG_KAPPA90, cy + ry, cx + rx, cy + ry * NVG_KAPPA90, cx + rx, cy)
@@ -314,11 +684,47 @@ impl Canvas {
         self.ellipse(cx, cy, r, r)
     }
 
+    pub fn move_to_p(&mut self, point: Point) -> &mut Self {
+        self.move_to(point.x, point.y)
+    }
+
+    pub fn line_to_p(&mut self, point: Point) -> &mut Self {
+        self.line_to(point.x, point.y)
+    }
+
+    pub fn bezier_to_p(&mut self, cp1: Point, cp2: Point, point: Point) -> &mut Self {
+        self.bezier_to(cp1.x, cp1.y, cp2.x, cp2.y, point.x, point.y)
+    }
+
+    pub fn rect_r(&mut self, rect: Rect) -> &mut Self {
+        self.rect(rect.origin.x, rect.origin.y, rect.size.width, rect.size.height)
+    }
+
+    /// Adds the ellipse inscribed in `rect`.
+    pub fn ellipse_r(&mut self, rect: Rect) -> &mut Self {
+        let center = rect.center();
+        self.ellipse(center.x, center.y, rect.size.width * 0.5, rect.size.height * 0.5)
+    }
+
+    pub fn circle_p(&mut self, center: Point, r: Scalar) -> &mut Self {
+        self.circle(center.x, center.y, r)
+    }
+
+    /// Returns the bounds of the current path, once flattened.
+    pub fn path_bounds(&mut self) -> Rect {
+        self.cache.flatten_paths(self.commands.iter(), self.tess_tol, self.dist_tol);
+        self.cache.bounds
+    }
+
     pub fn stroke<R>(&mut self, renderer: &mut R) where R: CanvasRenderer {
+        if let Some(capture) = &mut self.capture {
+            capture.record_stroke(&self.state, self.pixels_per_point, &self.commands);
+        }
+
         let mut state = self.state.clone();
         let stroke_paint = &mut state.stroke;
 
-        let scale = 1.0; // TODO: Get average scale from transform
+        let scale = state.transform.average_scale();
         let mut line_width = clamp(state.line_width * scale, 0.0, 200.0);
 
         if line_width < self.fringe {
@@ -332,7 +738,9 @@ impl Canvas {
 
         // TODO: Apply global alpha
 
+        let start = Instant::now();
         self.cache.flatten_paths(self.commands.iter(), self.tess_tol, self.dist_tol);
+        let flattened = Instant::now();
 
         let fringe = if state.shape_anti_alias {
             self.fringe
@@ -341,21 +749,34 @@ impl Canvas {
         };
         self.cache.expand_stroke(line_width * 0.5, fringe, state.line_cap, state.line_join, state.miter_limit, self.tess_tol);
 
-        renderer.stroke(&state.stroke, &state.scissor, fringe, state.line_width, Paths { cache: &self.cache });
+        if let Some(stats) = renderer.frame_stats_mut() {
+            stats.flatten_time += flattened - start;
+            stats.expand_stroke_time += flattened.elapsed();
+            self.cache.add_stats(stats);
+        }
+
+        renderer.stroke(&state.stroke, &state.composite, &state.scissor, state.clip_depth, fringe, line_width, Paths { cache: &self.cache });
     }
 
     pub fn set_fill_paint(&mut self, paint: &Paint) {
         self.state.fill = paint.clone();
+        self.state.fill.transform = paint.transform * self.state.transform;
     }
 
     pub fn set_fill_color(&mut self, color: Color) {
-        self.state.fill = Paint::color(self.convert_color(color));
+        self.state.fill = Paint::color(color.into());
     }
 
     pub fn fill<R>(&mut self, renderer: &mut R) where R: CanvasRenderer {
+        if let Some(capture) = &mut self.capture {
+            capture.record_fill(&self.state, self.pixels_per_point, &self.commands);
+        }
+
         let state = self.state.clone();
 
+        let start = Instant::now();
         self.cache.flatten_paths(self.commands.iter(), self.tess_tol, self.dist_tol);
+        let flattened = Instant::now();
 
         let fringe = if state.shape_anti_alias {
             self.fringe
@@ -365,9 +786,61 @@ impl Canvas {
 
         self.cache.expand_fill(self.fringe, fringe, LineJoin::Miter, 2.4);
 
+        if let Some(stats) = renderer.frame_stats_mut() {
+            stats.flatten_time += flattened - start;
+            stats.expand_fill_time += flattened.elapsed();
+            self.cache.add_stats(stats);
+        }
+
         // TODO: Apply global alpha
 
-        renderer.fill(&state.fill, &state.scissor, fringe, self.cache.bounds, Paths { cache: &self.cache });
+        renderer.fill(&state.fill, &state.composite, &state.scissor, state.clip_depth, fringe, self.cache.bounds, Paths { cache: &self.cache });
+    }
+
+    /// Intersects the current clip region with the current path. The clip region is part of
+    /// the state, so it is undone by `restore`.
+    pub fn clip<R>(&mut self, renderer: &mut R) where R: CanvasRenderer {
+        if let Some(capture) = &mut self.capture {
+            capture.record_clip(&self.state, self.pixels_per_point, &self.commands);
+        }
+
+        let start = Instant::now();
+        self.cache.flatten_paths(self.commands.iter(), self.tess_tol, self.dist_tol);
+        let flattened = Instant::now();
+
+        // Clip paths only go into the stencil buffer, so they don't need a fringe.
+        self.cache.expand_fill(self.fringe, 0.0, LineJoin::Miter, 2.4);
+
+        if let Some(stats) = renderer.frame_stats_mut() {
+            stats.flatten_time += flattened - start;
+            stats.expand_fill_time += flattened.elapsed();
+            self.cache.add_stats(stats);
+        }
+
+        renderer.clip(self.state.clip_depth, Paths { cache: &self.cache });
+        self.state.clip_depth += 1;
+    }
+
+    /// Starts drawing into an offscreen layer, which is composited with `opacity` and `blend`
+    /// once `pop_layer` is called, so overlapping shapes inside it don't blend with each other.
+    pub fn push_layer<R>(&mut self, renderer: &mut R, opacity: Scalar, blend: BlendMode) where R: CanvasRenderer {
+        if let Some(capture) = &mut self.capture {
+            capture.record_push_layer(&self.state, self.pixels_per_point, opacity, blend);
+        }
+
+        let composite = CompositeState {
+            blend_mode: blend,
+            ..self.state.composite
+        };
+        renderer.push_layer(clamp(opacity, 0.0, 1.0), &composite, self.state.clip_depth);
+    }
+
+    pub fn pop_layer<R>(&mut self, renderer: &mut R) where R: CanvasRenderer {
+        if let Some(capture) = &mut self.capture {
+            capture.record_pop_layer();
+        }
+
+        renderer.pop_layer();
     }
 }
 
@@ -377,6 +850,11 @@ const PI: Scalar = std::f32::consts::PI;
 const _2_PI: Scalar = 2.0 * PI;
 const FRAC_1_PI: Scalar = std::f32::consts::FRAC_1_PI;
 
+/// Half width of the box `linear_gradient` describes its line with, which also tells linear
+/// gradients apart from box gradients.
+pub(crate) const LINEAR_GRADIENT_EXTENT: Scalar = 1e5;
+
+#[derive(Clone)]
 enum Command {
     MoveTo(Scalar, Scalar),
     LineTo(Scalar, Scalar),
@@ -395,6 +873,9 @@ struct State {
     fill: Paint,
     shape_anti_alias: bool,
     scissor: Scissor,
+    composite: CompositeState,
+    clip_depth: usize,
+    transform: Transform,
 }
 
 impl Default for State {
@@ -411,11 +892,17 @@ impl Default for State {
                 transform: Transform::identity(),
                 extent: [-1.0; 2],
             },
+            composite: CompositeState::from_op(CompositeOp::SourceOver),
+            clip_depth: 0,
+            transform: Transform::identity(),
         }
     }
 }
 
-#[derive(Clone, Copy)]
+/// 2D affine transform, mapping `(x, y)` to `(e[0] * x + e[2] * y + e[4], e[1] * x + e[3] * y + e[5])`.
+///
+/// `a * b` applies `a` first, then `b`.
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Transform {
     pub e: [f32; 6],
 }
@@ -432,6 +919,117 @@ impl Transform {
         }
     }
 
+    pub fn translate(tx: Scalar, ty: Scalar) -> Self {
+        Transform {
+            e: [
+                1.0, 0.0,
+                0.0, 1.0,
+                tx, ty,
+            ]
+        }
+    }
+
+    pub fn scale(sx: Scalar, sy: Scalar) -> Self {
+        Transform {
+            e: [
+                sx, 0.0,
+                0.0, sy,
+                0.0, 0.0,
+            ]
+        }
+    }
+
+    /// Rotates by `angle` radians, clockwise with the y axis pointing down.
+    pub fn rotate(angle: Scalar) -> Self {
+        let (sn, cs) = angle.sin_cos();
+        Transform {
+            e: [
+                cs, sn,
+                -sn, cs,
+                0.0, 0.0,
+            ]
+        }
+    }
+
+    /// Skews along the x axis by `angle` radians.
+    pub fn skew_x(angle: Scalar) -> Self {
+        Transform {
+            e: [
+                1.0, 0.0,
+                angle.tan(), 1.0,
+                0.0, 0.0,
+            ]
+        }
+    }
+
+    /// Skews along the y axis by `angle` radians.
+    pub fn skew_y(angle: Scalar) -> Self {
+        Transform {
+            e: [
+                1.0, angle.tan(),
+                0.0, 1.0,
+                0.0, 0.0,
+            ]
+        }
+    }
+
+    /// Returns the transform applying `self`, then `other`. Same as `self * other`.
+    pub fn then(&self, other: &Transform) -> Transform {
+        *self * *other
+    }
+
+    /// Returns the transform applying `other`, then `self`. Same as `other * self`.
+    pub fn pre_multiply(&self, other: &Transform) -> Transform {
+        *other * *self
+    }
+
+    pub fn transform_point(&self, x: Scalar, y: Scalar) -> (Scalar, Scalar) {
+        (x * self.e[0] + y * self.e[2] + self.e[4], x * self.e[1] + y * self.e[3] + self.e[5])
+    }
+
+    /// Transforms a direction or a distance, which ignores the translation.
+    pub fn transform_vector(&self, x: Scalar, y: Scalar) -> (Scalar, Scalar) {
+        (x * self.e[0] + y * self.e[2], x * self.e[1] + y * self.e[3])
+    }
+
+    /// Returns the average of the scale along the x and y axes, for sizes that don't have a
+    /// direction such as line widths.
+    pub fn average_scale(&self) -> Scalar {
+        let sx = (self.e[0] * self.e[0] + self.e[2] * self.e[2]).sqrt();
+        let sy = (self.e[1] * self.e[1] + self.e[3] * self.e[3]).sqrt();
+        (sx + sy) * 0.5
+    }
+
+    pub fn is_identity(&self) -> bool {
+        *self == Transform::identity()
+    }
+
+    pub fn determinant(&self) -> Scalar {
+        self.e[0] * self.e[3] - self.e[2] * self.e[1]
+    }
+
+    /// Returns the row-major 3x3 matrix transforming column vectors `(x, y, 1)`.
+    pub fn to_matrix3(&self) -> [[Scalar; 3]; 3] {
+        [
+            [self.e[0], self.e[2], self.e[4]],
+            [self.e[1], self.e[3], self.e[5]],
+            [0.0, 0.0, 1.0],
+        ]
+    }
+
+    /// Creates a transform from a row-major 3x3 matrix transforming column vectors
+    /// `(x, y, 1)`. The last row is ignored, since it isn't affine otherwise.
+    pub fn from_matrix3(m: [[Scalar; 3]; 3]) -> Transform {
+        Transform {
+            e: [
+                m[0][0], m[1][0],
+                m[0][1], m[1][1],
+                m[0][2], m[1][2],
+            ]
+        }
+    }
+
+    /// Returns the inverse transform, or the identity if `self` can't be inverted.
     pub fn inverse(&self) -> Transform {
         let det = self.e[0] as f64 * self.e[3] as f64 - self.e[2] as f64 * self.e[1] as f64;
         if det > -1e-6 && det < 1e-6 {
@@ -473,7 +1071,7 @@ impl Mul<Transform> for Transform {
     }
 }
 
-
+#[derive(Clone)]
 struct PathBuilder {
     first: usize,
     count: usize,
@@ -491,11 +1089,12 @@ struct PathVertexRef {
     count: usize,
 }
 
+#[derive(Clone)]
 struct PathCache {
-    points: Vec<Point>,
+    points: Vec<PathPoint>,
     verts: Vec<Vertex>,
     paths: Vec<PathBuilder>,
-    bounds: [Scalar; 4],
+    bounds: Rect,
 }
 
 impl PathCache {
@@ -504,11 +1103,42 @@ impl PathCache {
             points: Vec::new(),
             verts: Vec::new(),
             paths: Vec::new(),
-            bounds: [0.0, 0.0, 0.0, 0.0],
+            bounds: Rect::zero(),
+        }
+    }
+
+    /// Copies the tessellated paths, without the flattened points.
+    fn tessellation(&self) -> PathCache {
+        PathCache {
+            points: Vec::new(),
+            verts: self.verts.clone(),
+            paths: self.paths.clone(),
+            bounds: self.bounds,
         }
     }
 
+    /// Copies the tessellated paths moved by `dx`, `dy`.
+    fn translated(&self, dx: Scalar, dy: Scalar) -> PathCache {
+        let mut cache = self.tessellation();
+        for vert in cache.verts.iter_mut() {
+            vert.x += dx;
+            vert.y += dy;
+        }
+        cache.bounds = self.bounds.translate(Vector::new(dx, dy));
+        cache
+    }
+
+    fn add_stats(&self, stats: &mut FrameStats) {
+        stats.paths += self.paths.len();
+        stats.points += self.points.len();
+    }
+
     fn flatten_paths<'a, T>(&mut self, iter: T, tess_tol: Scalar, dist_tol: Scalar) where T: Iterator<Item=&'a Command> {
+        // The path is already flattened when it was filled or stroked before.
+        if !self.paths.is_empty() {
+            return;
+        }
+
         for command in iter {
             match *command {
                 Command::MoveTo(x, y) => {
@@ -518,7 +1148,7 @@ impl PathCache {
                 Command::LineTo(x, y) => self.add_point(x, y, POINT_CORNER, dist_tol),
                 Command::BezierTo(cp1x, cp1y, cp2x, cp2y, x, y) => {
                     if let Some(last) = self.points.last() {
-                        self.tesselate_bezier(last.x,last.y, cp1x,cp1y, cp2x,cp2y, x, y, 0, POINT_CORNER, tess_tol, dist_tol);
+                        self.tesselate_bezier(last.x,last.y, cp1x,cp1y, cp2x,cp2y, x, y, POINT_CORNER, tess_tol, dist_tol);
                     }
                 },
                 Command::Close => self.close_path(),
@@ -526,21 +1156,20 @@ impl PathCache {
             }
         }
 
-        self.bounds[0] = std::f32::MAX;
-        self.bounds[1] = std::f32::MAX;
-        self.bounds[2] = std::f32::MIN;
-        self.bounds[3] = std::f32::MIN;
+        let mut bounds = [f32::MAX, f32::MAX, f32::MIN, f32::MIN];
 
         for path in self.paths.iter_mut() {
             let mut points = &mut self.points[path.first..(path.first + path.count)];
 
             // If the first and last points are the same, remove the last, mark as closed path.
-            let p0 = &points[path.count - 1];
-            let p1 = &points[0];
-            if point_equals(p0.x, p0.y, p1.x, p1.y, dist_tol) {
-                path.count = path.count - 1;
-                path.closed = true;
-                points = &mut self.points[path.first..(path.first + path.count)];
+            if path.count > 1 {
+                let p0 = &points[path.count - 1];
+                let p1 = &points[0];
+                if point_equals(p0.x, p0.y, p1.x, p1.y, dist_tol) {
+                    path.count -= 1;
+                    path.closed = true;
+                    points = &mut self.points[path.first..(path.first + path.count)];
+                }
             }
 
             // Enforce winding
@@ -566,47 +1195,48 @@ impl PathCache {
                 p0.dx = dx;
                 p0.dy = dy;
                 p0.len = len;
-
-                // Update bounds
-                self.bounds[0] = self.bounds[0].min(p0.x);
-                self.bounds[1] = self.bounds[1].min(p0.y);
-                self.bounds[2] = self.bounds[2].max(p0.x);
-                self.bounds[3] = self.bounds[3].max(p0.y);
             }
-        }
-    }
 
-    fn tesselate_bezier(&mut self, x1: Scalar, y1: Scalar, x2: Scalar, y2: Scalar, x3: Scalar, y3: Scalar, x4: Scalar, y4: Scalar, level: u32, flags: u32, tess_tol: Scalar, dist_tol: Scalar) {
-        if level > 10 {
-            return
+            // Update bounds
+            for point in points.iter() {
+                bounds[0] = bounds[0].min(point.x);
+                bounds[1] = bounds[1].min(point.y);
+                bounds[2] = bounds[2].max(point.x);
+                bounds[3] = bounds[3].max(point.y);
+            }
         }
 
-        let x12 = (x1 + x2) * 0.5;
-        let y12 = (y1 + y2) * 0.5;
-        let x23 = (x2 + x3) * 0.5;
-        let y23 = (y2 + y3) * 0.5;
-        let x34 = (x3 + x4) * 0.5;
-        let y34 = (y3 + y4) * 0.5;
-        let x123 = (x12 + x23) * 0.5;
-        let y123 = (y12 + y23) * 0.5;
-
-        let dx = x4 - x1;
-        let dy = y4 - y1;
-        let d2 = ((x2 - x4) * dy - (y2 - y4) * dx).abs();
-        let d3 = ((x3 - x4) * dy - (y3 - y4) * dx).abs();
+        self.bounds = if bounds[0] <= bounds[2] {
+            Rect::from_min_max(bounds[0], bounds[1], bounds[2], bounds[3])
+        } else {
+            Rect::zero()
+        };
+    }
 
-        if (d2 + d3) * (d2 + d3) < tess_tol * (dx * dx + dy * dy) {
-            self.add_point(x4, y4, flags, dist_tol);
-            return;
+    /// Flattens a cubic bezier into lines which stay within `tess_tol` of the curve. The number
+    /// of lines comes from Wang's formula, which bounds the distance with the second
+    /// differences of the control points.
+    fn tesselate_bezier(&mut self, x1: Scalar, y1: Scalar, x2: Scalar, y2: Scalar, x3: Scalar, y3: Scalar, x4: Scalar, y4: Scalar, flags: u32, tess_tol: Scalar, dist_tol: Scalar) {
+        let ddx0 = x1 - 2.0 * x2 + x3;
+        let ddy0 = y1 - 2.0 * y2 + y3;
+        let ddx1 = x2 - 2.0 * x3 + x4;
+        let ddy1 = y2 - 2.0 * y3 + y4;
+        let dd = (ddx0 * ddx0 + ddy0 * ddy0).max(ddx1 * ddx1 + ddy1 * ddy1).sqrt();
+        let n = (0.75 * dd / tess_tol).sqrt().ceil();
+        // NaN becomes 0 here, and the bound only stops holding for gigantic curves.
+        let n = clamp(n as usize, 1, MAX_CURVE_SEGMENTS);
+
+        for i in 1..n {
+            let t = i as Scalar / n as Scalar;
+            let mt = 1.0 - t;
+            let a = mt * mt * mt;
+            let b = 3.0 * mt * mt * t;
+            let c = 3.0 * mt * t * t;
+            let d = t * t * t;
+            self.add_point(a * x1 + b * x2 + c * x3 + d * x4, a * y1 + b * y2 + c * y3 + d * y4, 0, dist_tol);
         }
 
-        let x234 = (x23 + x34) * 0.5;
-        let y234 = (y23 + y34) * 0.5;
-        let x1234 = (x123 + x234) * 0.5;
-        let y1234 = (y123 + y234) * 0.5;
-
-        self.tesselate_bezier(x1, y1, x12, y12, x123, y123, x1234, y1234, level + 1, 0, tess_tol, dist_tol);
-        self.tesselate_bezier(x1234, y1234, x234, y234, x34, y34, x4, y4, level + 1, flags, tess_tol, dist_tol);
+        self.add_point(x4, y4, flags, dist_tol);
     }
 
     fn expand_stroke(&mut self, mut w: Scalar, fringe: Scalar, line_cap: LineCap, line_join: LineJoin, miter_limit: Scalar, tess_tol: Scalar) {
@@ -623,26 +1253,7 @@ impl PathCache {
 
         self.calculate_joins(w, line_join, miter_limit);
 
-        // Calculate max vertex usage.
-        let mut cverts = 0;
-        for path in self.paths.iter() {
-            let is_loop = path.closed;
-            if line_join == LineJoin::Round {
-                cverts += (path.count + path.nbevel * (ncap + 2) + 1) * 2; // plus one for loop
-            } else {
-                cverts += (path.count + path.nbevel * 5 + 1) * 2; // plus one for loop
-            }
-
-            if !is_loop {
-                // space for caps
-                if line_cap == LineCap::Round {
-                    cverts += (ncap * 2 + 2) * 2;
-                } else {
-                    cverts += (3 + 3) * 2;
-                }
-            }
-        }
-
+        let cverts = self.stroke_vertex_count(ncap, line_cap, line_join);
         self.verts.clear();
         self.verts.reserve(cverts);
 
@@ -654,11 +1265,17 @@ impl PathCache {
             let points = &self.points[path.first..(path.first + path.count)];
             let start: usize;
             let end: usize;
-            let mut p0: &Point;
-            let mut p1: &Point;
+            let mut p0: &PathPoint;
+            let mut p1: &PathPoint;
 
             path.fill = None;
 
+            // A single point has no direction to stroke along.
+            if path.count < 2 {
+                path.stroke = None;
+                continue;
+            }
+
             // Calculate fringe or stroke
             let is_loop = path.closed;
             let mut p1_index;
@@ -699,14 +1316,15 @@ impl PathCache {
                     add_vert(verts, p1.x - (p1.dmx * w), p1.y - (p1.dmy * w), u1, 1.0);
                 }
                 p0 = p1;
-                p1_index = p1_index + 1;
-                p1 = &points[p1_index];
+                // A loop ends back at its first point.
+                p1_index += 1;
+                p1 = &points[p1_index % path.count];
             }
 
             if is_loop {
                 // Loop it
-                add_vert(verts, verts[0].x, verts[0].y, u0, 1.0);
-                add_vert(verts, verts[1].x, verts[1].y, u1, 1.0);
+                add_vert(verts, verts[first].x, verts[first].y, u0, 1.0);
+                add_vert(verts, verts[first + 1].x, verts[first + 1].y, u1, 1.0);
             } else {
                 // Add cap
                 let (dx, dy, _) = normalize(p1.x - p0.x, p1.y - p0.y);
@@ -725,15 +1343,7 @@ impl PathCache {
         let fringe = w > 0.0;
         self.calculate_joins(w, line_join, miter_limit);
 
-        // Calculate max vertex usage.
-        let mut cverts = 0;
-        for path in self.paths.iter() {
-            cverts += path.count + path.nbevel + 1;
-            if fringe {
-                cverts += (path.count + path.nbevel * 5 + 1) * 2; // plus one for loop
-            }
-        }
-
+        let cverts = self.fill_vertex_count(fringe);
         self.verts.clear();
         self.verts.reserve(cverts);
 
@@ -744,15 +1354,20 @@ impl PathCache {
         for path in self.paths.iter_mut() {
             let first = verts.len();
             let points = &self.points[path.first..(path.first + path.count)];
+
+            if path.count == 0 {
+                path.fill = None;
+                path.stroke = None;
+                continue;
+            }
+
             // Calculate shape vertices.
             let woff = 0.5 * aa;
 
             if fringe {
                 // Looping
                 let mut p0 = &points[path.count - 1];
-                let mut p1_index = 0;
-                for _ in 0..path.count {
-                    let p1 = &points[p1_index];
+                for p1 in points.iter() {
                     if p1.flags & POINT_BEVEL != 0 {
                         let dlx0 = p0.dy;
                         let dly0 = -p0.dx;
@@ -774,7 +1389,6 @@ impl PathCache {
                         add_vert(verts, p1.x + (p1.dmx * woff), p1.y + (p1.dmy * woff), 0.5, 1.0);
                     }
                     p0 = p1;
-                    p1_index += 1;
                 }
             } else {
                 for point in points {
@@ -802,10 +1416,8 @@ impl PathCache {
 
                 // Looping
                 let mut p0 = &points[path.count - 1];
-                let mut p1_index = 0;
 
-                for _ in 0..path.count {
-                    let p1 = &points[p1_index];
+                for p1 in points.iter() {
                     if (p1.flags & (POINT_BEVEL | POINT_INNER_BEVEL)) != 0 {
                         bevel_join(verts, p0, p1, lw, rw, lu, ru, aa);
                     } else {
@@ -813,7 +1425,6 @@ impl PathCache {
                         add_vert(verts, p1.x - (p1.dmx * rw), p1.y - (p1.dmy * rw), ru, 1.0);
                     }
                     p0 = p1;
-                    p1_index += 1;
                 }
 
                 // Loop it
@@ -827,8 +1438,44 @@ impl PathCache {
         }
     }
 
+    /// Returns the most vertices `expand_stroke` adds for the paths, once the joins are
+    /// calculated.
+    fn stroke_vertex_count(&self, ncap: usize, line_cap: LineCap, line_join: LineJoin) -> usize {
+        let mut cverts = 0;
+        for path in self.paths.iter().filter(|path| path.count >= 2) {
+            let is_loop = path.closed;
+            if line_join == LineJoin::Round {
+                cverts += (path.count + path.nbevel * (ncap + 2) + 1) * 2; // plus one for loop
+            } else {
+                cverts += (path.count + path.nbevel * 5 + 1) * 2; // plus one for loop
+            }
+
+            if !is_loop {
+                // space for caps
+                if line_cap == LineCap::Round {
+                    cverts += (ncap * 2 + 2) * 2;
+                } else {
+                    cverts += (3 + 3) * 2;
+                }
+            }
+        }
+        cverts
+    }
+
+    /// Returns the most vertices `expand_fill` adds for the paths, once the joins are
+    /// calculated.
+    fn fill_vertex_count(&self, fringe: bool) -> usize {
+        let mut cverts = 0;
+        for path in self.paths.iter() {
+            cverts += path.count + path.nbevel + 1;
+            if fringe {
+                cverts += (path.count + path.nbevel * 5 + 1) * 2; // plus one for loop
+            }
+        }
+        cverts
+    }
+
     fn calculate_joins(&mut self, w: Scalar, line_join: LineJoin, miter_limit: Scalar) {
-        let mut nleft = 0;
         let mut iw = 0.0;
         if w > 0.0 {
             iw = 1.0 / w;
@@ -837,6 +1484,7 @@ impl PathCache {
         // Calculate which joins needs extra vertices to append, and gather vertex count.
         for path in self.paths.iter_mut() {
             path.nbevel = 0;
+            let mut nleft = 0;
             let points = &mut self.points[path.first..(path.first + path.count)];
             for (p0, p1) in edge_iter_mut(points) {
                 let dlx0 = p0.dy;
@@ -862,7 +1510,7 @@ impl PathCache {
                 // Keep track of left turns.
                 let cross = p1.dx * p0.dy - p0.dx * p1.dy;
                 if cross > 0.0 {
-                    nleft = nleft + 1;
+                    nleft += 1;
                     p1.flags |= POINT_LEFT;
                 }
 
@@ -873,18 +1521,16 @@ impl PathCache {
                 }
 
                 // Check to see if the corner needs to be beveled.
-                if (p1.flags & POINT_CORNER) != 0 {
-                    if (dmr2 * miter_limit * miter_limit) < 1.0 || line_join == LineJoin::Bevel || line_join == LineJoin::Round {
-                        p1.flags |= POINT_BEVEL;
-                    }
+                if (p1.flags & POINT_CORNER) != 0 && ((dmr2 * miter_limit * miter_limit) < 1.0 || line_join == LineJoin::Bevel || line_join == LineJoin::Round) {
+                    p1.flags |= POINT_BEVEL;
                 }
 
                 if (p1.flags & (POINT_BEVEL | POINT_INNER_BEVEL)) != 0 {
-                    path.nbevel = path.nbevel + 1;
+                    path.nbevel += 1;
                 }
             }
 
-            path.convex = if nleft == path.count { true } else { false };
+            path.convex = nleft == path.count;
         }
     }
 
@@ -905,15 +1551,15 @@ impl PathCache {
     fn add_point(&mut self, x: Scalar, y: Scalar, flags: u32, dist_tol: Scalar) {
         if let Some(path) = self.paths.last_mut() {
             // If the incoming and last points are the same, merge them
-            if path.count > 0 && self.points.len() > 0 {
+            if path.count > 0 && !self.points.is_empty() {
                 let last_point = self.points.last_mut().unwrap();
                 if point_equals(last_point.x, last_point.y, x, y, dist_tol) {
-                    last_point.flags = last_point.flags | flags;
+                    last_point.flags |= flags;
                     return;
                 }
             }
 
-            let point = Point {
+            let point = PathPoint {
                 x,
                 y,
                 dx: 0.0,
@@ -924,7 +1570,7 @@ impl PathCache {
                 flags,
             };
             self.points.push(point);
-            path.count = path.count + 1;
+            path.count += 1;
         }
     }
 
@@ -946,7 +1592,7 @@ impl PathCache {
     }
 }
 
-fn bevel_join(verts: &mut Vec<Vertex>, p0: &Point, p1: &Point, lw: Scalar, rw: Scalar, lu: Scalar, ru: Scalar, _fringe: Scalar) {
+fn bevel_join(verts: &mut Vec<Vertex>, p0: &PathPoint, p1: &PathPoint, lw: Scalar, rw: Scalar, lu: Scalar, ru: Scalar, _fringe: Scalar) {
     let dlx0 = p0.dy;
     let dly0 = -p0.dx;
     let dlx1 = p1.dy;
@@ -1010,7 +1656,7 @@ fn bevel_join(verts: &mut Vec<Vertex>, p0: &Point, p1: &Point, lw: Scalar, rw: S
     }
 }
 
-fn round_join(verts: &mut Vec<Vertex>, p0: &Point, p1: &Point, lw: Scalar, rw: Scalar, lu: Scalar, ru: Scalar, ncap: usize, _fringe: Scalar) {
+fn round_join(verts: &mut Vec<Vertex>, p0: &PathPoint, p1: &PathPoint, lw: Scalar, rw: Scalar, lu: Scalar, ru: Scalar, ncap: usize, _fringe: Scalar) {
     let dlx0 = p0.dy;
     let dly0 = -p0.dx;
     let dlx1 = p1.dy;
@@ -1088,7 +1734,7 @@ fn clamp<T>(a: T, mn: T, mx: T) -> T where T: PartialOrd {
 }
 
 #[inline(always)]
-fn choose_bevel(bevel: u32, p0: &Point, p1: &Point, w: Scalar) -> (Scalar, Scalar, Scalar, Scalar) {
+fn choose_bevel(bevel: u32, p0: &PathPoint, p1: &PathPoint, w: Scalar) -> (Scalar, Scalar, Scalar, Scalar) {
     if bevel != 0 {
         (p1.x + p0.dy * w, p1.y - p0.dx * w, p1.x + p1.dy * w, p1.y - p1.dx * w)
     } else {
@@ -1104,7 +1750,8 @@ pub struct Vertex {
     pub v: Scalar,
 }
 
-struct Point {
+#[derive(Clone)]
+struct PathPoint {
     x: Scalar,
     y: Scalar,
     dx: Scalar,
@@ -1115,6 +1762,9 @@ struct Point {
     flags: u32,
 }
 
+/// Most lines a single curve is flattened into.
+const MAX_CURVE_SEGMENTS: usize = 4096;
+
 const POINT_CORNER: u32 = 0x01;
 const POINT_LEFT: u32 = 0x02;
 const POINT_BEVEL: u32 = 0x04;
@@ -1124,10 +1774,10 @@ const POINT_INNER_BEVEL: u32 = 0x08;
 fn point_equals(x1: Scalar, y1: Scalar, x2: Scalar, y2: Scalar, tol: Scalar) -> bool {
     let dx = x2 - x1;
     let dy = y2 - y1;
-    return dx * dx + dy * dy < tol * tol;
+    dx * dx + dy * dy < tol * tol
 }
 
-fn polygon_area(points: &[Point]) -> Scalar {
+fn polygon_area(points: &[PathPoint]) -> Scalar {
     let mut area = 0.0;
     let a = &points[0];
     for i in 2..points.len() {
@@ -1145,7 +1795,7 @@ fn triangle_area2(ax: Scalar, ay: Scalar, bx: Scalar, by: Scalar, cx: Scalar, cy
     let aby = by - ay;
     let acx = cx - ax;
     let acy = cy - ay;
-    return acx * aby - abx * acy;
+    acx * aby - abx * acy
 }
 
 fn normalize(x: Scalar, y: Scalar) -> (Scalar, Scalar, Scalar)  {
@@ -1154,8 +1804,8 @@ fn normalize(x: Scalar, y: Scalar) -> (Scalar, Scalar, Scalar)  {
     let mut ny = y;
     if len > 1e-6 {
         let inv_len = 1.0 / len;
-        nx = nx * inv_len;
-        ny = ny * inv_len;
+        nx *= inv_len;
+        ny *= inv_len;
     }
     (nx, ny, len)
 }
@@ -1165,7 +1815,7 @@ fn curve_divs(r: Scalar, arc: Scalar, tol: Scalar) -> usize {
     ((arc / da).ceil() as usize).max(2)
 }
 
-fn butt_cap_start(verts: &mut Vec<Vertex>, p: &Point, dx: Scalar, dy: Scalar, w: Scalar, d: Scalar, aa: Scalar, u0: Scalar, u1: Scalar) {
+fn butt_cap_start(verts: &mut Vec<Vertex>, p: &PathPoint, dx: Scalar, dy: Scalar, w: Scalar, d: Scalar, aa: Scalar, u0: Scalar, u1: Scalar) {
     let px = p.x - dx * d;
     let py = p.y - dy * d;
     let dlx = dy;
@@ -1176,7 +1826,7 @@ fn butt_cap_start(verts: &mut Vec<Vertex>, p: &Point, dx: Scalar, dy: Scalar, w:
     add_vert(verts, px - dlx * w, py - dly * w, u1, 1.0);
 }
 
-fn round_cap_start(verts: &mut Vec<Vertex>, p: &Point, dx: Scalar, dy: Scalar, w: Scalar, ncap: usize, _aa: Scalar, u0: Scalar, u1: Scalar) {
+fn round_cap_start(verts: &mut Vec<Vertex>, p: &PathPoint, dx: Scalar, dy: Scalar, w: Scalar, ncap: usize, _aa: Scalar, u0: Scalar, u1: Scalar) {
     let px = p.x;
     let py = p.y;
     let dlx = dy;
@@ -1194,7 +1844,7 @@ fn round_cap_start(verts: &mut Vec<Vertex>, p: &Point, dx: Scalar, dy: Scalar, w
     add_vert(verts, px - dlx * w, py - dly * w, u1, 1.0);
 }
 
-fn butt_cap_end(verts: &mut Vec<Vertex>, p: &Point, dx: Scalar, dy: Scalar, w: Scalar, d: Scalar, aa: Scalar, u0: Scalar, u1: Scalar) {
+fn butt_cap_end(verts: &mut Vec<Vertex>, p: &PathPoint, dx: Scalar, dy: Scalar, w: Scalar, d: Scalar, aa: Scalar, u0: Scalar, u1: Scalar) {
     let px = p.x + dx * d;
     let py = p.y + dy * d;
     let dlx = dy;
@@ -1205,7 +1855,7 @@ fn butt_cap_end(verts: &mut Vec<Vertex>, p: &Point, dx: Scalar, dy: Scalar, w: S
     add_vert(verts, px - dlx * w + dx * aa, py - dly * w + dy * aa, u1, 0.0);
 }
 
-fn round_cap_end(verts: &mut Vec<Vertex>, p: &Point, dx: Scalar, dy: Scalar, w: Scalar, ncap: usize, _aa: Scalar, u0: Scalar, u1: Scalar) {
+fn round_cap_end(verts: &mut Vec<Vertex>, p: &PathPoint, dx: Scalar, dy: Scalar, w: Scalar, ncap: usize, _aa: Scalar, u0: Scalar, u1: Scalar) {
     let px = p.x;
     let py = p.y;
     let dlx = dy;
@@ -1245,7 +1895,7 @@ impl<'a, T> Iterator for EdgeIterMut<'a, T> {
                 (&mut *(&mut self.points[self.from] as *mut T), &mut *(&mut self.points[self.to] as *mut T))
             };
             self.from = self.to;
-            self.to = self.to + 1;
+            self.to += 1;
             Some(result)
         } else {
             None
@@ -1253,10 +1903,12 @@ impl<'a, T> Iterator for EdgeIterMut<'a, T> {
     }
 }
 
-fn edge_iter_mut<T>(points: &mut [T]) -> EdgeIterMut<T> {
+/// Iterates the edges of a closed polygon, starting with the one from the last point to the
+/// first. A single point has no edges.
+fn edge_iter_mut<T>(points: &mut [T]) -> EdgeIterMut<'_, T> {
     EdgeIterMut {
-        from: points.len() - 1,
-        to: 0,
+        from: points.len().saturating_sub(1),
+        to: if points.len() < 2 { points.len() } else { 0 },
         points,
     }
 }
@@ -1272,5 +1924,375 @@ mod test {
             .map(|(from, to)| (*from, *to))
             .collect::<Vec<_>>();
         assert_eq!(edges, vec![(300.0, 100.0), (100.0, 200.0), (200.0, 300.0)]);
+
+        let mut point = vec![100.0];
+        assert_eq!(edge_iter_mut(point.as_mut()).count(), 0);
+        assert_eq!(edge_iter_mut(&mut [] as &mut [Scalar]).count(), 0);
+    }
+
+    /// Xorshift generator, so the random paths are the same on every run.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: u64) -> u64 {
+            self.next() % n
+        }
+
+        /// Returns a coordinate on a coarse grid, so that points often coincide.
+        fn coord(&mut self) -> Scalar {
+            if self.below(4) == 0 {
+                self.below(100_000) as Scalar * 0.01 - 500.0
+            } else {
+                self.below(5) as Scalar * 10.0
+            }
+        }
+    }
+
+    fn random_commands(rng: &mut Rng) -> Vec<Command> {
+        (0..rng.below(16))
+            .map(|_| match rng.below(8) {
+                0..=1 => Command::MoveTo(rng.coord(), rng.coord()),
+                2..=4 => Command::LineTo(rng.coord(), rng.coord()),
+                5 => Command::BezierTo(rng.coord(), rng.coord(), rng.coord(), rng.coord(), rng.coord(), rng.coord()),
+                6 => Command::Close,
+                _ => Command::Winding(if rng.below(2) == 0 { Winding::CCW } else { Winding::CW }),
+            })
+            .collect()
+    }
+
+    fn check_verts(cache: &PathCache, cverts: usize) {
+        assert!(cache.verts.len() <= cverts, "{} vertices for a reservation of {}", cache.verts.len(), cverts);
+        for vert in cache.verts.iter() {
+            assert!(vert.x.is_finite() && vert.y.is_finite() && vert.u.is_finite() && vert.v.is_finite());
+        }
+        for path in (Paths { cache }).iter() {
+            // Slicing panics for references out of range.
+            let _ = (path.stroke(), path.fill());
+        }
+    }
+
+    #[test]
+    fn test_random_paths() {
+        const CAPS: [LineCap; 3] = [LineCap::Butt, LineCap::Round, LineCap::Square];
+        const JOINS: [LineJoin; 3] = [LineJoin::Round, LineJoin::Bevel, LineJoin::Miter];
+        let tess_tol = 0.25;
+        let dist_tol = 0.01;
+
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        for _ in 0..3000 {
+            let commands = random_commands(&mut rng);
+            let mut cache = PathCache::new();
+            cache.flatten_paths(commands.iter(), tess_tol, dist_tol);
+
+            for path in cache.paths.iter() {
+                let points = &cache.points[path.first..(path.first + path.count)];
+                if path.count > 2 {
+                    let area = polygon_area(points);
+                    match path.winding {
+                        Winding::CCW => assert!(area >= -1e-3, "CCW path with area {}", area),
+                        Winding::CW => assert!(area <= 1e-3, "CW path with area {}", area),
+                    }
+                }
+            }
+
+            let w = rng.below(50) as Scalar * 0.5;
+            let fringe = rng.below(2) as Scalar;
+            let line_cap = CAPS[rng.below(3) as usize];
+            let line_join = JOINS[rng.below(3) as usize];
+            let miter_limit = rng.below(20) as Scalar * 0.5;
+            let ncap = curve_divs(w, PI, tess_tol);
+            cache.expand_stroke(w, fringe, line_cap, line_join, miter_limit, tess_tol);
+            check_verts(&cache, cache.stroke_vertex_count(ncap, line_cap, line_join));
+
+            cache.expand_fill(1.0, fringe, LineJoin::Miter, 2.4);
+            check_verts(&cache, cache.fill_vertex_count(fringe > 0.0));
+        }
+    }
+
+    fn assert_near(a: (Scalar, Scalar), b: (Scalar, Scalar)) {
+        assert!((a.0 - b.0).abs() < 1e-4 && (a.1 - b.1).abs() < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn test_transform() {
+        let t = Transform::translate(10.0, 20.0);
+        let s = Transform::scale(2.0, 3.0);
+        let r = Transform::rotate(PI * 0.5);
+
+        // Multiplying applies the left transform first.
+        assert_eq!((t * s).transform_point(1.0, 1.0), (22.0, 63.0));
+        assert_eq!((s * t).transform_point(1.0, 1.0), (12.0, 23.0));
+        assert_eq!(t.then(&s), t * s);
+        assert_eq!(t.pre_multiply(&s), s * t);
+        assert_near(r.transform_point(1.0, 0.0), (0.0, 1.0));
+        assert_near(Transform::skew_x(PI * 0.25).transform_point(0.0, 2.0), (2.0, 2.0));
+        assert_near(Transform::skew_y(PI * 0.25).transform_point(2.0, 0.0), (2.0, 2.0));
+        assert_eq!((t * s).transform_vector(1.0, 1.0), (2.0, 3.0));
+
+        let m = t * r * s;
+        let inverse = m.inverse();
+        let (x, y) = m.transform_point(3.0, -7.0);
+        assert_near(inverse.transform_point(x, y), (3.0, -7.0));
+        let product = m * inverse;
+        for (a, b) in product.e.iter().zip(Transform::identity().e.iter()) {
+            assert!((a - b).abs() < 1e-5);
+        }
+        assert_eq!(Transform::scale(0.0, 1.0).inverse(), Transform::identity());
+
+        assert!(Transform::identity().is_identity());
+        assert!(!t.is_identity());
+        assert_eq!(s.determinant(), 6.0);
+        assert_near((m.determinant(), 0.0), (6.0, 0.0));
+        assert_eq!(s.average_scale(), 2.5);
+        assert_near(((r * s).average_scale(), 0.0), (2.5, 0.0));
+        assert_eq!(m.to_matrix3()[2], [0.0, 0.0, 1.0]);
+        assert_eq!(Transform::from_matrix3(m.to_matrix3()), m);
+        assert_eq!(t.to_matrix3(), [[1.0, 0.0, 10.0], [0.0, 1.0, 20.0], [0.0, 0.0, 1.0]]);
+    }
+
+    fn segment_distance(px: Scalar, py: Scalar, a: &PathPoint, b: &PathPoint) -> Scalar {
+        let (dx, dy) = (b.x - a.x, b.y - a.y);
+        let len2 = dx * dx + dy * dy;
+        let t = if len2 > 0.0 { clamp(((px - a.x) * dx + (py - a.y) * dy) / len2, 0.0, 1.0) } else { 0.0 };
+        let (x, y) = (a.x + t * dx - px, a.y + t * dy - py);
+        (x * x + y * y).sqrt()
+    }
+
+    #[test]
+    fn test_flatten_bezier() {
+        let curves: [[Scalar; 8]; 3] = [
+            [0.0, 0.0, 100.0, 0.0, 100.0, 100.0, 200.0, 100.0],
+            [0.0, 0.0, 1000.0, -800.0, -600.0, 900.0, 400.0, 20.0],
+            [0.0, 0.0, 0.1, 0.2, 0.3, 0.1, 0.5, 0.5],
+        ];
+        let dist_tol = 0.01;
+
+        for curve in curves.iter() {
+            let [x1, y1, x2, y2, x3, y3, x4, y4] = *curve;
+            let mut npoints = usize::MAX;
+            for &tess_tol in [0.001, 0.05, 0.25, 2.0].iter() {
+                let mut cache = PathCache::new();
+                let commands = [Command::MoveTo(x1, y1), Command::BezierTo(x2, y2, x3, y3, x4, y4)];
+                cache.flatten_paths(commands.iter(), tess_tol, dist_tol);
+                let points = &cache.points;
+                // Enforcing the winding may reverse the points.
+                let ends = [&points[0], points.last().unwrap()];
+                assert!(ends.iter().any(|end| (end.x, end.y) == (x4, y4)));
+                assert!(points.len() <= npoints);
+                npoints = points.len();
+
+                for i in 0..=1000 {
+                    let t = i as Scalar / 1000.0;
+                    let mt = 1.0 - t;
+                    let x = mt * mt * mt * x1 + 3.0 * mt * mt * t * x2 + 3.0 * mt * t * t * x3 + t * t * t * x4;
+                    let y = mt * mt * mt * y1 + 3.0 * mt * mt * t * y2 + 3.0 * mt * t * t * y3 + t * t * t * y4;
+                    let distance = points.windows(2)
+                        .map(|segment| segment_distance(x, y, &segment[0], &segment[1]))
+                        .fold(Scalar::MAX, Scalar::min);
+                    // Merging points closer than dist_tol moves the lines by up to that much.
+                    assert!(distance <= tess_tol + dist_tol + 1e-3, "{} away from the curve with a tolerance of {}", distance, tess_tol);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_tessellation_tolerance() {
+        let mut canvas = Canvas::new();
+        let mut list = DisplayList::new();
+        let mut count_points = |canvas: &mut Canvas| {
+            canvas.begin_path();
+            canvas.circle(0.0, 0.0, 100.0);
+            canvas.fill(&mut list);
+            canvas.cache.points.len()
+        };
+
+        let fine = count_points(&mut canvas);
+        canvas.set_tessellation_tolerance(2.0);
+        let coarse = count_points(&mut canvas);
+        canvas.set_pixels_per_point(2.0);
+        let scaled = count_points(&mut canvas);
+        assert!(coarse < fine);
+        assert!(coarse < scaled && scaled < fine);
+    }
+
+    #[test]
+    fn test_path_bounds() {
+        let mut canvas = Canvas::new();
+        canvas.begin_path();
+        assert_eq!(canvas.path_bounds(), Rect::zero());
+
+        canvas.rect_r(Rect::new(10.0, 20.0, 30.0, 40.0));
+        canvas.move_to_p(Point::new(-5.0, 25.0));
+        canvas.line_to_p(Point::new(0.0, 25.0));
+        assert_eq!(canvas.path_bounds(), Rect::new(-5.0, 20.0, 45.0, 40.0));
+
+        canvas.begin_path();
+        canvas.ellipse_r(Rect::new(0.0, 0.0, 20.0, 10.0));
+        let bounds = canvas.path_bounds();
+        assert!((bounds.max_x() - 20.0).abs() < 1e-3 && (bounds.max_y() - 10.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_degenerate_paths() {
+        let mut canvas = Canvas::new();
+        let mut list = DisplayList::new();
+
+        canvas.begin_path();
+        canvas.move_to(10.0, 10.0);
+        canvas.stroke(&mut list);
+        canvas.fill(&mut list);
+
+        canvas.begin_path();
+        canvas.move_to(10.0, 10.0).line_to(10.0, 10.0).close_path();
+        canvas.stroke(&mut list);
+
+        canvas.begin_path();
+        canvas.bezier_to(0.0, 0.0, 10.0, 10.0, 20.0, 0.0).line_to(5.0, 5.0).close_path();
+        canvas.stroke(&mut list);
+        canvas.fill(&mut list);
+        assert_eq!(list.len(), 5);
+    }
+
+    #[test]
+    fn test_stroke_closed_paths() {
+        let mut cache = PathCache::new();
+        let mut commands = Vec::new();
+        for &x in [0.0, 100.0].iter() {
+            commands.extend(vec![
+                Command::MoveTo(x, 0.0),
+                Command::LineTo(x + 50.0, 0.0),
+                Command::LineTo(x + 50.0, 50.0),
+                Command::LineTo(x, 50.0),
+                Command::Close,
+            ]);
+        }
+        cache.flatten_paths(commands.iter(), 0.25, 0.01);
+        cache.expand_stroke(2.0, 1.0, LineCap::Butt, LineJoin::Miter, 10.0, 0.25);
+
+        // Each loop ends where it started.
+        for path in (Paths { cache: &cache }).iter() {
+            assert!(path.closed());
+            let stroke = path.stroke().unwrap();
+            let n = stroke.len();
+            assert_eq!((stroke[0].x, stroke[0].y), (stroke[n - 2].x, stroke[n - 2].y));
+            assert_eq!((stroke[1].x, stroke[1].y), (stroke[n - 1].x, stroke[n - 1].y));
+        }
+
+        // Flattening again keeps the paths, which are tessellated once per fill or stroke.
+        cache.flatten_paths(commands.iter(), 0.25, 0.01);
+        assert_eq!(cache.paths.len(), 2);
+        assert!(cache.paths.iter().all(|path| path.convex));
+    }
+
+    #[test]
+    fn test_canvas_transform() {
+        let mut canvas = Canvas::new();
+        let mut list = DisplayList::new();
+        let transform = Transform::scale(2.0, 2.0) * Transform::translate(10.0, 20.0);
+
+        canvas.save();
+        canvas.set_transform(transform);
+        assert_eq!(canvas.current_transform(), transform);
+        canvas.begin_path();
+        canvas.rect(0.0, 0.0, 10.0, 5.0);
+        assert_eq!(canvas.path_bounds(), Rect::new(10.0, 20.0, 20.0, 10.0));
+
+        let paint = canvas.linear_gradient(0.0, 0.0, 10.0, 0.0, Color::rgb(255, 0, 0), Color::rgb(0, 0, 255));
+        canvas.set_fill_paint(&paint);
+        assert_eq!(canvas.state.fill.transform, paint.transform * transform);
+
+        canvas.set_stroke_width(3.0);
+        canvas.stroke(&mut list);
+        let stroke = canvas.cache.paths[0].stroke.unwrap();
+        let xs = canvas.cache.verts[stroke.first..stroke.first + stroke.count].iter().map(|vert| vert.x);
+        // The 6 pixels wide stroke, with half a pixel of fringe on each side.
+        assert_eq!(xs.fold(f32::MAX, f32::min), 10.0 - 3.5);
+
+        // Points added before the transform changes stay where they were.
+        canvas.reset_transform();
+        canvas.line_to(1.0, 2.0);
+        assert!(matches!(canvas.commands[..], [Command::MoveTo(10.0, 20.0), .., Command::LineTo(1.0, 2.0)]));
+        canvas.restore();
+        assert!(canvas.current_transform().is_identity());
+    }
+
+    #[test]
+    fn test_append_after_flatten() {
+        let mut canvas = Canvas::new();
+        let mut list = DisplayList::new();
+
+        canvas.begin_path();
+        canvas.rect(0.0, 0.0, 10.0, 10.0);
+        canvas.fill(&mut list);
+        canvas.rect(20.0, 0.0, 10.0, 10.0);
+        canvas.fill(&mut list);
+        assert_eq!(canvas.cache.paths.len(), 2);
+
+        assert_eq!(canvas.path_bounds(), Rect::new(0.0, 0.0, 30.0, 10.0));
+        canvas.move_to(0.0, 0.0).line_to(0.0, 40.0);
+        assert_eq!(canvas.path_bounds(), Rect::new(0.0, 0.0, 30.0, 40.0));
+        canvas.stroke(&mut list);
+        assert_eq!(canvas.cache.paths.len(), 3);
+
+        // Changing the tolerance flattens the same path again.
+        canvas.begin_path();
+        canvas.circle(0.0, 0.0, 100.0);
+        canvas.fill(&mut list);
+        let fine = canvas.cache.points.len();
+        canvas.set_tessellation_tolerance(2.0);
+        canvas.fill(&mut list);
+        assert!(canvas.cache.points.len() < fine);
+    }
+
+    #[test]
+    fn test_stroke_width() {
+        /// Keeps the line widths passed to `stroke`.
+        struct LineWidths(Vec<Scalar>);
+
+        impl CanvasRenderer for LineWidths {
+            fn stroke(&mut self, _: &Paint, _: &CompositeState, _: &Scissor, _: usize, _: Scalar, line_width: Scalar, _: Paths) {
+                self.0.push(line_width);
+            }
+            fn fill(&mut self, _: &Paint, _: &CompositeState, _: &Scissor, _: usize, _: Scalar, _: Rect, _: Paths) {}
+            fn clip(&mut self, _: usize, _: Paths) {}
+            fn push_layer(&mut self, _: Scalar, _: &CompositeState, _: usize) {}
+            fn pop_layer(&mut self) {}
+        }
+
+        let mut canvas = Canvas::new();
+        let mut widths = LineWidths(Vec::new());
+        canvas.begin_path();
+        canvas.move_to(0.0, 0.0).line_to(10.0, 0.0);
+        for &(scale, width) in [(2.0, 3.0), (0.5, 1.0), (1.0, 500.0)].iter() {
+            canvas.set_transform(Transform::scale(scale, scale));
+            canvas.set_stroke_width(width);
+            canvas.stroke(&mut widths);
+        }
+        // The widths the strokes were tessellated with: scaled, bumped to the fringe and clamped.
+        assert_eq!(widths.0, [6.0, 1.0, 200.0]);
+    }
+
+    #[test]
+    fn test_scissor() {
+        let mut canvas = Canvas::new();
+        canvas.save();
+        canvas.set_transform(Transform::scale(2.0, 2.0));
+        canvas.scissor(10.0, 20.0, 30.0, -5.0);
+        assert_eq!(canvas.state.scissor.extent, [15.0, 0.0]);
+        assert_eq!(canvas.state.scissor.transform.transform_point(15.0, 0.0), (80.0, 40.0));
+        canvas.reset_scissor();
+        assert_eq!(canvas.state.scissor.extent, [-1.0; 2]);
+
+        canvas.scissor(10.0, 20.0, 30.0, 40.0);
+        canvas.restore();
+        assert_eq!(canvas.state.scissor.extent, [-1.0; 2]);
     }
 }
@@ -0,0 +1,597 @@
+use std::{collections::HashMap, fmt::Write as _};
+
+use crate::*;
+
+const CATALOG_ID: usize = 1;
+const PAGES_ID: usize = 2;
+const RESOURCES_ID: usize = 3;
+
+/// Writes the draws as a PDF document, one page per `begin_page`.
+///
+/// Fills, clips and scissors become PDF paths, stroke outlines are filled from their
+/// tessellation, and linear and radial gradients become shadings, with a soft mask for
+/// translucent stops. The shadings always extend the end colors, so `SpreadMode::Repeat` and
+/// `SpreadMode::Reflect` are drawn as `SpreadMode::Pad`. Box and sweep gradients and images are
+/// drawn with `inner_color`, and only the blend mode of the composite state is kept. Turn off
+/// `set_shape_anti_alias` for exact outlines, since viewers already anti-alias the paths.
+///
+/// Draws before the first `begin_page` are dropped, and layers still open when a page ends are
+/// composited onto it.
+pub struct PdfCanvasRenderer {
+    /// Object bodies, object `n` being at index `n - 1`.
+    objects: Vec<Vec<u8>>,
+    pages: Vec<usize>,
+    page: Option<Page>,
+    layers: Vec<Layer>,
+    clips: Vec<String>,
+    /// Indices of the graphics states by their dictionary entries.
+    ext_gstates: HashMap<String, usize>,
+    ext_gstate_ids: Vec<usize>,
+    shadings: Vec<usize>,
+    xobjects: Vec<usize>,
+}
+
+struct Page {
+    width: Scalar,
+    height: Scalar,
+    content: String,
+}
+
+struct Layer {
+    content: String,
+    opacity: Scalar,
+    blend_mode: BlendMode,
+    clip_depth: usize,
+}
+
+impl Default for PdfCanvasRenderer {
+    fn default() -> Self {
+        PdfCanvasRenderer::new()
+    }
+}
+
+impl PdfCanvasRenderer {
+    pub fn new() -> PdfCanvasRenderer {
+        PdfCanvasRenderer {
+            // The catalog, the page tree and the shared resources are written by `finish`.
+            objects: vec![Vec::new(); 3],
+            pages: Vec::new(),
+            page: None,
+            layers: Vec::new(),
+            clips: Vec::new(),
+            ext_gstates: HashMap::new(),
+            ext_gstate_ids: Vec::new(),
+            shadings: Vec::new(),
+            xobjects: Vec::new(),
+        }
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.pages.len() + self.page.is_some() as usize
+    }
+
+    /// Starts a new page of `width` by `height` points, ending the current one. The canvas
+    /// coordinates start at the top-left of the page.
+    pub fn begin_page(&mut self, width: Scalar, height: Scalar) {
+        self.end_page();
+        self.page = Some(Page {
+            width,
+            height,
+            content: String::new(),
+        });
+    }
+
+    /// Ends the current page, compositing the layers left open onto it.
+    pub fn end_page(&mut self) {
+        if self.page.is_none() {
+            return;
+        }
+        while !self.layers.is_empty() {
+            self.pop_layer();
+        }
+        let page = self.page.take().unwrap();
+        self.clips.clear();
+
+        let content = format!("1 0 0 -1 0 {} cm\n{}", num(page.height), page.content);
+        let contents = self.add_stream(String::new(), content.into_bytes());
+        let id = self.add_object(format!(
+            "<< /Type /Page /Parent {} 0 R /MediaBox [0 0 {} {}] /Resources {} 0 R /Contents {} 0 R >>",
+            PAGES_ID, num(page.width), num(page.height), RESOURCES_ID, contents,
+        ).into_bytes());
+        self.pages.push(id);
+    }
+
+    /// Ends the current page and returns the PDF file.
+    pub fn finish(mut self) -> Vec<u8> {
+        self.end_page();
+
+        self.objects[CATALOG_ID - 1] = format!("<< /Type /Catalog /Pages {} 0 R >>", PAGES_ID).into_bytes();
+        let kids: Vec<String> = self.pages.iter().map(|id| format!("{} 0 R", id)).collect();
+        self.objects[PAGES_ID - 1] = format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids.join(" "), self.pages.len()).into_bytes();
+
+        let mut resources = String::from("<< /ExtGState <<");
+        for (index, id) in self.ext_gstate_ids.iter().enumerate() {
+            let _ = write!(resources, " /GS{} {} 0 R", index, id);
+        }
+        resources.push_str(" >> /Shading <<");
+        for (index, id) in self.shadings.iter().enumerate() {
+            let _ = write!(resources, " /Sh{} {} 0 R", index, id);
+        }
+        resources.push_str(" >> /XObject <<");
+        for (index, id) in self.xobjects.iter().enumerate() {
+            let _ = write!(resources, " /X{} {} 0 R", index, id);
+        }
+        resources.push_str(" >> >>");
+        self.objects[RESOURCES_ID - 1] = resources.into_bytes();
+
+        let mut file = b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n".to_vec();
+        let mut offsets = Vec::with_capacity(self.objects.len());
+        for (index, object) in self.objects.iter().enumerate() {
+            offsets.push(file.len());
+            file.extend_from_slice(format!("{} 0 obj\n", index + 1).as_bytes());
+            file.extend_from_slice(object);
+            file.extend_from_slice(b"\nendobj\n");
+        }
+
+        let xref = file.len();
+        let mut trailer = format!("xref\n0 {}\n0000000000 65535 f \n", self.objects.len() + 1);
+        for offset in offsets {
+            let _ = writeln!(trailer, "{:010} 00000 n ", offset);
+        }
+        let _ = write!(trailer, "trailer\n<< /Size {} /Root {} 0 R >>\nstartxref\n{}\n%%EOF\n", self.objects.len() + 1, CATALOG_ID, xref);
+        file.extend_from_slice(trailer.as_bytes());
+        file
+    }
+
+    fn add_object(&mut self, object: Vec<u8>) -> usize {
+        self.objects.push(object);
+        self.objects.len()
+    }
+
+    fn add_stream(&mut self, dict: String, data: Vec<u8>) -> usize {
+        let mut object = format!("<< {}/Length {} >>\nstream\n", dict, data.len()).into_bytes();
+        object.extend_from_slice(&data);
+        object.extend_from_slice(b"\nendstream");
+        self.add_object(object)
+    }
+
+    /// Returns the content of the innermost layer or of the page, if a page was started.
+    fn content(&mut self) -> Option<&mut String> {
+        let page = self.page.as_mut()?;
+        match self.layers.last_mut() {
+            Some(layer) => Some(&mut layer.content),
+            None => Some(&mut page.content),
+        }
+    }
+
+    /// Returns the resource name of a graphics state with the `entries` dictionary entries.
+    fn ext_gstate(&mut self, entries: String) -> String {
+        let index = match self.ext_gstates.get(&entries) {
+            Some(&index) => index,
+            None => {
+                let id = self.add_object(format!("<< /Type /ExtGState {} >>", entries).into_bytes());
+                self.ext_gstate_ids.push(id);
+                self.ext_gstates.insert(entries, self.ext_gstate_ids.len() - 1);
+                self.ext_gstate_ids.len() - 1
+            }
+        };
+        format!("/GS{}", index)
+    }
+
+    fn add_shading(&mut self, shading: String) -> String {
+        let id = self.add_object(shading.into_bytes());
+        self.shadings.push(id);
+        format!("/Sh{}", self.shadings.len() - 1)
+    }
+
+    fn add_xobject(&mut self, dict: String, content: String) -> String {
+        let id = self.add_stream(dict, content.into_bytes());
+        self.xobjects.push(id);
+        format!("/X{}", self.xobjects.len() - 1)
+    }
+
+    /// Starts a draw, clipped by the clip paths and the scissor.
+    fn begin_draw(&mut self, scissor: Option<&Scissor>, clip_depth: usize) -> String {
+        let mut content = String::from("q\n");
+        for clip in self.clips.iter().take(clip_depth) {
+            content.push_str(clip);
+            content.push_str("W n\n");
+        }
+        if let Some(scissor) = scissor {
+            if scissor.extent[0] >= -0.5 && scissor.extent[1] >= -0.5 {
+                let [ex, ey] = scissor.extent;
+                polygon(&mut content, [(-ex, -ey), (ex, -ey), (ex, ey), (-ex, ey)].iter().map(|&(x, y)| scissor.transform.transform_point(x, y)));
+                content.push_str("W n\n");
+            }
+        }
+        content
+    }
+
+    fn draw(&mut self, paint: &Paint, composite: &CompositeState, scissor: &Scissor, clip_depth: usize, path: String) {
+        // Without a page, the shadings and graphics states would be written for nothing.
+        if self.page.is_none() {
+            return;
+        }
+        let mut content = self.begin_draw(Some(scissor), clip_depth);
+        let blend_mode = composite.blend_mode;
+
+        match gradient(paint) {
+            Some((shading_type, coords, domain, transform)) => {
+                content.push_str(&path);
+                content.push_str("W n\n");
+                let _ = writeln!(content, "{} cm", transform_operands(&transform));
+
+                let shading = format!(
+                    "<< /ShadingType {} /ColorSpace /DeviceRGB /Coords [{}] /Domain [{} 1] /Extend [true true] /Function {} >>",
+                    shading_type, coords, num(domain), function(paint, |color| format!("{} {} {}", num(color[0]), num(color[1]), num(color[2]))),
+                );
+                let shading = self.add_shading(shading);
+
+                let alphas = stops(paint).iter().map(|stop| stop.1[3]).collect::<Vec<_>>();
+                let mut entries = format!("/BM /{}", blend_mode_name(blend_mode));
+                if alphas.iter().all(|&alpha| alpha == alphas[0]) {
+                    let _ = write!(entries, " /ca {}", num(alphas[0]));
+                } else {
+                    // Translucent stops become a luminosity mask drawn with a gray shading.
+                    let mask = format!(
+                        "<< /ShadingType {} /ColorSpace /DeviceGray /Coords [{}] /Domain [{} 1] /Extend [true true] /Function {} >>",
+                        shading_type, coords, num(domain), function(paint, |color| num(color[3])),
+                    );
+                    let mask = self.add_shading(mask);
+                    let group = self.add_stream(
+                        format!("/Type /XObject /Subtype /Form /BBox [-1e6 -1e6 1e6 1e6] /Group << /S /Transparency /CS /DeviceGray >> /Resources {} 0 R ", RESOURCES_ID),
+                        format!("{} sh", mask).into_bytes(),
+                    );
+                    let _ = write!(entries, " /SMask << /S /Luminosity /G {} 0 R >>", group);
+                }
+                let gs = self.ext_gstate(entries);
+                let _ = writeln!(content, "{} gs\n{} sh", gs, shading);
+            }
+            None => {
+                let color = paint.inner_color;
+                if color[3] < 1.0 || blend_mode != BlendMode::Normal {
+                    let gs = self.ext_gstate(format!("/BM /{} /ca {}", blend_mode_name(blend_mode), num(color[3])));
+                    let _ = writeln!(content, "{} gs", gs);
+                }
+                let _ = writeln!(content, "{} {} {} rg", num(color[0]), num(color[1]), num(color[2]));
+                content.push_str(&path);
+                content.push_str("f\n");
+            }
+        }
+
+        content.push_str("Q\n");
+        if let Some(page_content) = self.content() {
+            page_content.push_str(&content);
+        }
+    }
+}
+
+impl CanvasRenderer for PdfCanvasRenderer {
+    fn stroke(&mut self, paint: &Paint, composite: &CompositeState, scissor: &Scissor, clip_depth: usize, _fringe: Scalar, _line_width: Scalar, paths: Paths) {
+        // The outline of a strip isn't simple where joins overlap, so its triangles are
+        // filled instead, all turned the same way so that they add up with nonzero winding.
+        let mut path = String::new();
+        for path_verts in paths.iter() {
+            let stroke = match path_verts.stroke() {
+                Some(stroke) => stroke,
+                None => continue,
+            };
+            for triangle in stroke.windows(3) {
+                let (a, b, c) = (&triangle[0], &triangle[1], &triangle[2]);
+                let area = (b.x - a.x) * (c.y - a.y) - (c.x - a.x) * (b.y - a.y);
+                if area > 0.0 {
+                    polygon(&mut path, [(a.x, a.y), (b.x, b.y), (c.x, c.y)].iter().copied());
+                } else if area < 0.0 {
+                    polygon(&mut path, [(a.x, a.y), (c.x, c.y), (b.x, b.y)].iter().copied());
+                }
+            }
+        }
+        if !path.is_empty() {
+            self.draw(paint, composite, scissor, clip_depth, path);
+        }
+    }
+
+    fn fill(&mut self, paint: &Paint, composite: &CompositeState, scissor: &Scissor, clip_depth: usize, _fringe: Scalar, _bounds: Rect, paths: Paths) {
+        let path = fill_path(paths);
+        if !path.is_empty() {
+            self.draw(paint, composite, scissor, clip_depth, path);
+        }
+    }
+
+    fn clip(&mut self, depth: usize, paths: Paths) {
+        self.clips.truncate(depth);
+        self.clips.push(fill_path(paths));
+    }
+
+    fn push_layer(&mut self, opacity: Scalar, composite: &CompositeState, clip_depth: usize) {
+        self.layers.push(Layer {
+            content: String::new(),
+            opacity,
+            blend_mode: composite.blend_mode,
+            clip_depth,
+        });
+    }
+
+    fn pop_layer(&mut self) {
+        let layer = match self.layers.pop() {
+            Some(layer) => layer,
+            None => return,
+        };
+
+        let (width, height) = match &self.page {
+            Some(page) => (page.width, page.height),
+            None => return,
+        };
+        let xobject = self.add_xobject(
+            format!("/Type /XObject /Subtype /Form /BBox [0 0 {} {}] /Group << /S /Transparency >> /Resources {} 0 R ", num(width), num(height), RESOURCES_ID),
+            layer.content,
+        );
+        let gs = self.ext_gstate(format!("/BM /{} /ca {} /CA {}", blend_mode_name(layer.blend_mode), num(layer.opacity), num(layer.opacity)));
+        let mut content = self.begin_draw(None, layer.clip_depth);
+        let _ = writeln!(content, "{} gs\n{} Do\nQ", gs, xobject);
+        if let Some(page_content) = self.content() {
+            page_content.push_str(&content);
+        }
+    }
+}
+
+/// Formats a number for a content stream, which doesn't allow exponents.
+fn num(value: Scalar) -> String {
+    if !value.is_finite() {
+        return "0".to_string();
+    }
+    let text = format!("{:.3}", value);
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    if text == "-0" {
+        "0".to_string()
+    } else {
+        text.to_string()
+    }
+}
+
+fn transform_operands(t: &Transform) -> String {
+    t.e.iter().map(|&e| num(e)).collect::<Vec<_>>().join(" ")
+}
+
+fn polygon<I>(content: &mut String, points: I) where I: Iterator<Item=(Scalar, Scalar)> {
+    for (i, (x, y)) in points.enumerate() {
+        let _ = writeln!(content, "{} {} {}", num(x), num(y), if i == 0 { "m" } else { "l" });
+    }
+    content.push_str("h\n");
+}
+
+fn fill_path(paths: Paths) -> String {
+    let mut path = String::new();
+    for path_verts in paths.iter() {
+        if let Some(fill) = path_verts.fill() {
+            if fill.len() >= 3 {
+                polygon(&mut path, fill.iter().map(|vert| (vert.x, vert.y)));
+            }
+        }
+    }
+    path
+}
+
+fn blend_mode_name(blend_mode: BlendMode) -> &'static str {
+    match blend_mode {
+        BlendMode::Normal => "Normal",
+        BlendMode::Multiply => "Multiply",
+        BlendMode::Screen => "Screen",
+        BlendMode::Overlay => "Overlay",
+        BlendMode::Darken => "Darken",
+        BlendMode::Lighten => "Lighten",
+        BlendMode::ColorDodge => "ColorDodge",
+        BlendMode::ColorBurn => "ColorBurn",
+        BlendMode::HardLight => "HardLight",
+        BlendMode::SoftLight => "SoftLight",
+        BlendMode::Difference => "Difference",
+        BlendMode::Exclusion => "Exclusion",
+    }
+}
+
+/// Returns the shading type, coordinates, start of the domain and transform to paint space
+/// of linear and radial gradients.
+fn gradient(paint: &Paint) -> Option<(u32, String, Scalar, Transform)> {
+    if paint.image != 0 || paint.shape != GradientShape::Box || (paint.inner_color == paint.outer_color && paint.stops.is_empty()) {
+        return None;
+    }
+
+    let feather = paint.feather;
+    if paint.extent[0] == LINEAR_GRADIENT_EXTENT {
+        // The gradient goes along the y axis across the edge of the box.
+        let origin = Transform::translate(0.0, paint.extent[1]);
+        let coords = format!("0 {} 0 {}", num(-feather * 0.5), num(feather * 0.5));
+        Some((2, coords, 0.0, origin * paint.transform))
+    } else if paint.extent[0] == paint.extent[1] && paint.radius >= paint.extent[0] {
+        // The gradient goes across the circle of the box, starting at the center when the
+        // feather is larger than the circle.
+        let inner = paint.radius - feather * 0.5;
+        let outer = paint.radius + feather * 0.5;
+        let (inner, domain) = if inner < 0.0 { (0.0, -inner / feather) } else { (inner, 0.0) };
+        let coords = format!("0 0 {} 0 0 {}", num(inner), num(outer));
+        Some((3, coords, domain, paint.transform))
+    } else {
+        None
+    }
+}
+
+/// Returns the gradient stops covering the whole range from 0 to 1.
+fn stops(paint: &Paint) -> Vec<(Scalar, [f32; 4])> {
+    if paint.stops.is_empty() {
+        return vec![(0.0, paint.inner_color), (1.0, paint.outer_color)];
+    }
+    let mut stops = paint.stops.clone();
+    if stops[0].0 > 0.0 {
+        stops.insert(0, (0.0, stops[0].1));
+    }
+    if stops[stops.len() - 1].0 < 1.0 {
+        stops.push((1.0, stops[stops.len() - 1].1));
+    }
+    stops
+}
+
+/// Writes a function interpolating `components` of the gradient colors.
+fn function<F>(paint: &Paint, components: F) -> String where F: Fn(&[f32; 4]) -> String {
+    let stops = stops(paint);
+    let interpolate = |from: &[f32; 4], to: &[f32; 4]| {
+        format!("<< /FunctionType 2 /Domain [0 1] /C0 [{}] /C1 [{}] /N 1 >>", components(from), components(to))
+    };
+    if stops.len() == 2 {
+        return interpolate(&stops[0].1, &stops[1].1);
+    }
+
+    let functions: Vec<String> = stops.windows(2).map(|pair| interpolate(&pair[0].1, &pair[1].1)).collect();
+    let bounds: Vec<String> = stops[1..stops.len() - 1].iter().map(|stop| num(stop.0)).collect();
+    let encode = vec!["0 1"; functions.len()];
+    format!(
+        "<< /FunctionType 3 /Domain [0 1] /Functions [{}] /Bounds [{}] /Encode [{}] >>",
+        functions.join(" "), bounds.join(" "), encode.join(" "),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Checks that each cross-reference entry points at its object.
+    fn check_xref(file: &[u8]) -> usize {
+        let text = String::from_utf8_lossy(file);
+        let start: usize = text.rsplit("startxref\n").next().unwrap().lines().next().unwrap().parse().unwrap();
+        let xref = String::from_utf8_lossy(&file[start..]);
+        let mut lines = xref.lines();
+        assert_eq!(lines.next(), Some("xref"));
+        let count: usize = lines.next().unwrap().split(' ').nth(1).unwrap().parse().unwrap();
+        lines.next();
+        for id in 1..count {
+            let offset: usize = lines.next().unwrap()[..10].parse().unwrap();
+            assert!(file[offset..].starts_with(format!("{} 0 obj\n", id).as_bytes()));
+        }
+        count - 1
+    }
+
+    #[test]
+    fn test_num() {
+        assert_eq!(num(1.0), "1");
+        assert_eq!(num(-0.0001), "0");
+        assert_eq!(num(0.125), "0.125");
+        assert_eq!(num(1e6), "1000000");
+        assert_eq!(num(f32::NAN), "0");
+    }
+
+    #[test]
+    fn test_pages() {
+        let mut pdf = PdfCanvasRenderer::new();
+        let mut canvas = Canvas::new();
+        canvas.set_shape_anti_alias(false);
+
+        pdf.begin_page(200.0, 100.0);
+        canvas.begin_path();
+        canvas.rect(10.0, 10.0, 50.0, 50.0);
+        canvas.set_fill_color(Color::rgba(255, 0, 0, 128));
+        canvas.fill(&mut pdf);
+        canvas.begin_path();
+        canvas.move_to(10.0, 80.0).line_to(100.0, 90.0).line_to(190.0, 80.0);
+        canvas.set_stroke_width(4.0);
+        canvas.stroke(&mut pdf);
+
+        pdf.begin_page(200.0, 100.0);
+        canvas.save();
+        canvas.scissor(50.0, 25.0, 100.0, 50.0);
+        canvas.begin_path();
+        canvas.circle(100.0, 50.0, 40.0);
+        canvas.clip(&mut pdf);
+        canvas.push_layer(&mut pdf, 0.5, BlendMode::Multiply);
+        let paint = canvas.linear_gradient(0.0, 0.0, 200.0, 0.0, Color::rgb(255, 0, 0), Color::rgba(0, 0, 255, 0));
+        canvas.set_fill_paint(&paint);
+        canvas.begin_path();
+        canvas.rect(0.0, 0.0, 200.0, 100.0);
+        canvas.fill(&mut pdf);
+        let paint = canvas.radial_gradient_stops(100.0, 50.0, 0.0, 50.0, &[(0.0, Color::rgb(255, 255, 255)), (0.5, Color::rgb(0, 255, 0)), (1.0, Color::rgb(0, 0, 0))]);
+        canvas.set_fill_paint(&paint);
+        canvas.fill(&mut pdf);
+        canvas.pop_layer(&mut pdf);
+        canvas.restore();
+        assert_eq!(pdf.page_count(), 2);
+
+        let file = pdf.finish();
+        let text = String::from_utf8_lossy(&file);
+        assert!(text.starts_with("%PDF-1.4\n"));
+        assert!(text.ends_with("%%EOF\n"));
+        assert!(check_xref(&file) > 10);
+        assert!(text.contains("/Type /Pages /Kids [") && text.contains("/Count 2"));
+        assert!(text.contains("1 0 0 -1 0 100 cm\n"));
+        assert!(text.contains("/BM /Normal /ca 0.502"));
+        assert!(text.contains("/ShadingType 2 /ColorSpace /DeviceRGB"));
+        assert!(text.contains("/ShadingType 3 /ColorSpace /DeviceRGB /Coords [0 0 0 0 0 50] /Domain [0 1]"));
+        assert!(text.contains("/FunctionType 3 /Domain [0 1]"));
+        assert!(text.contains("/SMask << /S /Luminosity"));
+        assert!(text.contains("/BM /Multiply /ca 0.5 /CA 0.5"));
+        assert!(text.contains("/X0 Do"));
+        assert!(text.contains("150 75 l\n"));
+    }
+
+    #[test]
+    fn test_gradients() {
+        let draw = |paint: &Paint| {
+            let mut pdf = PdfCanvasRenderer::new();
+            let mut canvas = Canvas::new();
+            pdf.begin_page(200.0, 100.0);
+            canvas.begin_path();
+            canvas.rect(0.0, 0.0, 200.0, 100.0);
+            canvas.set_fill_paint(paint);
+            canvas.fill(&mut pdf);
+            String::from_utf8_lossy(&pdf.finish()).into_owned()
+        };
+        let canvas = Canvas::new();
+
+        let mut paint = canvas.linear_gradient(0.0, 0.0, 50.0, 0.0, Color::rgb(255, 0, 0), Color::rgb(0, 0, 255));
+        let pad = draw(&paint);
+        assert!(pad.contains("/ShadingType 2 /ColorSpace /DeviceRGB /Coords [0 -25 0 25] /Domain [0 1] /Extend [true true]"));
+        paint.spread = SpreadMode::Repeat;
+        assert_eq!(draw(&paint), pad);
+        paint.spread = SpreadMode::Reflect;
+        assert_eq!(draw(&paint), pad);
+
+        // A box gradient wider than the page isn't a linear gradient.
+        let mut paint = Paint::color([1.0, 0.0, 0.0, 1.0]);
+        paint.transform = Transform::translate(100.0, 50.0);
+        paint.extent = [2e4, 2e4];
+        paint.feather = 10.0;
+        paint.outer_color = [0.0, 0.0, 1.0, 1.0];
+        let text = draw(&paint);
+        assert!(!text.contains("/ShadingType"));
+        assert!(text.contains("1 0 0 rg"));
+    }
+
+    #[test]
+    fn test_unbalanced_pages() {
+        let mut pdf = PdfCanvasRenderer::new();
+        let mut canvas = Canvas::new();
+        let fill = |canvas: &mut Canvas, pdf: &mut PdfCanvasRenderer| {
+            canvas.begin_path();
+            canvas.rect(10.0, 10.0, 50.0, 50.0);
+            canvas.fill(pdf);
+        };
+
+        // Nothing is drawn before the first page.
+        canvas.push_layer(&mut pdf, 0.5, BlendMode::Normal);
+        fill(&mut canvas, &mut pdf);
+        canvas.pop_layer(&mut pdf);
+        fill(&mut canvas, &mut pdf);
+        assert_eq!(pdf.page_count(), 0);
+        assert_eq!(pdf.objects.len(), 3);
+
+        // Layers left open are composited when their page ends.
+        pdf.begin_page(200.0, 100.0);
+        canvas.push_layer(&mut pdf, 0.5, BlendMode::Normal);
+        fill(&mut canvas, &mut pdf);
+        pdf.begin_page(200.0, 100.0);
+        canvas.push_layer(&mut pdf, 0.5, BlendMode::Screen);
+        fill(&mut canvas, &mut pdf);
+        assert_eq!(pdf.page_count(), 2);
+
+        let text = String::from_utf8_lossy(&pdf.finish()).into_owned();
+        assert!(text.contains("/X0 Do") && text.contains("/X1 Do"));
+        assert!(text.contains("/BM /Screen /ca 0.5 /CA 0.5"));
+    }
+}
@@ -0,0 +1,497 @@
+use std::io::{self, Read, Write};
+
+use crate::*;
+use crate::command_stream::Input;
+
+/// Version of the remote drawing protocol.
+pub const REMOTE_PROTOCOL_VERSION: u16 = 1;
+
+const MAGIC: &[u8; 4] = b"RDRP";
+
+const MESSAGE_FILL: u8 = 0x01;
+const MESSAGE_STROKE: u8 = 0x02;
+const MESSAGE_CLIP: u8 = 0x03;
+const MESSAGE_PUSH_LAYER: u8 = 0x04;
+const MESSAGE_POP_LAYER: u8 = 0x05;
+const MESSAGE_END_FRAME: u8 = 0x06;
+
+/// Sends the tessellated draws to a `RemoteReceiver` over `writer`, typically a `TcpStream` or
+/// a `UnixStream`.
+///
+/// Each call is sent as one message, so wrap unbuffered writers in a `BufWriter`. The renderer
+/// methods can't fail, so the first write error is kept and returned by `end_frame`, and
+/// nothing more is sent after it.
+///
+/// Images aren't transmitted, and the sender's image ids mean nothing to the receiver, so
+/// strokes and fills with an image paint are skipped instead of sent. `RemoteReceiver` rejects
+/// messages with a non-zero image id.
+pub struct RemoteCanvasRenderer<W: Write> {
+    writer: W,
+    message: Vec<u8>,
+    error: Option<io::Error>,
+}
+
+impl<W: Write> RemoteCanvasRenderer<W> {
+    /// Starts the stream by sending the protocol header.
+    pub fn new(mut writer: W) -> io::Result<RemoteCanvasRenderer<W>> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&REMOTE_PROTOCOL_VERSION.to_le_bytes())?;
+        Ok(RemoteCanvasRenderer {
+            writer,
+            message: Vec::new(),
+            error: None,
+        })
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Marks the end of the frame and flushes the writer.
+    pub fn end_frame(&mut self) -> io::Result<()> {
+        self.begin_message(MESSAGE_END_FRAME);
+        self.send_message();
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+        self.writer.flush()
+    }
+
+    fn begin_message(&mut self, ty: u8) {
+        self.message.clear();
+        self.message.push(ty);
+        // Length of the payload, filled in by `send_message`.
+        self.message.extend_from_slice(&[0; 4]);
+    }
+
+    fn send_message(&mut self) {
+        if self.error.is_some() {
+            return;
+        }
+        let len = (self.message.len() - 5) as u32;
+        self.message[1..5].copy_from_slice(&len.to_le_bytes());
+        if let Err(error) = self.writer.write_all(&self.message) {
+            self.error = Some(error);
+        }
+    }
+}
+
+impl<W: Write> CanvasRenderer for RemoteCanvasRenderer<W> {
+    fn stroke(&mut self, paint: &Paint, composite: &CompositeState, scissor: &Scissor, clip_depth: usize, fringe: Scalar, line_width: Scalar, paths: Paths) {
+        if paint.image != 0 {
+            return;
+        }
+        self.begin_message(MESSAGE_STROKE);
+        let buf = &mut self.message;
+        write_paint(buf, paint);
+        write_composite(buf, composite);
+        write_scissor(buf, scissor);
+        write_u32(buf, clip_depth as u32);
+        write_f32(buf, fringe);
+        write_f32(buf, line_width);
+        write_paths(buf, paths.cache);
+        self.send_message();
+    }
+
+    fn fill(&mut self, paint: &Paint, composite: &CompositeState, scissor: &Scissor, clip_depth: usize, fringe: Scalar, bounds: Rect, paths: Paths) {
+        if paint.image != 0 {
+            return;
+        }
+        self.begin_message(MESSAGE_FILL);
+        let buf = &mut self.message;
+        write_paint(buf, paint);
+        write_composite(buf, composite);
+        write_scissor(buf, scissor);
+        write_u32(buf, clip_depth as u32);
+        write_f32(buf, fringe);
+        write_rect(buf, &bounds);
+        write_paths(buf, paths.cache);
+        self.send_message();
+    }
+
+    fn clip(&mut self, depth: usize, paths: Paths) {
+        self.begin_message(MESSAGE_CLIP);
+        write_u32(&mut self.message, depth as u32);
+        write_paths(&mut self.message, paths.cache);
+        self.send_message();
+    }
+
+    fn push_layer(&mut self, opacity: Scalar, composite: &CompositeState, clip_depth: usize) {
+        self.begin_message(MESSAGE_PUSH_LAYER);
+        write_f32(&mut self.message, opacity);
+        write_composite(&mut self.message, composite);
+        write_u32(&mut self.message, clip_depth as u32);
+        self.send_message();
+    }
+
+    fn pop_layer(&mut self) {
+        self.begin_message(MESSAGE_POP_LAYER);
+        self.send_message();
+    }
+}
+
+/// Reads the draws sent by a `RemoteCanvasRenderer` and passes them to a local renderer.
+///
+/// Messages are checked before they reach the renderer, so a malformed stream is an
+/// `InvalidData` error rather than a panic: vertex ranges must be in bounds, image ids zero and
+/// scissors finite.
+pub struct RemoteReceiver<R: Read> {
+    reader: R,
+    message: Vec<u8>,
+}
+
+impl<R: Read> RemoteReceiver<R> {
+    /// Reads and checks the protocol header.
+    pub fn new(mut reader: R) -> io::Result<RemoteReceiver<R>> {
+        let mut header = [0; 6];
+        reader.read_exact(&mut header)?;
+        if &header[..4] != MAGIC {
+            return Err(invalid_data(CommandStreamError::InvalidMagic));
+        }
+        let version = u16::from_le_bytes([header[4], header[5]]);
+        if version != REMOTE_PROTOCOL_VERSION {
+            return Err(invalid_data(CommandStreamError::UnsupportedVersion(version)));
+        }
+        Ok(RemoteReceiver {
+            reader,
+            message: Vec::new(),
+        })
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Passes the draws of the next frame to `renderer`, blocking until the whole frame was
+    /// received. Returns `false` if the sender closed the stream instead of starting a frame.
+    ///
+    /// `renderer` isn't flushed, so draw the frame with `GlCanvasRenderer::flush` afterwards.
+    pub fn receive_frame<T>(&mut self, renderer: &mut T) -> io::Result<bool> where T: CanvasRenderer {
+        let mut first = true;
+        loop {
+            let mut header = [0; 5];
+            if first {
+                // A clean end of the stream is only expected between frames.
+                match self.reader.read(&mut header[..1])? {
+                    0 => return Ok(false),
+                    _ => self.reader.read_exact(&mut header[1..])?,
+                }
+                first = false;
+            } else {
+                self.reader.read_exact(&mut header)?;
+            }
+
+            let len = u32::from_le_bytes([header[1], header[2], header[3], header[4]]) as usize;
+            self.message.clear();
+            (&mut self.reader).take(len as u64).read_to_end(&mut self.message)?;
+            if self.message.len() != len {
+                return Err(invalid_data(CommandStreamError::UnexpectedEnd));
+            }
+
+            let mut input = Input { bytes: &self.message };
+            match header[0] {
+                MESSAGE_END_FRAME => return Ok(true),
+                ty => dispatch(ty, &mut input, renderer).map_err(invalid_data)?,
+            }
+            if !input.bytes.is_empty() {
+                return Err(invalid_data(CommandStreamError::InvalidValue));
+            }
+        }
+    }
+}
+
+/// Reads a paint, rejecting image ids since images aren't transmitted.
+fn read_remote_paint(input: &mut Input) -> Result<Paint, CommandStreamError> {
+    let paint = read_paint(input)?;
+    if paint.image != 0 {
+        return Err(CommandStreamError::InvalidValue);
+    }
+    Ok(paint)
+}
+
+fn dispatch<T>(ty: u8, input: &mut Input, renderer: &mut T) -> Result<(), CommandStreamError> where T: CanvasRenderer {
+    match ty {
+        MESSAGE_FILL => {
+            let paint = read_remote_paint(input)?;
+            let composite = read_composite(input)?;
+            let scissor = read_scissor(input)?;
+            let clip_depth = input.u32()? as usize;
+            let fringe = input.f32()?;
+            let bounds = read_rect(input)?;
+            let cache = read_paths(input)?;
+            renderer.fill(&paint, &composite, &scissor, clip_depth, fringe, bounds, Paths { cache: &cache });
+        }
+        MESSAGE_STROKE => {
+            let paint = read_remote_paint(input)?;
+            let composite = read_composite(input)?;
+            let scissor = read_scissor(input)?;
+            let clip_depth = input.u32()? as usize;
+            let fringe = input.f32()?;
+            let line_width = input.f32()?;
+            let cache = read_paths(input)?;
+            renderer.stroke(&paint, &composite, &scissor, clip_depth, fringe, line_width, Paths { cache: &cache });
+        }
+        MESSAGE_CLIP => {
+            let depth = input.u32()? as usize;
+            let cache = read_paths(input)?;
+            renderer.clip(depth, Paths { cache: &cache });
+        }
+        MESSAGE_PUSH_LAYER => {
+            let opacity = input.f32()?;
+            let composite = read_composite(input)?;
+            let clip_depth = input.u32()? as usize;
+            renderer.push_layer(opacity, &composite, clip_depth);
+        }
+        MESSAGE_POP_LAYER => renderer.pop_layer(),
+        _ => return Err(CommandStreamError::InvalidValue),
+    }
+    Ok(())
+}
+
+fn invalid_data(error: CommandStreamError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+fn write_rect(buf: &mut Vec<u8>, rect: &Rect) {
+    write_f32s(buf, &[rect.origin.x, rect.origin.y, rect.size.width, rect.size.height]);
+}
+
+fn read_rect(input: &mut Input) -> Result<Rect, CommandStreamError> {
+    let [x, y, width, height] = input.f32s()?;
+    Ok(Rect::new(x, y, width, height))
+}
+
+fn write_vertex_ref(buf: &mut Vec<u8>, vertex_ref: Option<PathVertexRef>) {
+    match vertex_ref {
+        Some(vertex_ref) => {
+            buf.push(1);
+            write_u32(buf, vertex_ref.first as u32);
+            write_u32(buf, vertex_ref.count as u32);
+        }
+        None => buf.push(0),
+    }
+}
+
+/// Writes the tessellated vertices of the paths. The flattened points stay on the sender.
+fn write_paths(buf: &mut Vec<u8>, cache: &PathCache) {
+    write_rect(buf, &cache.bounds);
+    write_u32(buf, cache.verts.len() as u32);
+    for vert in cache.verts.iter() {
+        write_f32s(buf, &[vert.x, vert.y, vert.u, vert.v]);
+    }
+    write_u32(buf, cache.paths.len() as u32);
+    for path in cache.paths.iter() {
+        buf.push(path.closed as u8);
+        buf.push(path.convex as u8);
+        buf.push(index_of(&WINDINGS, path.winding));
+        write_u32(buf, path.nbevel as u32);
+        write_vertex_ref(buf, path.stroke);
+        write_vertex_ref(buf, path.fill);
+    }
+}
+
+fn read_vertex_ref(input: &mut Input, nverts: usize) -> Result<Option<PathVertexRef>, CommandStreamError> {
+    match input.u8()? {
+        0 => Ok(None),
+        1 => {
+            let first = input.u32()? as usize;
+            let count = input.u32()? as usize;
+            // Out of range references would panic when the renderer slices the vertices.
+            if first.checked_add(count).is_none_or(|end| end > nverts) {
+                return Err(CommandStreamError::InvalidValue);
+            }
+            Ok(Some(PathVertexRef { first, count }))
+        }
+        _ => Err(CommandStreamError::InvalidValue),
+    }
+}
+
+fn read_paths(input: &mut Input) -> Result<PathCache, CommandStreamError> {
+    let mut cache = PathCache::new();
+    cache.bounds = read_rect(input)?;
+    let nverts = input.u32()? as usize;
+    cache.verts.reserve(nverts.min(input.bytes.len() / 16));
+    for _ in 0..nverts {
+        let [x, y, u, v] = input.f32s()?;
+        cache.verts.push(Vertex { x, y, u, v });
+    }
+    let npaths = input.u32()? as usize;
+    for _ in 0..npaths {
+        let closed = input.u8()? != 0;
+        let convex = input.u8()? != 0;
+        let winding = input.value(&WINDINGS)?;
+        let nbevel = input.u32()? as usize;
+        let stroke = read_vertex_ref(input, nverts)?;
+        let fill = read_vertex_ref(input, nverts)?;
+        cache.paths.push(PathBuilder {
+            first: 0,
+            count: 0,
+            closed,
+            winding,
+            nbevel,
+            convex,
+            stroke,
+            fill,
+        });
+    }
+    Ok(cache)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        net::{TcpListener, TcpStream},
+        thread,
+    };
+
+    use super::*;
+
+    fn draw_frame<R: CanvasRenderer>(renderer: &mut R) {
+        let mut canvas = Canvas::new();
+        canvas.begin_path();
+        canvas.rect(10.0, 10.0, 100.0, 50.0);
+        canvas.set_fill_color(Color::rgb(255, 0, 0));
+        canvas.fill(renderer);
+        canvas.clip(renderer);
+        canvas.push_layer(renderer, 0.5, BlendMode::Screen);
+        canvas.begin_path();
+        canvas.move_to(0.0, 0.0).line_to(40.0, 30.0).line_to(80.0, 0.0);
+        canvas.set_stroke_width(4.0);
+        canvas.stroke(renderer);
+        canvas.pop_layer(renderer);
+    }
+
+    #[test]
+    fn test_loopback() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let sender = thread::spawn(move || {
+            let stream = TcpStream::connect(addr).unwrap();
+            let mut renderer = RemoteCanvasRenderer::new(io::BufWriter::new(stream)).unwrap();
+            for _ in 0..2 {
+                draw_frame(&mut renderer);
+                renderer.end_frame().unwrap();
+            }
+        });
+
+        let (stream, _) = listener.accept().unwrap();
+        let mut receiver = RemoteReceiver::new(stream).unwrap();
+        let mut frames = Vec::new();
+        loop {
+            // Resend what was received, to compare it with what was drawn locally.
+            let mut received = RemoteCanvasRenderer::new(Vec::new()).unwrap();
+            if !receiver.receive_frame(&mut received).unwrap() {
+                break;
+            }
+            received.end_frame().unwrap();
+            frames.push(received.into_inner());
+        }
+        sender.join().unwrap();
+
+        let mut local = RemoteCanvasRenderer::new(Vec::new()).unwrap();
+        draw_frame(&mut local);
+        local.end_frame().unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0], local.get_ref()[..]);
+        assert_eq!(frames[1], local.get_ref()[..]);
+
+        let mut list = DisplayList::new();
+        let mut receiver = RemoteReceiver::new(&frames[0][..]).unwrap();
+        assert!(receiver.receive_frame(&mut list).unwrap());
+        assert_eq!(list.len(), 5);
+        assert!(!receiver.receive_frame(&mut list).unwrap());
+    }
+
+    #[test]
+    fn test_invalid_messages() {
+        assert_eq!(RemoteReceiver::new(&b"RDCS\x01\x00"[..]).err().unwrap().kind(), io::ErrorKind::InvalidData);
+
+        let mut list = DisplayList::new();
+        let truncated = b"RDRP\x01\x00\x05\x04\x00\x00\x00";
+        let mut receiver = RemoteReceiver::new(&truncated[..]).unwrap();
+        assert!(receiver.receive_frame(&mut list).is_err());
+
+        // A clip whose only path references vertices past the end.
+        let mut message = vec![MESSAGE_CLIP, 0, 0, 0, 0];
+        write_u32(&mut message, 0);
+        write_f32s(&mut message, &[0.0; 4]);
+        write_u32(&mut message, 0);
+        write_u32(&mut message, 1);
+        message.extend_from_slice(&[0, 0, 0]);
+        write_u32(&mut message, 0);
+        write_vertex_ref(&mut message, None);
+        write_vertex_ref(&mut message, Some(PathVertexRef { first: 0, count: 3 }));
+        let len = (message.len() - 5) as u32;
+        message[1..5].copy_from_slice(&len.to_le_bytes());
+        let mut stream = b"RDRP\x01\x00".to_vec();
+        stream.extend_from_slice(&message);
+        let mut receiver = RemoteReceiver::new(&stream[..]).unwrap();
+        assert_eq!(receiver.receive_frame(&mut list).err().unwrap().kind(), io::ErrorKind::InvalidData);
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    fn test_scissors() {
+        let send = |extent: [Scalar; 2]| {
+            let mut renderer = RemoteCanvasRenderer::new(Vec::new()).unwrap();
+            let scissor = Scissor { transform: Transform::translate(50.0, 40.0), extent };
+            let composite = CompositeState::from_op(CompositeOp::SourceOver);
+            let paint = Paint::color([1.0, 0.0, 0.0, 1.0]);
+            renderer.fill(&paint, &composite, &scissor, 0, 1.0, Rect::zero(), Paths { cache: &PathCache::new() });
+            renderer.end_frame().unwrap();
+            renderer.into_inner()
+        };
+
+        // Enabled scissors are passed on, the renderers clip with them.
+        let mut list = DisplayList::new();
+        let stream = send([20.0, 10.0]);
+        let mut receiver = RemoteReceiver::new(&stream[..]).unwrap();
+        assert!(receiver.receive_frame(&mut list).unwrap());
+        assert_eq!(list.len(), 1);
+
+        // Non-finite ones can't clip anything.
+        for &extent in [[f32::NAN, 10.0], [20.0, f32::INFINITY]].iter() {
+            let stream = send(extent);
+            let mut receiver = RemoteReceiver::new(&stream[..]).unwrap();
+            assert_eq!(receiver.receive_frame(&mut list).err().unwrap().kind(), io::ErrorKind::InvalidData);
+        }
+        assert_eq!(list.len(), 1);
+    }
+
+    #[test]
+    fn test_image_paints() {
+        let mut renderer = RemoteCanvasRenderer::new(Vec::new()).unwrap();
+        let mut canvas = Canvas::new();
+        canvas.begin_path();
+        canvas.rect(10.0, 10.0, 100.0, 50.0);
+        let paint = canvas.image_pattern(0.0, 0.0, 100.0, 50.0, 0.0, 1, 1.0);
+        canvas.set_fill_paint(&paint);
+        canvas.fill(&mut renderer);
+        renderer.end_frame().unwrap();
+        let mut list = DisplayList::new();
+        let mut receiver = RemoteReceiver::new(&renderer.get_ref()[..]).unwrap();
+        assert!(receiver.receive_frame(&mut list).unwrap());
+        assert!(list.is_empty());
+
+        // A fill with an image id, as sent by a sender that doesn't skip them.
+        let mut message = vec![MESSAGE_FILL, 0, 0, 0, 0];
+        write_paint(&mut message, &paint);
+        write_composite(&mut message, &CompositeState::from_op(CompositeOp::SourceOver));
+        write_scissor(&mut message, &Scissor { transform: Transform::identity(), extent: [-1.0, -1.0] });
+        write_u32(&mut message, 0);
+        write_f32(&mut message, 1.0);
+        write_rect(&mut message, &Rect::new(10.0, 10.0, 100.0, 50.0));
+        write_paths(&mut message, &PathCache::new());
+        let len = (message.len() - 5) as u32;
+        message[1..5].copy_from_slice(&len.to_le_bytes());
+        let mut stream = b"RDRP\x01\x00".to_vec();
+        stream.extend_from_slice(&message);
+        let mut receiver = RemoteReceiver::new(&stream[..]).unwrap();
+        assert_eq!(receiver.receive_frame(&mut list).err().unwrap().kind(), io::ErrorKind::InvalidData);
+        assert!(list.is_empty());
+    }
+}
@@ -1,13 +1,140 @@
 use std::{
+    collections::HashMap,
+    error::Error,
+    fmt,
     mem::*,
     ffi::*,
     ptr::*,
+    sync::{Arc, Weak},
 };
 use gl::types::*;
 
 use crate::*;
 
+/// OpenGL flavor the renderer targets, which picks the GLSL dialect and the GL features used.
+/// WebGL 1 is served by `Gles2` and WebGL 2 by `Gles3`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum GlBackend {
+    /// OpenGL 2.1 with `ARB_framebuffer_object`, or a later compatibility profile, with GLSL 1.20.
+    Gl2,
+    /// OpenGL 3.3 core profile with GLSL 3.30.
+    Gl3,
+    /// OpenGL ES 2.0 with GLSL ES 1.00.
+    Gles2,
+    /// OpenGL ES 3.0 with GLSL ES 3.00.
+    Gles3,
+}
+
+impl GlBackend {
+    fn uses_uniform_buffer(self) -> bool {
+        self == GlBackend::Gl3 || self == GlBackend::Gles3
+    }
+
+    fn uses_vertex_array(self) -> bool {
+        self == GlBackend::Gl3 || self == GlBackend::Gles3
+    }
+
+    /// Internal format of color textures, GLES 2 has no sized formats.
+    fn color_format(self) -> GLenum {
+        match self {
+            GlBackend::Gles2 => gl::RGBA,
+            _ => gl::RGBA8,
+        }
+    }
+
+    /// GLES 2 needs `OES_element_index_uint` for 32-bit indices.
+    fn supports_uint_indices(self) -> bool {
+        self != GlBackend::Gles2
+    }
+
+    fn supports_float_textures(self) -> bool {
+        self == GlBackend::Gl3 || self == GlBackend::Gles3
+    }
+
+    /// Whether sRGB writes have to be enabled with `FRAMEBUFFER_SRGB`, GLES does it whenever
+    /// the framebuffer is sRGB.
+    fn uses_framebuffer_srgb(self) -> bool {
+        self == GlBackend::Gl2 || self == GlBackend::Gl3
+    }
+
+    fn shader_header(self, stage: GLenum) -> String {
+        let mut header = String::from(match self {
+            GlBackend::Gl2 => "#version 120\n",
+            GlBackend::Gl3 => "#version 330 core\n",
+            GlBackend::Gles2 => "#version 100\n",
+            GlBackend::Gles3 => "#version 300 es\n",
+        });
+
+        if stage == gl::FRAGMENT_SHADER && (self == GlBackend::Gles2 || self == GlBackend::Gles3) {
+            header.push_str("#ifdef GL_FRAGMENT_PRECISION_HIGH\nprecision highp float;\n#else\nprecision mediump float;\n#endif\n");
+        }
+
+        header.push_str(&format!("#define UNIFORMARRAY_SIZE {}\n", UNIFORMARRAY_SIZE));
+        if self.uses_uniform_buffer() {
+            header.push_str("#define USE_UNIFORMBUFFER 1\n");
+            header.push_str("#define ATTRIBUTE in\n#define TEXTURE texture\n");
+            if stage == gl::VERTEX_SHADER {
+                header.push_str("#define VARYING out\n");
+            } else {
+                header.push_str("#define VARYING in\nout vec4 out_color;\n#define FRAG_COLOR out_color\n");
+            }
+        } else {
+            header.push_str("#define ATTRIBUTE attribute\n#define VARYING varying\n#define TEXTURE texture2D\n#define FRAG_COLOR gl_FragColor\n");
+        }
+
+        header
+    }
+}
+
+/// Debug view drawn over the frame by `flush`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum DebugMode {
+    None,
+    /// Outlines the triangles of the tessellated strips and fans.
+    Wireframe,
+    /// Highlights the anti-aliased fringes, brighter where `u` is further from 0.5.
+    Fringes,
+    /// Adds up the fans drawn into the stencil buffer, so overdraw shows as a brighter red.
+    StencilOverdraw,
+    /// Outlines the bounds of each fill and stroke.
+    BoundingBoxes,
+}
+
+/// Reasons the GL renderer can fail to initialize.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum RendererError {
+    /// A shader failed to compile, `log` is the info log of the driver.
+    ShaderCompile { stage: ShaderStage, log: String },
+    /// The shader program failed to link, `log` is the info log of the driver.
+    ProgramLink { log: String },
+    /// A uniform the renderer needs is missing from the linked program.
+    MissingUniform { name: &'static str },
+    /// The GL context doesn't provide the version required by the backend, `version` is
+    /// the `GL_VERSION` string of the context.
+    UnsupportedVersion { backend: GlBackend, version: String },
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ShaderStage {
+    Vertex,
+    Fragment,
+}
+
+impl fmt::Display for RendererError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RendererError::ShaderCompile { stage, log } => write!(f, "failed to compile {:?} shader: {}", stage, log),
+            RendererError::ProgramLink { log } => write!(f, "failed to link shader program: {}", log),
+            RendererError::MissingUniform { name } => write!(f, "missing uniform {}", name),
+            RendererError::UnsupportedVersion { backend, version } => write!(f, "{:?} backend is not supported by GL version {:?}", backend, version),
+        }
+    }
+}
+
+impl Error for RendererError {}
+
 pub struct GlCanvasRenderer {
+    backend: GlBackend,
     width: f32,
     height: f32,
     pixels_per_point: f32,
@@ -15,40 +142,69 @@ pub struct GlCanvasRenderer {
     draw_calls: Vec<DrawCall>,
     vao: GLuint,
     vbo: GLuint,
+    ebo: GLuint,
     ubo: GLuint,
     uniform_buffer: UniformBuffer,
     paths: Vec<BufferRef>,
     verts: Vec<ShaderVertex>,
+    backdrop: GLuint,
+    backdrop_size: [i32; 2],
+    clip_paths: Vec<ClipPath>,
+    clip_stack: Vec<usize>,
+    layers: Vec<Framebuffer>,
+    layer_stack: Vec<LayerState>,
+    images: HashMap<i32, GlImage>,
+    next_image: i32,
+    ramps: Vec<f32>,
+    ramp_texture: GLuint,
+    color_space: ColorSpace,
+    gradient_interpolation: GradientInterpolation,
+    backdrop_color_space: ColorSpace,
+    restore_state: bool,
+    indices: Vec<u32>,
+    stats: FrameStats,
+    debug_mode: DebugMode,
 }
 
 const FRAG_BINDING: GLuint = 0;
 
 impl GlCanvasRenderer {
-    pub fn new() -> GlCanvasRenderer {
-        let shader = Shader::load();
+    /// Creates a renderer for the current GL context, which must support `backend`.
+    pub fn new(backend: GlBackend) -> Result<GlCanvasRenderer, RendererError> {
+        check_version(backend)?;
+        let shader = Shader::load(backend)?;
 
         let mut vao = 0;
         let mut vbo = 0;
+        let mut ebo = 0;
         let mut ubo = 0;
         let frag_size;
 
         unsafe {
-            gl::GenVertexArrays(1, &mut vao);
-            gl::GenBuffers(1, &mut vbo);
+            if backend.uses_vertex_array() {
+                gl::GenVertexArrays(1, &mut vao);
+            }
 
-            gl::BindVertexArray(vao);
-            gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+            gl::GenBuffers(1, &mut vbo);
+            if backend.supports_uint_indices() {
+                gl::GenBuffers(1, &mut ebo);
+            }
 
-            gl::UniformBlockBinding(shader.prog.id, shader.loc_frag, FRAG_BINDING);
-            gl::GenBuffers(1, &mut ubo);
-            let mut align = 0;
-            gl::GetIntegerv(gl::UNIFORM_BUFFER_OFFSET_ALIGNMENT, &mut align);
-            frag_size = size_of::<Uniforms>() + align as usize - size_of::<Uniforms>() % align as usize;
+            if backend.uses_uniform_buffer() {
+                gl::UniformBlockBinding(shader.prog.id, shader.frag_block, FRAG_BINDING);
+                gl::GenBuffers(1, &mut ubo);
+                let mut align = 0;
+                gl::GetIntegerv(gl::UNIFORM_BUFFER_OFFSET_ALIGNMENT, &mut align);
+                frag_size = FRAG_BLOCK_SIZE.div_ceil(align as usize) * align as usize;
+            } else {
+                frag_size = FRAG_BLOCK_SIZE;
+            }
 
             gl::Finish();
         }
 
-        GlCanvasRenderer {
+        Ok(GlCanvasRenderer {
+            backend,
             width: 0.0,
             height: 0.0,
             pixels_per_point: 1.0,
@@ -56,6 +212,7 @@ impl GlCanvasRenderer {
             draw_calls: Vec::new(),
             vao,
             vbo,
+            ebo,
             ubo,
             uniform_buffer: UniformBuffer {
                 uniform_size: frag_size,
@@ -64,7 +221,24 @@ impl GlCanvasRenderer {
             },
             paths: Vec::new(),
             verts: Vec::new(),
-        }
+            backdrop: 0,
+            backdrop_size: [0, 0],
+            clip_paths: Vec::new(),
+            clip_stack: Vec::new(),
+            layers: Vec::new(),
+            layer_stack: Vec::new(),
+            images: HashMap::new(),
+            next_image: 0,
+            ramps: Vec::new(),
+            ramp_texture: 0,
+            color_space: ColorSpace::Srgb,
+            gradient_interpolation: GradientInterpolation::ColorSpace,
+            backdrop_color_space: ColorSpace::Srgb,
+            restore_state: false,
+            indices: Vec::new(),
+            stats: FrameStats::default(),
+            debug_mode: DebugMode::None,
+        })
     }
 
     pub fn set_viewport_size(&mut self, width: f32, height: f32, pixels_per_point: f32) {
@@ -76,6 +250,30 @@ impl GlCanvasRenderer {
         }
     }
 
+    /// Sets the color space used for blending, which applies to whole frames so it should be
+    /// set before drawing. `ColorSpace::LinearSrgb` needs an sRGB capable framebuffer, and
+    /// layers and framebuffers then get sRGB textures too.
+    pub fn set_color_space(&mut self, color_space: ColorSpace) {
+        self.color_space = color_space;
+    }
+
+    pub fn set_gradient_interpolation(&mut self, gradient_interpolation: GradientInterpolation) {
+        self.gradient_interpolation = gradient_interpolation;
+    }
+
+    /// When enabled, `flush` saves the GL state it touches and restores it afterwards, so rdraw
+    /// can draw on top of another renderer. Otherwise `flush` leaves that state at the GL
+    /// defaults, except for the viewport which is set by `set_viewport_size`. Without vertex
+    /// arrays, the pointers of the vertex attributes 0 and 1 aren't restored.
+    pub fn set_restore_state(&mut self, restore_state: bool) {
+        self.restore_state = restore_state;
+    }
+
+    /// Sets the debug view drawn over the following frames.
+    pub fn set_debug_mode(&mut self, debug_mode: DebugMode) {
+        self.debug_mode = debug_mode;
+    }
+
     pub fn clear(&mut self, r: u8, g: u8, b: u8, a: u8) {
         unsafe {
             gl::ClearColor(r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, a as f32 / 255.0);
@@ -83,6 +281,52 @@ impl GlCanvasRenderer {
         }
     }
 
+    /// Creates a framebuffer that can be rendered into with `bind_framebuffer`. Its texture
+    /// follows the current color space, so set that first.
+    pub fn create_framebuffer(&mut self, width: i32, height: i32) -> Framebuffer {
+        unsafe { Framebuffer::new(width, height, self.framebuffer_format()) }
+    }
+
+    /// Internal format of the textures drawn into. With `ColorSpace::LinearSrgb` they're sRGB, so
+    /// that blended values are stored encoded and decoded to linear light when sampled.
+    fn framebuffer_format(&self) -> GLenum {
+        match self.color_space {
+            ColorSpace::Srgb => self.backend.color_format(),
+            ColorSpace::LinearSrgb => gl::SRGB8_ALPHA8,
+        }
+    }
+
+    /// Flushes the pending draw calls and renders the following ones into `framebuffer`, or into
+    /// the default framebuffer for `None`. The viewport size has to be set for the new target.
+    pub fn bind_framebuffer(&mut self, framebuffer: Option<&Framebuffer>) {
+        self.flush_draw_calls();
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer.map(|framebuffer| framebuffer.fbo).unwrap_or(0));
+        }
+    }
+
+    /// Returns an image handle for the color texture of `framebuffer`, to be used with
+    /// `Canvas::image_pattern`. Once `framebuffer` is dropped, paints using the handle draw
+    /// nothing, and handles aren't reused for other framebuffers.
+    pub fn framebuffer_image(&mut self, framebuffer: &Framebuffer) -> i32 {
+        // GL reuses the names of deleted textures, so the images are tied to the framebuffer
+        // rather than to its texture.
+        self.images.retain(|_, image| image.framebuffer.strong_count() > 0);
+        let owner = Arc::downgrade(&framebuffer.alive);
+        if let Some((&handle, _)) = self.images.iter().find(|(_, image)| image.framebuffer.ptr_eq(&owner)) {
+            return handle;
+        }
+
+        self.next_image += 1;
+        self.images.insert(self.next_image, GlImage {
+            texture: framebuffer.texture,
+            flip_y: true,
+            tex_type: TEXTURE_RGBA_PREMULTIPLIED,
+            framebuffer: owner,
+        });
+        self.next_image
+    }
+
     fn add_paths(&mut self, path: &Path) -> u32 {
         let mut vertex_offset = self.verts.len() as u32;
         let mut r = BufferRef {
@@ -125,10 +369,19 @@ impl GlCanvasRenderer {
             tex_coord: [u, v],
         })
     }
+
+    fn clip_at_depth(&mut self, depth: usize) -> Option<usize> {
+        self.clip_stack.truncate(depth);
+        self.clip_stack.last().cloned()
+    }
 }
 
 impl CanvasRenderer for GlCanvasRenderer {
-    fn stroke(&mut self, paint: &Paint, scissor: &Scissor, fringe: Scalar, line_width: Scalar, paths: Paths) {
+    fn stroke(&mut self, paint: &Paint, composite: &CompositeState, scissor: &Scissor, clip_depth: usize, fringe: Scalar, line_width: Scalar, paths: Paths) {
+        if paint.image != 0 && find_image(&self.images, paint.image).is_none() {
+            return;
+        }
+
         let mut maxverts: u32 = 0;
         let mut npaths: u32 = 0;
         for path in paths.iter() {
@@ -145,11 +398,14 @@ impl CanvasRenderer for GlCanvasRenderer {
             self.add_paths(&path);
         }
 
+        let image = find_image(&self.images, paint.image);
+        let ramp_row = add_gradient_ramp(&mut self.ramps, paint, self.color_space, self.gradient_interpolation);
         let uniform_index = self.uniform_buffer.alloc(1);
         {
             let uniforms = self.uniform_buffer.get_mut(uniform_index);
             *uniforms = unsafe { std::mem::zeroed() };
-            convert_paint(uniforms, paint, scissor, line_width, fringe, -1.0);
+            convert_paint(uniforms, paint, image, ramp_row, self.color_space, scissor, line_width, fringe, -1.0);
+            uniforms.blend_mode = composite.blend_mode as u32 as f32;
         }
 
         let call = DrawCall {
@@ -159,20 +415,22 @@ impl CanvasRenderer for GlCanvasRenderer {
             triangle_offset: 0,
             triangle_count: 0,
             uniform_offset: self.uniform_buffer.offset(uniform_index) as u32,
-            color: convert_color(paint.inner_color),
-            image: 0,
-            blend_func: BlendFunc {
-                src_rgb: gl::ONE,
-                dst_rgb: gl::ONE_MINUS_SRC_ALPHA,
-                src_alpha: gl::ONE,
-                dst_alpha: gl::ONE_MINUS_SRC_ALPHA,
-            },
+            image: image.map(|image| image.texture).unwrap_or(0),
+            blend_func: convert_blend_func(composite),
+            blend_mode: composite.blend_mode,
+            clip: self.clip_at_depth(clip_depth),
+            indices: None,
         };
 
         self.draw_calls.push(call);
     }
 
-    fn fill(&mut self, paint: &Paint, scissor: &Scissor, fringe: Scalar, bounds: [Scalar; 4], paths: Paths) {
+    fn fill(&mut self, paint: &Paint, composite: &CompositeState, scissor: &Scissor, clip_depth: usize, fringe: Scalar, bounds: Rect, paths: Paths) {
+        // The image of a dropped framebuffer.
+        if paint.image != 0 && find_image(&self.images, paint.image).is_none() {
+            return;
+        }
+
         let ty = DrawCallType::Fill;
         let triangle_count = 4;
         let mut maxverts: u32 = 0;
@@ -195,11 +453,13 @@ impl CanvasRenderer for GlCanvasRenderer {
         }
 
         let triangle_offset = vert_offset;
-        self.add_verts(bounds[2], bounds[3], 0.5, 1.0);
-        self.add_verts(bounds[2], bounds[1], 0.5, 1.0);
-        self.add_verts(bounds[0], bounds[3], 0.5, 1.0);
-        self.add_verts(bounds[0], bounds[1], 0.5, 1.0);
+        self.add_verts(bounds.max_x(), bounds.max_y(), 0.5, 1.0);
+        self.add_verts(bounds.max_x(), bounds.min_y(), 0.5, 1.0);
+        self.add_verts(bounds.min_x(), bounds.max_y(), 0.5, 1.0);
+        self.add_verts(bounds.min_x(), bounds.min_y(), 0.5, 1.0);
 
+        let image = find_image(&self.images, paint.image);
+        let ramp_row = add_gradient_ramp(&mut self.ramps, paint, self.color_space, self.gradient_interpolation);
         let uniform_index = self.uniform_buffer.alloc(2);
         {
             // Simple shader for stencil
@@ -212,7 +472,8 @@ impl CanvasRenderer for GlCanvasRenderer {
             // Fill shader
             let uniforms = self.uniform_buffer.get_mut(uniform_index + 1);
             *uniforms = unsafe { std::mem::zeroed() };
-            convert_paint(uniforms, paint, scissor, fringe, fringe, -1.0);
+            convert_paint(uniforms, paint, image, ramp_row, self.color_space, scissor, fringe, fringe, -1.0);
+            uniforms.blend_mode = composite.blend_mode as u32 as f32;
         }
 
         let call = DrawCall {
@@ -222,22 +483,178 @@ impl CanvasRenderer for GlCanvasRenderer {
             triangle_offset,
             triangle_count,
             uniform_offset: self.uniform_buffer.offset(uniform_index) as u32,
-            color: convert_color(paint.inner_color),
-            image: 0,
-            blend_func: BlendFunc {
-                src_rgb: gl::ONE,
-                dst_rgb: gl::ONE_MINUS_SRC_ALPHA,
-                src_alpha: gl::ONE,
-                dst_alpha: gl::ONE_MINUS_SRC_ALPHA,
-            },
+            image: image.map(|image| image.texture).unwrap_or(0),
+            blend_func: convert_blend_func(composite),
+            blend_mode: composite.blend_mode,
+            clip: self.clip_at_depth(clip_depth),
+            indices: None,
         };
         self.draw_calls.push(call);
     }
+
+    fn clip(&mut self, depth: usize, paths: Paths) {
+        let parent = self.clip_at_depth(depth);
+        let mut clip = ClipPath {
+            parent,
+            verts: Vec::new(),
+            fill_counts: Vec::new(),
+            vertex_offset: 0,
+        };
+
+        for path in paths.iter() {
+            if let Some(fill) = path.fill() {
+                clip.verts.extend(fill.iter().map(|vert| ShaderVertex {
+                    pos: [vert.x, vert.y],
+                    tex_coord: [vert.u, vert.v],
+                }));
+                clip.fill_counts.push(fill.len() as u32);
+            }
+        }
+
+        self.clip_stack.push(self.clip_paths.len());
+        self.clip_paths.push(clip);
+    }
+
+    fn frame_stats_mut(&mut self) -> Option<&mut FrameStats> {
+        Some(&mut self.stats)
+    }
+
+    fn push_layer(&mut self, opacity: Scalar, composite: &CompositeState, clip_depth: usize) {
+        let clip = self.clip_at_depth(clip_depth);
+        self.layer_stack.push(LayerState {
+            opacity,
+            composite: *composite,
+            clip,
+        });
+
+        self.draw_calls.push(DrawCall {
+            ty: DrawCallType::PushLayer,
+            path_offset: 0,
+            path_count: 0,
+            triangle_offset: 0,
+            triangle_count: 0,
+            uniform_offset: 0,
+            image: 0,
+            blend_func: convert_blend_func(composite),
+            blend_mode: BlendMode::Normal,
+            clip: None,
+            indices: None,
+        });
+    }
+
+    fn pop_layer(&mut self) {
+        let layer = match self.layer_stack.pop() {
+            Some(layer) => layer,
+            None => return,
+        };
+
+        // The layer covers the whole viewport, flipped since textures start at the bottom.
+        let view_width = self.width / self.pixels_per_point;
+        let view_height = self.height / self.pixels_per_point;
+        let triangle_offset = self.verts.len() as u32;
+        self.add_verts(view_width, view_height, 1.0, 0.0);
+        self.add_verts(view_width, 0.0, 1.0, 1.0);
+        self.add_verts(0.0, view_height, 0.0, 0.0);
+        self.add_verts(0.0, view_height, 0.0, 0.0);
+        self.add_verts(view_width, 0.0, 1.0, 1.0);
+        self.add_verts(0.0, 0.0, 0.0, 1.0);
+
+        let uniform_index = self.uniform_buffer.alloc(1);
+        {
+            let uniforms = self.uniform_buffer.get_mut(uniform_index);
+            *uniforms = unsafe { std::mem::zeroed() };
+            uniforms.inner_col = [layer.opacity; 4];
+            uniforms.scissor_ext = [1.0, 1.0];
+            uniforms.scissor_scale = [1.0, 1.0];
+            uniforms.stroke_thr = -1.0;
+            uniforms.ty = SHADER_IMAGE;
+            uniforms.blend_mode = layer.composite.blend_mode as u32 as f32;
+        }
+
+        self.draw_calls.push(DrawCall {
+            ty: DrawCallType::PopLayer,
+            path_offset: 0,
+            path_count: 0,
+            triangle_offset,
+            triangle_count: 6,
+            uniform_offset: self.uniform_buffer.offset(uniform_index) as u32,
+            image: 0,
+            blend_func: convert_blend_func(&layer.composite),
+            blend_mode: layer.composite.blend_mode,
+            clip: layer.clip,
+            indices: None,
+        });
+    }
 }
 
 impl GlCanvasRenderer {
-    pub fn flush(&mut self) {
+    /// Renders the pending draw calls, returning the statistics since the previous `flush`.
+    pub fn flush(&mut self) -> FrameStats {
+        self.flush_draw_calls();
+        std::mem::take(&mut self.stats)
+    }
+
+    fn flush_draw_calls(&mut self) {
+        // Layers can't outlive a frame, so composite the ones left open.
+        while !self.layer_stack.is_empty() {
+            self.pop_layer();
+        }
+
+        for draw_call in self.draw_calls.iter() {
+            match draw_call.ty {
+                DrawCallType::Fill => self.stats.fill_calls += 1,
+                DrawCallType::Stroke => self.stats.stroke_calls += 1,
+                DrawCallType::PushLayer => self.stats.layer_calls += 1,
+                DrawCallType::PopLayer => {}
+            }
+            if draw_call.ty == DrawCallType::Fill {
+                // Counting the winding, then covering the fill resets it.
+                self.stats.stencil_passes += 2;
+            }
+        }
+        self.stats.draws_saved += self.batch_draw_calls();
+
         if !self.draw_calls.is_empty() {
+            let clipped = self.draw_calls.iter().any(|draw_call| draw_call.clip.is_some());
+            let mut clip_quad_offset = 0;
+            let mut clip_uniform_offset = 0;
+            if clipped {
+                for clip in self.clip_paths.iter_mut() {
+                    clip.vertex_offset = self.verts.len() as u32;
+                    self.verts.extend_from_slice(&clip.verts);
+                }
+
+                // Covers the whole viewport to update the clip bit outside of the clip paths.
+                let view_width = self.width / self.pixels_per_point;
+                let view_height = self.height / self.pixels_per_point;
+                clip_quad_offset = self.verts.len() as u32;
+                self.add_verts(view_width, view_height, 0.5, 1.0);
+                self.add_verts(view_width, 0.0, 0.5, 1.0);
+                self.add_verts(0.0, view_height, 0.5, 1.0);
+                self.add_verts(0.0, 0.0, 0.5, 1.0);
+
+                let uniform_index = self.uniform_buffer.alloc(1);
+                let uniforms = self.uniform_buffer.get_mut(uniform_index);
+                *uniforms = unsafe { std::mem::zeroed() };
+                uniforms.stroke_thr = -1.0;
+                uniforms.ty = SHADER_SIMPLE;
+                clip_uniform_offset = self.uniform_buffer.offset(uniform_index) as u32;
+            }
+
+            let debug_overlay = match self.debug_mode {
+                DebugMode::None => None,
+                _ => Some(self.prepare_debug_overlay()),
+            };
+
+            let saved_state = if self.restore_state {
+                Some(unsafe { GlState::save(self.backend) })
+            } else {
+                None
+            };
+
+            self.stats.vertices += self.verts.len();
+            self.stats.uniform_bytes += self.uniform_buffer.nuniforms * self.uniform_buffer.uniform_size;
+
             unsafe {
                 gl::UseProgram(self.shader.prog.id);
 
@@ -253,17 +670,20 @@ impl GlCanvasRenderer {
                 gl::StencilFunc(gl::ALWAYS, 0, 0xffffffff);
                 gl::ActiveTexture(gl::TEXTURE0);
                 gl::BindTexture(gl::TEXTURE_2D, 0);
-//                gl::PolygonMode(gl::FRONT_AND_BACK, gl::LINE);
 
-                gl::BindBuffer(gl::UNIFORM_BUFFER, self.ubo);
-                gl::BufferData(
-                    gl::UNIFORM_BUFFER,
-                    (self.uniform_buffer.nuniforms * self.uniform_buffer.uniform_size) as GLsizeiptr,
-                    self.uniform_buffer.buf.as_ptr() as * const _,
-                    gl::STREAM_DRAW
-                );
+                if self.ubo != 0 {
+                    gl::BindBuffer(gl::UNIFORM_BUFFER, self.ubo);
+                    gl::BufferData(
+                        gl::UNIFORM_BUFFER,
+                        (self.uniform_buffer.nuniforms * self.uniform_buffer.uniform_size) as GLsizeiptr,
+                        self.uniform_buffer.buf.as_ptr() as * const _,
+                        gl::STREAM_DRAW
+                    );
+                }
 
-                gl::BindVertexArray(self.vao);
+                if self.vao != 0 {
+                    gl::BindVertexArray(self.vao);
+                }
 
                 gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
                 gl::BufferData(
@@ -271,12 +691,20 @@ impl GlCanvasRenderer {
                     (size_of::<ShaderVertex>() * self.verts.len()) as GLsizeiptr,
                     self.verts.as_ptr() as *const _, gl::STREAM_DRAW
                 );
+                if self.ebo != 0 {
+                    gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.ebo);
+                    gl::BufferData(
+                        gl::ELEMENT_ARRAY_BUFFER,
+                        (size_of::<u32>() * self.indices.len()) as GLsizeiptr,
+                        self.indices.as_ptr() as *const _, gl::STREAM_DRAW
+                    );
+                }
                 gl::EnableVertexAttribArray(0);
                 gl::EnableVertexAttribArray(1);
                 gl::VertexAttribPointer(
                     0, 2, gl::FLOAT, gl::FALSE,
                     size_of::<ShaderVertex>() as GLint,
-                    0 as *const _,
+                    null(),
                 );
                 gl::VertexAttribPointer(
                     1, 2, gl::FLOAT, gl::FALSE,
@@ -284,174 +712,1295 @@ impl GlCanvasRenderer {
                     (2 * size_of::<f32>()) as *const _,
                 );
 
+                if self.color_space == ColorSpace::LinearSrgb && self.backend.uses_framebuffer_srgb() {
+                    gl::Enable(gl::FRAMEBUFFER_SRGB);
+                }
+
                 gl::Uniform1i(self.shader.loc_tex, 0);
+                gl::Uniform1i(self.shader.loc_backdrop, BACKDROP_TEXTURE_UNIT as GLint);
+                gl::Uniform1i(self.shader.loc_ramp, RAMP_TEXTURE_UNIT as GLint);
                 let view_size = [self.width / self.pixels_per_point, self.height / self.pixels_per_point];
                 gl::Uniform2fv(self.shader.loc_view_size, 1, view_size.as_ptr());
+                let frame_size = [self.width, self.height];
+                gl::Uniform2fv(self.shader.loc_frame_size, 1, frame_size.as_ptr());
+                let ramp_size = [RAMP_WIDTH as f32, (self.ramps.len() / (RAMP_WIDTH * 4)).max(1) as f32];
+                gl::Uniform2fv(self.shader.loc_ramp_size, 1, ramp_size.as_ptr());
+
+                if self.draw_calls.iter().any(|draw_call| draw_call.blend_mode != BlendMode::Normal) {
+                    self.prepare_backdrop();
+                }
 
+                if !self.ramps.is_empty() {
+                    self.upload_ramps();
+                }
+
+                self.prepare_layers();
+                let mut target_fbo = 0;
+                gl::GetIntegerv(gl::FRAMEBUFFER_BINDING, &mut target_fbo);
+
+                let frag_uniforms = FragUniforms {
+                    ubo: self.ubo,
+                    loc: self.shader.loc_frag,
+                    buf: &self.uniform_buffer.buf,
+                };
+
+                let mut applied_clip = None;
+                let mut parent_clips = Vec::new();
                 for draw_call in self.draw_calls.iter() {
-                    draw_call.draw(&self.paths, self.ubo, self.uniform_buffer.uniform_size as _);
+                    match draw_call.ty {
+                        DrawCallType::PushLayer => {
+                            // The stencil buffer belongs to the framebuffer, so the layer starts unclipped.
+                            let layer = &self.layers[parent_clips.len()];
+                            gl::BindFramebuffer(gl::FRAMEBUFFER, layer.fbo);
+                            gl::ClearColor(0.0, 0.0, 0.0, 0.0);
+                            gl::StencilMask(0xff);
+                            gl::ClearStencil(0);
+                            gl::Clear(gl::COLOR_BUFFER_BIT | gl::STENCIL_BUFFER_BIT);
+                            parent_clips.push(applied_clip);
+                            applied_clip = None;
+                            continue;
+                        }
+                        DrawCallType::PopLayer => {
+                            applied_clip = parent_clips.pop().unwrap_or(None);
+                            let parent_fbo = match parent_clips.len() {
+                                0 => target_fbo as GLuint,
+                                depth => self.layers[depth - 1].fbo,
+                            };
+                            gl::BindFramebuffer(gl::FRAMEBUFFER, parent_fbo);
+                            // Bound here because the layer isn't known until the layers are allocated.
+                            gl::BindTexture(gl::TEXTURE_2D, self.layers[parent_clips.len()].texture);
+                        }
+                        _ => {}
+                    }
+
+                    if draw_call.clip != applied_clip {
+                        frag_uniforms.bind(clip_uniform_offset);
+                        let intersections = apply_clip(&self.clip_paths, applied_clip, draw_call.clip, clip_quad_offset);
+                        self.stats.stencil_passes += 3 * intersections;
+                        applied_clip = draw_call.clip;
+                    }
+                    if draw_call.blend_mode != BlendMode::Normal {
+                        // Advanced blend modes read the destination in the shader, so snapshot the
+                        // part of it the call covers first.
+                        let bounds = draw_call.pixel_bounds(&self.paths, &self.verts, self.pixels_per_point, self.backdrop_size);
+                        if let Some([x, y, width, height]) = bounds {
+                            gl::ActiveTexture(gl::TEXTURE0 + BACKDROP_TEXTURE_UNIT);
+                            gl::CopyTexSubImage2D(gl::TEXTURE_2D, 0, x, y, x, y, width, height);
+                            gl::ActiveTexture(gl::TEXTURE0);
+                        }
+                    }
+                    draw_call.draw(&self.paths, &frag_uniforms, self.uniform_buffer.uniform_size as _);
+                }
+
+                if applied_clip.is_some() {
+                    clear_clip_bit();
+                }
+
+                if let Some(debug_overlay) = debug_overlay {
+                    self.draw_debug_overlay(&debug_overlay, &frag_uniforms);
                 }
 
                 gl::DisableVertexAttribArray(0);
                 gl::DisableVertexAttribArray(1);
 
-                gl::BindVertexArray(0);
+                if self.vao != 0 {
+                    gl::BindVertexArray(0);
+                } else {
+                    gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
+                }
 
                 gl::Disable(gl::CULL_FACE);
+                if self.backend.uses_framebuffer_srgb() {
+                    gl::Disable(gl::FRAMEBUFFER_SRGB);
+                }
                 gl::BindBuffer(gl::ARRAY_BUFFER, 0);
                 gl::UseProgram(0);
 
+                gl::ActiveTexture(gl::TEXTURE0 + BACKDROP_TEXTURE_UNIT);
+                gl::BindTexture(gl::TEXTURE_2D, 0);
+                gl::ActiveTexture(gl::TEXTURE0 + RAMP_TEXTURE_UNIT);
+                gl::BindTexture(gl::TEXTURE_2D, 0);
+                gl::ActiveTexture(gl::TEXTURE0);
                 gl::BindTexture(gl::TEXTURE_2D, 0);
+
+                match saved_state {
+                    Some(state) => state.restore(self.backend),
+                    None => reset_state(self.backend),
+                }
             }
         }
 
         self.paths.clear();
         self.verts.clear();
+        self.indices.clear();
         self.draw_calls.clear();
         self.uniform_buffer.clear();
+        self.ramps.clear();
+
+        // Only the clip paths still on the stack are needed by later frames.
+        let mut clip_paths = Vec::with_capacity(self.clip_stack.len());
+        for (index, clip_index) in self.clip_stack.iter_mut().enumerate() {
+            let mut clip = std::mem::take(&mut self.clip_paths[*clip_index]);
+            clip.parent = index.checked_sub(1);
+            clip_paths.push(clip);
+            *clip_index = index;
+        }
+        self.clip_paths = clip_paths;
     }
 }
 
-fn convert_color(color: [f32; 4]) -> [f32; 4] {
-    let a = color[3];
-    [
-        color[0] * a,
-        color[1] * a,
-        color[2] * a,
-        a
-    ]
+struct LayerState {
+    opacity: Scalar,
+    composite: CompositeState,
+    clip: Option<usize>,
 }
 
-fn convert_transform(t: Transform) -> [f32; 12] {
-    [
-        t.e[0], t.e[1], 0.0, 0.0,
-        t.e[2], t.e[3], 0.0, 0.0,
-        t.e[4], t.e[5], 1.0, 0.0,
-    ]
+struct GlImage {
+    texture: GLuint,
+    flip_y: bool,
+    tex_type: f32,
+    framebuffer: Weak<()>,
 }
 
-fn convert_paint(uniforms: &mut Uniforms, paint: &Paint, scissor: &Scissor, width: Scalar, fringe: Scalar, stroke_thr: Scalar) {
-    uniforms.inner_col = convert_color(paint.inner_color);
-    uniforms.outer_col = convert_color(paint.outer_color);
+/// Framebuffer object with a color texture and a stencil buffer for the fill passes.
+pub struct Framebuffer {
+    fbo: GLuint,
+    texture: GLuint,
+    stencil: GLuint,
+    width: i32,
+    height: i32,
+    format: GLenum,
+    /// Only referenced weakly by the images of the framebuffer, to tell when it's dropped.
+    alive: Arc<()>,
+}
 
-    if scissor.extent[0] < -0.5 || scissor.extent[1] < -0.5 {
-        uniforms.scissor_mat = [0.0; 12];
-        uniforms.scissor_ext = [1.0, 1.0];
-        uniforms.scissor_scale = [1.0, 1.0];
-    } else {
-        unimplemented!();
-    }
+impl Framebuffer {
+    unsafe fn new(width: i32, height: i32, format: GLenum) -> Framebuffer {
+        let mut fbo = 0;
+        let mut texture = 0;
+        let mut stencil = 0;
+        let mut prev_fbo = 0;
+        gl::GetIntegerv(gl::FRAMEBUFFER_BINDING, &mut prev_fbo);
+
+        gl::GenTextures(1, &mut texture);
+        gl::BindTexture(gl::TEXTURE_2D, texture);
+        gl::TexImage2D(gl::TEXTURE_2D, 0, format as GLint, width, height, 0, gl::RGBA, gl::UNSIGNED_BYTE, null());
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as GLint);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as GLint);
+        gl::BindTexture(gl::TEXTURE_2D, 0);
 
-    uniforms.extent = paint.extent;
+        gl::GenRenderbuffers(1, &mut stencil);
+        gl::BindRenderbuffer(gl::RENDERBUFFER, stencil);
+        gl::RenderbufferStorage(gl::RENDERBUFFER, gl::STENCIL_INDEX8, width, height);
+        gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
 
-    uniforms.stroke_mult = (width * 0.5 + fringe * 0.5) / fringe;
-    uniforms.stroke_thr = stroke_thr;
+        gl::GenFramebuffers(1, &mut fbo);
+        gl::BindFramebuffer(gl::FRAMEBUFFER, fbo);
+        gl::FramebufferTexture2D(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::TEXTURE_2D, texture, 0);
+        gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, gl::STENCIL_ATTACHMENT, gl::RENDERBUFFER, stencil);
+        gl::ClearColor(0.0, 0.0, 0.0, 0.0);
+        gl::StencilMask(0xff);
+        gl::ClearStencil(0);
+        gl::Clear(gl::COLOR_BUFFER_BIT | gl::STENCIL_BUFFER_BIT);
+        gl::BindFramebuffer(gl::FRAMEBUFFER, prev_fbo as GLuint);
+
+        Framebuffer {
+            fbo,
+            texture,
+            stencil,
+            width,
+            height,
+            format,
+            alive: Arc::new(()),
+        }
+    }
 
-    // TODO: Texture
+    pub fn width(&self) -> i32 {
+        self.width
+    }
 
-    uniforms.ty = SHADER_FILL_GRADIENT;
-    uniforms.radius = paint.radius;
-    uniforms.feather = paint.feather;
-    let inv_transform = paint.transform.inverse();
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+}
 
-    uniforms.paint_mat = convert_transform(inv_transform);
+impl Drop for Framebuffer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.fbo);
+            gl::DeleteRenderbuffers(1, &self.stencil);
+            gl::DeleteTextures(1, &self.texture);
+        }
+    }
 }
 
-struct DrawCall {
-    ty: DrawCallType,
-    path_offset: u32,
-    path_count: u32,
-    triangle_offset: u32,
-    triangle_count: u32,
-    uniform_offset: u32,
-    color: [f32; 4],
-    image: u32,
-    blend_func: BlendFunc,
+/// Resets the state changed by `flush` that the end of `flush` doesn't already unbind or
+/// disable to the GL defaults.
+unsafe fn reset_state(backend: GlBackend) {
+    gl::Disable(gl::BLEND);
+    gl::Disable(gl::STENCIL_TEST);
+    gl::BlendFuncSeparate(gl::ONE, gl::ZERO, gl::ONE, gl::ZERO);
+    gl::CullFace(gl::BACK);
+    gl::FrontFace(gl::CCW);
+    gl::ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE);
+    gl::StencilMask(0xffffffff);
+    gl::StencilFunc(gl::ALWAYS, 0, 0xffffffff);
+    gl::StencilOp(gl::KEEP, gl::KEEP, gl::KEEP);
+    gl::ClearColor(0.0, 0.0, 0.0, 0.0);
+    gl::ClearStencil(0);
+    gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+    if backend.uses_uniform_buffer() {
+        gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+    }
 }
 
-#[repr(u32)]
-enum DrawCallType {
-    Fill,
-    ConvexFill,
-    Stroke,
-    Triangles,
+/// Snapshot of the GL state that `flush` changes.
+struct GlState {
+    program: GLint,
+    array_buffer: GLint,
+    element_array_buffer: GLint,
+    uniform_buffer: GLint,
+    vertex_array: GLint,
+    vertex_attribs_enabled: [GLint; 2],
+    active_texture: GLint,
+    textures: [GLint; 3],
+    blend: bool,
+    cull_face: bool,
+    depth_test: bool,
+    scissor_test: bool,
+    stencil_test: bool,
+    framebuffer_srgb: bool,
+    cull_face_mode: GLint,
+    front_face: GLint,
+    blend_func: [GLint; 4],
+    color_mask: [GLboolean; 4],
+    stencil: [StencilState; 2],
+    clear_color: [GLfloat; 4],
+    clear_stencil: GLint,
+    unpack_alignment: GLint,
 }
 
-impl DrawCall {
-    unsafe fn draw(&self, paths: &[BufferRef], ubo: GLuint, uniform_size: u32) {
-        let blend = &self.blend_func;
-        gl::BlendFuncSeparate(blend.src_rgb, blend.dst_rgb, blend.src_alpha, blend.dst_alpha);
-        match self.ty {
-            DrawCallType::Stroke => self.stroke(paths, ubo),
-            DrawCallType::Fill => self.fill(paths, ubo, uniform_size),
-            _ => {},
-        }
+#[derive(Default)]
+struct StencilState {
+    func: GLint,
+    reference: GLint,
+    value_mask: GLint,
+    write_mask: GLint,
+    fail: GLint,
+    depth_fail: GLint,
+    depth_pass: GLint,
+}
+
+impl StencilState {
+    unsafe fn save(face: GLenum) -> StencilState {
+        let names = if face == gl::FRONT {
+            [gl::STENCIL_FUNC, gl::STENCIL_REF, gl::STENCIL_VALUE_MASK, gl::STENCIL_WRITEMASK, gl::STENCIL_FAIL, gl::STENCIL_PASS_DEPTH_FAIL, gl::STENCIL_PASS_DEPTH_PASS]
+        } else {
+            [gl::STENCIL_BACK_FUNC, gl::STENCIL_BACK_REF, gl::STENCIL_BACK_VALUE_MASK, gl::STENCIL_BACK_WRITEMASK, gl::STENCIL_BACK_FAIL, gl::STENCIL_BACK_PASS_DEPTH_FAIL, gl::STENCIL_BACK_PASS_DEPTH_PASS]
+        };
+        let mut state = StencilState::default();
+        gl::GetIntegerv(names[0], &mut state.func);
+        gl::GetIntegerv(names[1], &mut state.reference);
+        gl::GetIntegerv(names[2], &mut state.value_mask);
+        gl::GetIntegerv(names[3], &mut state.write_mask);
+        gl::GetIntegerv(names[4], &mut state.fail);
+        gl::GetIntegerv(names[5], &mut state.depth_fail);
+        gl::GetIntegerv(names[6], &mut state.depth_pass);
+        state
     }
 
-    unsafe fn stroke(&self, paths: &[BufferRef], ubo: GLuint) {
-        let paths = &paths[self.path_offset as usize..(self.path_offset + self.path_count) as usize];
+    unsafe fn restore(&self, face: GLenum) {
+        gl::StencilFuncSeparate(face, self.func as GLenum, self.reference, self.value_mask as GLuint);
+        gl::StencilMaskSeparate(face, self.write_mask as GLuint);
+        gl::StencilOpSeparate(face, self.fail as GLenum, self.depth_fail as GLenum, self.depth_pass as GLenum);
+    }
+}
 
-        gl::BindBufferRange(gl::UNIFORM_BUFFER, FRAG_BINDING, ubo, self.uniform_offset as _, size_of::<Uniforms>() as _);
+impl GlState {
+    unsafe fn save(backend: GlBackend) -> GlState {
+        let integer = |name: GLenum| {
+            let mut value = 0;
+            gl::GetIntegerv(name, &mut value);
+            value
+        };
 
-        // TODO: Texture
-        gl::BindTexture(gl::TEXTURE_2D, 0);
+        let active_texture = integer(gl::ACTIVE_TEXTURE);
+        let mut textures = [0; 3];
+        for (unit, texture) in textures.iter_mut().enumerate() {
+            gl::ActiveTexture(gl::TEXTURE0 + unit as GLenum);
+            *texture = integer(gl::TEXTURE_BINDING_2D);
+        }
+        gl::ActiveTexture(active_texture as GLenum);
 
-        for path in paths.iter() {
-            gl::DrawArrays(gl::TRIANGLE_STRIP, path.stroke_offset as _, path.stroke_count as _);
+        // With a vertex array the attributes are part of its state, which isn't modified.
+        let mut vertex_attribs_enabled = [0; 2];
+        if !backend.uses_vertex_array() {
+            for (index, enabled) in vertex_attribs_enabled.iter_mut().enumerate() {
+                gl::GetVertexAttribiv(index as GLuint, gl::VERTEX_ATTRIB_ARRAY_ENABLED, enabled);
+            }
         }
-    }
 
-    unsafe fn fill(&self, paths: &[BufferRef], ubo: GLuint, uniform_size: u32) {
-        let paths = &paths[self.path_offset as usize..(self.path_offset + self.path_count) as usize];
+        let mut color_mask = [gl::TRUE; 4];
+        gl::GetBooleanv(gl::COLOR_WRITEMASK, color_mask.as_mut_ptr());
+        let mut clear_color = [0.0; 4];
+        gl::GetFloatv(gl::COLOR_CLEAR_VALUE, clear_color.as_mut_ptr());
+
+        GlState {
+            program: integer(gl::CURRENT_PROGRAM),
+            array_buffer: integer(gl::ARRAY_BUFFER_BINDING),
+            element_array_buffer: if backend.uses_vertex_array() { 0 } else { integer(gl::ELEMENT_ARRAY_BUFFER_BINDING) },
+            uniform_buffer: if backend.uses_uniform_buffer() { integer(gl::UNIFORM_BUFFER_BINDING) } else { 0 },
+            vertex_array: if backend.uses_vertex_array() { integer(gl::VERTEX_ARRAY_BINDING) } else { 0 },
+            vertex_attribs_enabled,
+            active_texture,
+            textures,
+            blend: gl::IsEnabled(gl::BLEND) == gl::TRUE,
+            cull_face: gl::IsEnabled(gl::CULL_FACE) == gl::TRUE,
+            depth_test: gl::IsEnabled(gl::DEPTH_TEST) == gl::TRUE,
+            scissor_test: gl::IsEnabled(gl::SCISSOR_TEST) == gl::TRUE,
+            stencil_test: gl::IsEnabled(gl::STENCIL_TEST) == gl::TRUE,
+            framebuffer_srgb: backend.uses_framebuffer_srgb() && gl::IsEnabled(gl::FRAMEBUFFER_SRGB) == gl::TRUE,
+            cull_face_mode: integer(gl::CULL_FACE_MODE),
+            front_face: integer(gl::FRONT_FACE),
+            blend_func: [integer(gl::BLEND_SRC_RGB), integer(gl::BLEND_DST_RGB), integer(gl::BLEND_SRC_ALPHA), integer(gl::BLEND_DST_ALPHA)],
+            color_mask,
+            stencil: [StencilState::save(gl::FRONT), StencilState::save(gl::BACK)],
+            clear_color,
+            clear_stencil: integer(gl::STENCIL_CLEAR_VALUE),
+            unpack_alignment: integer(gl::UNPACK_ALIGNMENT),
+        }
+    }
 
-        // Draw shapes
-        gl::Enable(gl::STENCIL_TEST);
-        gl::StencilMask(0xff);
-        gl::StencilFunc(gl::ALWAYS, 0, 0xff);
-        gl::ColorMask(gl::FALSE, gl::FALSE, gl::FALSE, gl::FALSE);
+    unsafe fn restore(&self, backend: GlBackend) {
+        let enable = |cap: GLenum, enabled: bool| {
+            if enabled {
+                gl::Enable(cap);
+            } else {
+                gl::Disable(cap);
+            }
+        };
 
-        gl::BindBufferRange(gl::UNIFORM_BUFFER, FRAG_BINDING, ubo, self.uniform_offset as _, size_of::<Uniforms>() as _);
-        gl::StencilOpSeparate(gl::FRONT, gl::KEEP, gl::KEEP, gl::INCR_WRAP);
-        gl::StencilOpSeparate(gl::BACK, gl::KEEP, gl::KEEP, gl::DECR_WRAP);
-        gl::Disable(gl::CULL_FACE);
-        for path in paths.iter() {
-            gl::DrawArrays(gl::TRIANGLE_FAN, path.fill_offset as _, path.fill_count as _);
+        gl::UseProgram(self.program as GLuint);
+        if backend.uses_vertex_array() {
+            gl::BindVertexArray(self.vertex_array as GLuint);
+        } else {
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.element_array_buffer as GLuint);
+            for (index, &enabled) in self.vertex_attribs_enabled.iter().enumerate() {
+                if enabled != 0 {
+                    gl::EnableVertexAttribArray(index as GLuint);
+                }
+            }
         }
-        gl::Enable(gl::CULL_FACE);
+        gl::BindBuffer(gl::ARRAY_BUFFER, self.array_buffer as GLuint);
+        if backend.uses_uniform_buffer() {
+            gl::BindBuffer(gl::UNIFORM_BUFFER, self.uniform_buffer as GLuint);
+        }
+        for (unit, &texture) in self.textures.iter().enumerate() {
+            gl::ActiveTexture(gl::TEXTURE0 + unit as GLenum);
+            gl::BindTexture(gl::TEXTURE_2D, texture as GLuint);
+        }
+        gl::ActiveTexture(self.active_texture as GLenum);
+
+        enable(gl::BLEND, self.blend);
+        enable(gl::CULL_FACE, self.cull_face);
+        enable(gl::DEPTH_TEST, self.depth_test);
+        enable(gl::SCISSOR_TEST, self.scissor_test);
+        enable(gl::STENCIL_TEST, self.stencil_test);
+        if backend.uses_framebuffer_srgb() {
+            enable(gl::FRAMEBUFFER_SRGB, self.framebuffer_srgb);
+        }
+        gl::CullFace(self.cull_face_mode as GLenum);
+        gl::FrontFace(self.front_face as GLenum);
+        let blend = &self.blend_func;
+        gl::BlendFuncSeparate(blend[0] as GLenum, blend[1] as GLenum, blend[2] as GLenum, blend[3] as GLenum);
+        let mask = &self.color_mask;
+        gl::ColorMask(mask[0], mask[1], mask[2], mask[3]);
+        self.stencil[0].restore(gl::FRONT);
+        self.stencil[1].restore(gl::BACK);
+        let color = &self.clear_color;
+        gl::ClearColor(color[0], color[1], color[2], color[3]);
+        gl::ClearStencil(self.clear_stencil);
+        gl::PixelStorei(gl::UNPACK_ALIGNMENT, self.unpack_alignment);
+    }
+}
 
-        // Draw anti-aliased pixels
-        gl::ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE);
+/// Stencil bit marking pixels outside of the current clip region.
+const STENCIL_CLIP_BIT: GLuint = 0x80;
+/// Stencil bits used to count the winding of filled paths.
+const STENCIL_WINDING_MASK: GLuint = 0x7f;
+
+#[derive(Default)]
+struct ClipPath {
+    parent: Option<usize>,
+    verts: Vec<ShaderVertex>,
+    fill_counts: Vec<u32>,
+    vertex_offset: u32,
+}
 
-        gl::BindBufferRange(gl::UNIFORM_BUFFER, FRAG_BINDING, ubo, (self.uniform_offset + uniform_size) as _, size_of::<Uniforms>() as _);
+unsafe fn clear_clip_bit() {
+    gl::StencilMask(STENCIL_CLIP_BIT);
+    gl::ClearStencil(0);
+    gl::Clear(gl::STENCIL_BUFFER_BIT);
+}
 
-        // Anti-alias
-        {
-            gl::StencilFunc(gl::EQUAL, 0x0, 0xff);
-            gl::StencilOp(gl::KEEP, gl::KEEP, gl::KEEP);
-            // Draw fringes
-            for path in paths.iter() {
-                gl::DrawArrays(gl::TRIANGLE_STRIP, path.stroke_offset as _, path.stroke_count as _);
-            }
+/// Updates the clip bit in the stencil buffer from the `applied` clip path to `target`, and
+/// returns the number of clip paths intersected.
+unsafe fn apply_clip(clip_paths: &[ClipPath], applied: Option<usize>, target: Option<usize>, quad_offset: u32) -> usize {
+    let target = match target {
+        Some(target) => target,
+        None => {
+            clear_clip_bit();
+            return 0;
         }
+    };
 
-        // Draw fill
-        gl::StencilFunc(gl::NOTEQUAL, 0x0, 0xff);
-        gl::StencilOp(gl::ZERO, gl::ZERO, gl::ZERO);
-        gl::DrawArrays(gl::TRIANGLE_STRIP, self.triangle_offset as _, self.triangle_count as _);
+    // Nested clips only need to intersect the new path, otherwise rebuild from the root.
+    if applied.is_some() && clip_paths[target].parent == applied {
+        intersect_clip(&clip_paths[target], quad_offset);
+        return 1;
+    }
 
-        gl::Disable(gl::STENCIL_TEST);
+    if applied.is_some() {
+        clear_clip_bit();
+    }
+
+    let mut chain = Vec::new();
+    let mut index = Some(target);
+    while let Some(i) = index {
+        chain.push(i);
+        index = clip_paths[i].parent;
+    }
+    for &i in chain.iter().rev() {
+        intersect_clip(&clip_paths[i], quad_offset);
     }
+    chain.len()
 }
 
-struct BlendFunc {
-    src_rgb: GLenum,
-    dst_rgb: GLenum,
-    src_alpha: GLenum,
-    dst_alpha: GLenum,
+unsafe fn intersect_clip(clip: &ClipPath, quad_offset: u32) {
+    gl::Enable(gl::STENCIL_TEST);
+    gl::ColorMask(gl::FALSE, gl::FALSE, gl::FALSE, gl::FALSE);
+
+    // Count the winding of the clip path.
+    gl::StencilMask(STENCIL_WINDING_MASK);
+    gl::StencilFunc(gl::ALWAYS, 0, 0xff);
+    gl::StencilOpSeparate(gl::FRONT, gl::KEEP, gl::KEEP, gl::INCR_WRAP);
+    gl::StencilOpSeparate(gl::BACK, gl::KEEP, gl::KEEP, gl::DECR_WRAP);
+    gl::Disable(gl::CULL_FACE);
+    let mut offset = clip.vertex_offset;
+    for &count in clip.fill_counts.iter() {
+        gl::DrawArrays(gl::TRIANGLE_FAN, offset as _, count as _);
+        offset += count;
+    }
+    gl::Enable(gl::CULL_FACE);
+
+    // Mark everything outside of the clip path as clipped.
+    gl::StencilMask(STENCIL_CLIP_BIT);
+    gl::StencilFunc(gl::EQUAL, STENCIL_CLIP_BIT as _, STENCIL_WINDING_MASK);
+    gl::StencilOp(gl::KEEP, gl::KEEP, gl::REPLACE);
+    gl::DrawArrays(gl::TRIANGLE_STRIP, quad_offset as _, 4);
+
+    // Reset the winding.
+    gl::StencilMask(STENCIL_WINDING_MASK);
+    gl::StencilFunc(gl::NOTEQUAL, 0, STENCIL_WINDING_MASK);
+    gl::StencilOp(gl::ZERO, gl::ZERO, gl::ZERO);
+    gl::DrawArrays(gl::TRIANGLE_STRIP, quad_offset as _, 4);
+
+    gl::ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE);
+    gl::Disable(gl::STENCIL_TEST);
 }
 
-#[repr(C)]
-struct Uniforms {
+impl GlCanvasRenderer {
+    /// Makes sure there is a framebuffer of the viewport size for each nested layer.
+    unsafe fn prepare_layers(&mut self) {
+        let mut depth = 0;
+        let mut max_depth = 0;
+        for draw_call in self.draw_calls.iter() {
+            match draw_call.ty {
+                DrawCallType::PushLayer => {
+                    depth += 1;
+                    max_depth = max_depth.max(depth);
+                }
+                DrawCallType::PopLayer => depth -= 1,
+                _ => {}
+            }
+        }
+
+        let size = [self.width as i32, self.height as i32];
+        let format = self.framebuffer_format();
+        self.layers.retain(|layer| layer.width == size[0] && layer.height == size[1] && layer.format == format);
+        while self.layers.len() < max_depth {
+            self.layers.push(Framebuffer::new(size[0], size[1], format));
+        }
+    }
+
+    /// Uploads the gradient ramps of this frame and binds them to their unit.
+    unsafe fn upload_ramps(&mut self) {
+        if self.ramp_texture == 0 {
+            gl::GenTextures(1, &mut self.ramp_texture);
+        }
+
+        let rows = self.ramps.len() / (RAMP_WIDTH * 4);
+        gl::ActiveTexture(gl::TEXTURE0 + RAMP_TEXTURE_UNIT);
+        gl::BindTexture(gl::TEXTURE_2D, self.ramp_texture);
+        gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+        if self.backend.supports_float_textures() {
+            gl::TexImage2D(gl::TEXTURE_2D, 0, gl::RGBA16F as GLint, RAMP_WIDTH as GLint, rows as GLint, 0, gl::RGBA, gl::FLOAT, self.ramps.as_ptr() as *const _);
+        } else {
+            let ramps: Vec<u8> = self.ramps.iter().map(|&c| (clamp(c, 0.0, 1.0) * 255.0 + 0.5) as u8).collect();
+            gl::TexImage2D(gl::TEXTURE_2D, 0, self.backend.color_format() as GLint, RAMP_WIDTH as GLint, rows as GLint, 0, gl::RGBA, gl::UNSIGNED_BYTE, ramps.as_ptr() as *const _);
+        }
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as GLint);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as GLint);
+        gl::ActiveTexture(gl::TEXTURE0);
+    }
+
+    /// Allocates the uniforms of the debug overlay, and the vertices of the bounding boxes.
+    fn prepare_debug_overlay(&mut self) -> DebugOverlay {
+        let (color, tex_type) = match self.debug_mode {
+            DebugMode::Fringes => ([1.0, 0.0, 1.0, 1.0], 1.0),
+            DebugMode::StencilOverdraw => ([0.2, 0.0, 0.0, 0.2], 0.0),
+            DebugMode::BoundingBoxes => ([0.0, 1.0, 1.0, 1.0], 0.0),
+            _ => ([0.0, 1.0, 0.0, 1.0], 0.0),
+        };
+
+        let uniform_index = self.uniform_buffer.alloc(1);
+        let uniforms = self.uniform_buffer.get_mut(uniform_index);
+        *uniforms = unsafe { std::mem::zeroed() };
+        uniforms.inner_col = color;
+        uniforms.stroke_thr = -1.0;
+        uniforms.ty = SHADER_DEBUG;
+        uniforms.tex_type = tex_type;
+
+        let mut boxes = Vec::new();
+        if self.debug_mode == DebugMode::BoundingBoxes {
+            for draw_call in self.draw_calls.iter() {
+                let paths = &self.paths[draw_call.path_offset as usize..(draw_call.path_offset + draw_call.path_count) as usize];
+                let mut bounds = [Scalar::MAX, Scalar::MAX, Scalar::MIN, Scalar::MIN];
+                for path in paths.iter() {
+                    let fill = path.fill_offset as usize..(path.fill_offset + path.fill_count) as usize;
+                    let stroke = path.stroke_offset as usize..(path.stroke_offset + path.stroke_count) as usize;
+                    for vert in self.verts[fill].iter().chain(self.verts[stroke].iter()) {
+                        bounds = [
+                            bounds[0].min(vert.pos[0]),
+                            bounds[1].min(vert.pos[1]),
+                            bounds[2].max(vert.pos[0]),
+                            bounds[3].max(vert.pos[1]),
+                        ];
+                    }
+                }
+                if bounds[0] <= bounds[2] {
+                    boxes.push(bounds);
+                }
+            }
+        }
+
+        let box_offset = self.verts.len() as u32;
+        for bounds in boxes.iter() {
+            self.add_verts(bounds[0], bounds[1], 0.5, 1.0);
+            self.add_verts(bounds[2], bounds[1], 0.5, 1.0);
+            self.add_verts(bounds[2], bounds[3], 0.5, 1.0);
+            self.add_verts(bounds[0], bounds[3], 0.5, 1.0);
+        }
+
+        DebugOverlay {
+            uniform_offset: self.uniform_buffer.offset(uniform_index) as u32,
+            box_offset,
+            box_count: boxes.len() as u32,
+            clipped: self.draw_calls.iter().any(|draw_call| draw_call.clip.is_some()),
+        }
+    }
+
+    /// Draws the debug view over the frame, ignoring clipping.
+    unsafe fn draw_debug_overlay(&self, overlay: &DebugOverlay, uniforms: &FragUniforms) {
+        gl::Disable(gl::STENCIL_TEST);
+        gl::Disable(gl::CULL_FACE);
+        gl::BindTexture(gl::TEXTURE_2D, 0);
+        uniforms.bind(overlay.uniform_offset);
+
+        let draw_calls = self.draw_calls.iter().filter(|draw_call| draw_call.ty == DrawCallType::Fill || draw_call.ty == DrawCallType::Stroke);
+        let paths = draw_calls.clone().flat_map(|draw_call| {
+            self.paths[draw_call.path_offset as usize..(draw_call.path_offset + draw_call.path_count) as usize].iter()
+        });
+        match self.debug_mode {
+            DebugMode::Wireframe => {
+                gl::BlendFuncSeparate(gl::ONE, gl::ONE_MINUS_SRC_ALPHA, gl::ONE, gl::ONE_MINUS_SRC_ALPHA);
+                // GLES has no polygon mode, so the strips and fans are drawn as line strips there.
+                let polygon_mode = self.backend == GlBackend::Gl2 || self.backend == GlBackend::Gl3;
+                let (strip, fan) = if polygon_mode {
+                    gl::PolygonMode(gl::FRONT_AND_BACK, gl::LINE);
+                    (gl::TRIANGLE_STRIP, gl::TRIANGLE_FAN)
+                } else {
+                    (gl::LINE_STRIP, gl::LINE_STRIP)
+                };
+                for path in paths {
+                    gl::DrawArrays(fan, path.fill_offset as _, path.fill_count as _);
+                    gl::DrawArrays(strip, path.stroke_offset as _, path.stroke_count as _);
+                }
+                if polygon_mode {
+                    gl::PolygonMode(gl::FRONT_AND_BACK, gl::FILL);
+                }
+            }
+            DebugMode::Fringes => {
+                gl::BlendFuncSeparate(gl::ONE, gl::ONE_MINUS_SRC_ALPHA, gl::ONE, gl::ONE_MINUS_SRC_ALPHA);
+                for path in paths {
+                    gl::DrawArrays(gl::TRIANGLE_STRIP, path.stroke_offset as _, path.stroke_count as _);
+                }
+            }
+            DebugMode::StencilOverdraw => {
+                gl::BlendFuncSeparate(gl::ONE, gl::ONE, gl::ONE, gl::ONE);
+                for draw_call in draw_calls.filter(|draw_call| draw_call.ty == DrawCallType::Fill) {
+                    for path in self.paths[draw_call.path_offset as usize..(draw_call.path_offset + draw_call.path_count) as usize].iter() {
+                        gl::DrawArrays(gl::TRIANGLE_FAN, path.fill_offset as _, path.fill_count as _);
+                    }
+                }
+                for clip in self.clip_paths.iter().filter(|_| overlay.clipped) {
+                    let mut offset = clip.vertex_offset;
+                    for &count in clip.fill_counts.iter() {
+                        gl::DrawArrays(gl::TRIANGLE_FAN, offset as _, count as _);
+                        offset += count;
+                    }
+                }
+            }
+            DebugMode::BoundingBoxes => {
+                gl::BlendFuncSeparate(gl::ONE, gl::ONE_MINUS_SRC_ALPHA, gl::ONE, gl::ONE_MINUS_SRC_ALPHA);
+                for i in 0..overlay.box_count {
+                    gl::DrawArrays(gl::LINE_LOOP, (overlay.box_offset + i * 4) as _, 4);
+                }
+            }
+            DebugMode::None => {}
+        }
+
+        gl::Enable(gl::CULL_FACE);
+    }
+
+    /// Converts the triangle strips and fans of the paths into indexed triangle lists, and merges
+    /// consecutive strokes which can be drawn at once. Returns the number of GL draws saved.
+    fn batch_draw_calls(&mut self) -> usize {
+        if !self.backend.supports_uint_indices() {
+            return 0;
+        }
+
+        let mut draws_before = 0;
+        let mut draws_after = 0;
+        let mut batched: Vec<DrawCall> = Vec::with_capacity(self.draw_calls.len());
+        for mut draw_call in std::mem::take(&mut self.draw_calls) {
+            let paths = &self.paths[draw_call.path_offset as usize..(draw_call.path_offset + draw_call.path_count) as usize];
+            match draw_call.ty {
+                DrawCallType::Stroke => {
+                    let stroke_offset = self.indices.len() as u32;
+                    for path in paths.iter() {
+                        add_strip_indices(&mut self.indices, path.stroke_offset, path.stroke_count);
+                    }
+                    let stroke_count = self.indices.len() as u32 - stroke_offset;
+                    draw_call.indices = Some(DrawIndices { stroke_offset, stroke_count, ..Default::default() });
+                    draws_before += paths.len();
+
+                    // The indices and paths of the previous stroke end where these start.
+                    if let Some(previous) = batched.last_mut() {
+                        if previous.can_merge(&draw_call, &self.uniform_buffer) {
+                            debug_assert_eq!(previous.path_offset + previous.path_count, draw_call.path_offset);
+                            previous.indices.as_mut().unwrap().stroke_count += stroke_count;
+                            previous.path_count += draw_call.path_count;
+                            continue;
+                        }
+                    }
+                    draws_after += (stroke_count > 0) as usize;
+                }
+                DrawCallType::Fill => {
+                    let fill_offset = self.indices.len() as u32;
+                    for path in paths.iter() {
+                        add_fan_indices(&mut self.indices, path.fill_offset, path.fill_count);
+                    }
+                    let fill_count = self.indices.len() as u32 - fill_offset;
+                    let stroke_offset = self.indices.len() as u32;
+                    for path in paths.iter() {
+                        add_strip_indices(&mut self.indices, path.stroke_offset, path.stroke_count);
+                    }
+                    let stroke_count = self.indices.len() as u32 - stroke_offset;
+                    draw_call.indices = Some(DrawIndices { fill_offset, fill_count, stroke_offset, stroke_count });
+                    draws_before += 2 * paths.len() + 1;
+                    draws_after += (fill_count > 0) as usize + (stroke_count > 0) as usize + 1;
+                }
+                _ => {}
+            }
+            batched.push(draw_call);
+        }
+
+        self.draw_calls = batched;
+        draws_before - draws_after
+    }
+
+    /// Binds the backdrop texture to its unit, reallocating it when the viewport size changed.
+    unsafe fn prepare_backdrop(&mut self) {
+        let size = [self.width as i32, self.height as i32];
+
+        if self.backdrop == 0 {
+            gl::GenTextures(1, &mut self.backdrop);
+        }
+
+        gl::ActiveTexture(gl::TEXTURE0 + BACKDROP_TEXTURE_UNIT);
+        gl::BindTexture(gl::TEXTURE_2D, self.backdrop);
+
+        if self.backdrop_size != size || self.backdrop_color_space != self.color_space {
+            // An sRGB texture keeps the copied framebuffer decoding to linear values when sampled.
+            let format = self.framebuffer_format();
+            gl::TexImage2D(gl::TEXTURE_2D, 0, format as GLint, size[0], size[1], 0, gl::RGBA, gl::UNSIGNED_BYTE, null());
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as GLint);
+            self.backdrop_size = size;
+            self.backdrop_color_space = self.color_space;
+        }
+
+        gl::ActiveTexture(gl::TEXTURE0);
+    }
+}
+
+impl Drop for GlCanvasRenderer {
+    fn drop(&mut self) {
+        unsafe {
+            if self.backdrop != 0 {
+                gl::DeleteTextures(1, &self.backdrop);
+            }
+            if self.ramp_texture != 0 {
+                gl::DeleteTextures(1, &self.ramp_texture);
+            }
+            if self.ubo != 0 {
+                gl::DeleteBuffers(1, &self.ubo);
+            }
+            gl::DeleteBuffers(1, &self.vbo);
+            if self.ebo != 0 {
+                gl::DeleteBuffers(1, &self.ebo);
+            }
+            if self.vao != 0 {
+                gl::DeleteVertexArrays(1, &self.vao);
+            }
+        }
+    }
+}
+
+/// Checks the version of the current context against the one required by `backend`.
+fn check_version(backend: GlBackend) -> Result<(), RendererError> {
+    let version = unsafe {
+        let version = gl::GetString(gl::VERSION);
+        if version.is_null() {
+            String::new()
+        } else {
+            CStr::from_ptr(version as *const _).to_string_lossy().into_owned()
+        }
+    };
+
+    let supported = match parse_version(&version) {
+        Some((es, major, minor)) => {
+            let version = (major, minor);
+            let core_profile = !es && version >= (3, 2) && unsafe {
+                let mut mask = 0;
+                gl::GetIntegerv(gl::CONTEXT_PROFILE_MASK, &mut mask);
+                mask as GLenum & gl::CONTEXT_CORE_PROFILE_BIT != 0
+            };
+            let framebuffer_object = !es && version < (3, 0) && unsafe {
+                let extensions = gl::GetString(gl::EXTENSIONS);
+                !extensions.is_null() && CStr::from_ptr(extensions as *const _)
+                    .to_string_lossy()
+                    .split(' ')
+                    .any(|extension| extension == "GL_ARB_framebuffer_object")
+            };
+            supports_backend(backend, es, version, core_profile, framebuffer_object)
+        }
+        None => false,
+    };
+
+    if supported {
+        Ok(())
+    } else {
+        Err(RendererError::UnsupportedVersion { backend, version })
+    }
+}
+
+/// Whether a context of `version` can run `backend`. `framebuffer_object` tells whether
+/// `ARB_framebuffer_object` is available, which is only looked up for GL 2.
+fn supports_backend(backend: GlBackend, es: bool, version: (u32, u32), core_profile: bool, framebuffer_object: bool) -> bool {
+    match backend {
+        // Layers and framebuffers need framebuffer objects, and core profiles dropped GLSL 1.20.
+        GlBackend::Gl2 => !es && !core_profile && (version >= (3, 0) || version >= (2, 1) && framebuffer_object),
+        GlBackend::Gl3 => !es && version >= (3, 3),
+        // Desktop GL runs GLSL ES since ARB_ES2_compatibility and ARB_ES3_compatibility. GLES 2
+        // draws without a vertex array, which core profiles don't allow.
+        GlBackend::Gles2 => if es { version >= (2, 0) } else { version >= (4, 1) && !core_profile },
+        GlBackend::Gles3 => if es { version >= (3, 0) } else { version >= (4, 3) },
+    }
+}
+
+/// Parses a `GL_VERSION` string into whether it is GLES and its major and minor version.
+fn parse_version(version: &str) -> Option<(bool, u32, u32)> {
+    let (es, version) = match version.strip_prefix("OpenGL ES") {
+        Some(rest) => {
+            // GLES 1 reports "OpenGL ES-CM 1.1".
+            let rest = rest.trim_start_matches(|c: char| c != ' ');
+            (true, rest.trim_start())
+        }
+        None => (false, version),
+    };
+    let mut numbers = version.split(|c: char| !c.is_ascii_digit());
+    let major = numbers.next()?.parse().ok()?;
+    let minor = numbers.next()?.parse().ok()?;
+    Some((es, major, minor))
+}
+
+/// Converts a color to the blending color space and premultiplies it.
+fn convert_color(color: [f32; 4], color_space: ColorSpace) -> [f32; 4] {
+    let a = color[3];
+    let rgb = match color_space {
+        ColorSpace::Srgb => [color[0], color[1], color[2]],
+        ColorSpace::LinearSrgb => [srgb_to_linear(color[0]), srgb_to_linear(color[1]), srgb_to_linear(color[2])],
+    };
+    [
+        rgb[0] * a,
+        rgb[1] * a,
+        rgb[2] * a,
+        a
+    ]
+}
+
+fn convert_blend_factor(factor: BlendFactor) -> GLenum {
+    match factor {
+        BlendFactor::Zero => gl::ZERO,
+        BlendFactor::One => gl::ONE,
+        BlendFactor::SrcColor => gl::SRC_COLOR,
+        BlendFactor::OneMinusSrcColor => gl::ONE_MINUS_SRC_COLOR,
+        BlendFactor::DstColor => gl::DST_COLOR,
+        BlendFactor::OneMinusDstColor => gl::ONE_MINUS_DST_COLOR,
+        BlendFactor::SrcAlpha => gl::SRC_ALPHA,
+        BlendFactor::OneMinusSrcAlpha => gl::ONE_MINUS_SRC_ALPHA,
+        BlendFactor::DstAlpha => gl::DST_ALPHA,
+        BlendFactor::OneMinusDstAlpha => gl::ONE_MINUS_DST_ALPHA,
+        BlendFactor::SrcAlphaSaturate => gl::SRC_ALPHA_SATURATE,
+    }
+}
+
+fn convert_blend_func(composite: &CompositeState) -> BlendFunc {
+    BlendFunc {
+        src_rgb: convert_blend_factor(composite.src_rgb),
+        dst_rgb: convert_blend_factor(composite.dst_rgb),
+        src_alpha: convert_blend_factor(composite.src_alpha),
+        dst_alpha: convert_blend_factor(composite.dst_alpha),
+    }
+}
+
+fn convert_transform(t: Transform) -> [f32; 12] {
+    [
+        t.e[0], t.e[1], 0.0, 0.0,
+        t.e[2], t.e[3], 0.0, 0.0,
+        t.e[4], t.e[5], 1.0, 0.0,
+    ]
+}
+
+fn find_image(images: &HashMap<i32, GlImage>, image: i32) -> Option<&GlImage> {
+    images.get(&image).filter(|image| image.framebuffer.strong_count() > 0)
+}
+
+/// Returns the ramp texture row of paints that need one, which are multi-stop gradients,
+/// sweep gradients and gradients interpolated in OKLab.
+fn add_gradient_ramp(ramps: &mut Vec<f32>, paint: &Paint, color_space: ColorSpace, interpolation: GradientInterpolation) -> Option<u32> {
+    if paint.image != 0 {
+        return None;
+    }
+
+    let two_stops = [(0.0, paint.inner_color), (1.0, paint.outer_color)];
+    if !paint.stops.is_empty() {
+        Some(add_ramp_row(ramps, &paint.stops, color_space, interpolation))
+    } else if let GradientShape::Sweep { .. } = paint.shape {
+        Some(add_ramp_row(ramps, &two_stops, color_space, interpolation))
+    } else if interpolation == GradientInterpolation::Oklab && paint.inner_color != paint.outer_color {
+        Some(add_ramp_row(ramps, &two_stops, color_space, interpolation))
+    } else {
+        None
+    }
+}
+
+/// Rasterizes color stops sorted by offset into a new row of the ramp texture.
+fn add_ramp_row(ramps: &mut Vec<f32>, stops: &[(Scalar, [f32; 4])], color_space: ColorSpace, interpolation: GradientInterpolation) -> u32 {
+    let row = ramps.len() / (RAMP_WIDTH * 4);
+    let mut stop = 0;
+    for i in 0..RAMP_WIDTH {
+        let offset = i as Scalar / (RAMP_WIDTH - 1) as Scalar;
+        while stop + 1 < stops.len() && stops[stop + 1].0 < offset {
+            stop += 1;
+        }
+
+        let (offset0, color0) = stops[stop];
+        let (color1, t) = match stops.get(stop + 1) {
+            Some(&(offset1, color1)) if offset > offset0 && offset1 > offset0 => {
+                (color1, clamp((offset - offset0) / (offset1 - offset0), 0.0, 1.0))
+            }
+            Some(&(_, color1)) if offset > offset0 => (color1, 1.0),
+            _ => (color0, 0.0),
+        };
+
+        ramps.extend_from_slice(&interpolate_color(color0, color1, t, color_space, interpolation));
+    }
+
+    row as u32
+}
+
+/// Interpolates two colors, returning a premultiplied color in the blending color space.
+fn interpolate_color(color0: [f32; 4], color1: [f32; 4], t: Scalar, color_space: ColorSpace, interpolation: GradientInterpolation) -> [f32; 4] {
+    match interpolation {
+        GradientInterpolation::ColorSpace => {
+            let c0 = convert_color(color0, color_space);
+            let c1 = convert_color(color1, color_space);
+            [
+                c0[0] + (c1[0] - c0[0]) * t,
+                c0[1] + (c1[1] - c0[1]) * t,
+                c0[2] + (c1[2] - c0[2]) * t,
+                c0[3] + (c1[3] - c0[3]) * t,
+            ]
+        }
+        GradientInterpolation::Oklab => {
+            let to_oklab = |c: [f32; 4]| linear_srgb_to_oklab([srgb_to_linear(c[0]), srgb_to_linear(c[1]), srgb_to_linear(c[2])]);
+            let lab0 = to_oklab(color0);
+            let lab1 = to_oklab(color1);
+            let (a0, a1) = (color0[3], color1[3]);
+            let a = a0 + (a1 - a0) * t;
+
+            // Interpolate premultiplied values so transparent stops don't bleed their color.
+            let mut lab = [0.0; 3];
+            if a > 0.0 {
+                for i in 0..3 {
+                    lab[i] = (lab0[i] * a0 + (lab1[i] * a1 - lab0[i] * a0) * t) / a;
+                }
+            }
+
+            let rgb = oklab_to_linear_srgb(lab);
+            let rgb = match color_space {
+                ColorSpace::Srgb => [linear_to_srgb(rgb[0]), linear_to_srgb(rgb[1]), linear_to_srgb(rgb[2])],
+                ColorSpace::LinearSrgb => rgb,
+            };
+            [
+                clamp(rgb[0], 0.0, 1.0) * a,
+                clamp(rgb[1], 0.0, 1.0) * a,
+                clamp(rgb[2], 0.0, 1.0) * a,
+                a,
+            ]
+        }
+    }
+}
+
+fn convert_paint(uniforms: &mut Uniforms, paint: &Paint, image: Option<&GlImage>, ramp_row: Option<u32>, color_space: ColorSpace, scissor: &Scissor, width: Scalar, fringe: Scalar, stroke_thr: Scalar) {
+    uniforms.inner_col = convert_color(paint.inner_color, color_space);
+    uniforms.outer_col = convert_color(paint.outer_color, color_space);
+
+    if scissor.extent[0] < -0.5 || scissor.extent[1] < -0.5 {
+        uniforms.scissor_mat = [0.0; 12];
+        uniforms.scissor_ext = [1.0, 1.0];
+        uniforms.scissor_scale = [1.0, 1.0];
+    } else {
+        let e = &scissor.transform.e;
+        uniforms.scissor_mat = convert_transform(scissor.transform.inverse());
+        uniforms.scissor_ext = scissor.extent;
+        uniforms.scissor_scale = [
+            (e[0] * e[0] + e[2] * e[2]).sqrt() / fringe,
+            (e[1] * e[1] + e[3] * e[3]).sqrt() / fringe,
+        ];
+    }
+
+    uniforms.extent = paint.extent;
+
+    uniforms.stroke_mult = (width * 0.5 + fringe * 0.5) / fringe;
+    uniforms.stroke_thr = stroke_thr;
+
+    let inv_transform = if let Some(image) = image {
+        uniforms.ty = SHADER_FILL_IMAGE;
+        uniforms.tex_type = image.tex_type;
+
+        if image.flip_y {
+            // Mirror the image around the middle of the paint extent.
+            let m1 = Transform::translate(0.0, -paint.extent[1] * 0.5);
+            let m2 = Transform::scale(1.0, -1.0);
+            let m3 = Transform::translate(0.0, paint.extent[1] * 0.5);
+            (m1 * m2 * m3 * paint.transform).inverse()
+        } else {
+            paint.transform.inverse()
+        }
+    } else {
+        uniforms.spread_mode = paint.spread as u32 as f32;
+        match (paint.shape, ramp_row) {
+            (GradientShape::Sweep { start_angle, end_angle }, Some(ramp_row)) => {
+                uniforms.ty = SHADER_FILL_SWEEP;
+                uniforms.ramp_row = ramp_row as f32;
+                uniforms.sweep_angles = [start_angle, end_angle];
+            }
+            (_, Some(ramp_row)) => {
+                uniforms.ty = SHADER_FILL_GRADIENT_RAMP;
+                uniforms.ramp_row = ramp_row as f32;
+            }
+            (_, None) => uniforms.ty = SHADER_FILL_GRADIENT,
+        }
+        uniforms.radius = paint.radius;
+        uniforms.feather = paint.feather;
+        paint.transform.inverse()
+    };
+
+    uniforms.paint_mat = convert_transform(inv_transform);
+}
+
+struct DrawCall {
+    ty: DrawCallType,
+    path_offset: u32,
+    path_count: u32,
+    triangle_offset: u32,
+    triangle_count: u32,
+    uniform_offset: u32,
+    image: u32,
+    blend_func: BlendFunc,
+    blend_mode: BlendMode,
+    clip: Option<usize>,
+    indices: Option<DrawIndices>,
+}
+
+struct DebugOverlay {
+    uniform_offset: u32,
+    box_offset: u32,
+    box_count: u32,
+    /// Whether the clip paths were uploaded with the frame.
+    clipped: bool,
+}
+
+/// Ranges of the element buffer holding the paths of a batched draw call as triangle lists.
+#[derive(Copy, Clone, Default)]
+struct DrawIndices {
+    fill_offset: u32,
+    fill_count: u32,
+    stroke_offset: u32,
+    stroke_count: u32,
+}
+
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum DrawCallType {
+    Fill,
+    Stroke,
+    PushLayer,
+    PopLayer,
+}
+
+impl DrawCall {
+    /// Whether `other` can be drawn in the same GL draw as `self`, which needs the same uniforms
+    /// and state. Advanced blend modes copy the backdrop before each call, so they aren't merged.
+    fn can_merge(&self, other: &DrawCall, uniform_buffer: &UniformBuffer) -> bool {
+        let uniforms = |draw_call: &DrawCall| {
+            let offset = draw_call.uniform_offset as usize;
+            &uniform_buffer.buf[offset..offset + size_of::<Uniforms>()]
+        };
+
+        self.ty == DrawCallType::Stroke && other.ty == DrawCallType::Stroke &&
+            self.indices.is_some() && other.indices.is_some() &&
+            self.blend_mode == BlendMode::Normal && other.blend_mode == BlendMode::Normal &&
+            self.image == other.image &&
+            self.blend_func == other.blend_func &&
+            self.clip == other.clip &&
+            uniforms(self) == uniforms(other)
+    }
+
+    /// Returns the `[x, y, width, height]` window rectangle covered by the vertices of the call,
+    /// rounded out to whole pixels and clamped to a framebuffer of `size` pixels.
+    fn pixel_bounds(&self, paths: &[BufferRef], verts: &[ShaderVertex], pixels_per_point: f32, size: [i32; 2]) -> Option<[i32; 4]> {
+        let mut ranges = vec![(self.triangle_offset, self.triangle_count)];
+        for path in &paths[self.path_offset as usize..(self.path_offset + self.path_count) as usize] {
+            ranges.push((path.fill_offset, path.fill_count));
+            ranges.push((path.stroke_offset, path.stroke_count));
+        }
+
+        let mut bounds = [f32::MAX, f32::MAX, f32::MIN, f32::MIN];
+        for &(offset, count) in ranges.iter() {
+            for vert in &verts[offset as usize..(offset + count) as usize] {
+                bounds[0] = bounds[0].min(vert.pos[0]);
+                bounds[1] = bounds[1].min(vert.pos[1]);
+                bounds[2] = bounds[2].max(vert.pos[0]);
+                bounds[3] = bounds[3].max(vert.pos[1]);
+            }
+        }
+
+        // Window coordinates start at the bottom left.
+        let height = size[1] as f32;
+        let x0 = ((bounds[0] * pixels_per_point).floor() as i32).max(0);
+        let y0 = ((height - bounds[3] * pixels_per_point).floor() as i32).max(0);
+        let x1 = ((bounds[2] * pixels_per_point).ceil() as i32).min(size[0]);
+        let y1 = ((height - bounds[1] * pixels_per_point).ceil() as i32).min(size[1]);
+        if x0 < x1 && y0 < y1 {
+            Some([x0, y0, x1 - x0, y1 - y0])
+        } else {
+            None
+        }
+    }
+
+    unsafe fn draw(&self, paths: &[BufferRef], uniforms: &FragUniforms, uniform_size: u32) {
+        let blend = &self.blend_func;
+        gl::BlendFuncSeparate(blend.src_rgb, blend.dst_rgb, blend.src_alpha, blend.dst_alpha);
+        match self.ty {
+            DrawCallType::Stroke => self.stroke(paths, uniforms),
+            DrawCallType::Fill => self.fill(paths, uniforms, uniform_size),
+            DrawCallType::PopLayer => self.triangles(uniforms),
+            DrawCallType::PushLayer => {},
+        }
+    }
+
+    unsafe fn triangles(&self, uniforms: &FragUniforms) {
+        uniforms.bind(self.uniform_offset);
+
+        if self.clip.is_some() {
+            gl::Enable(gl::STENCIL_TEST);
+            gl::StencilMask(0);
+            gl::StencilFunc(gl::EQUAL, 0, STENCIL_CLIP_BIT);
+            gl::StencilOp(gl::KEEP, gl::KEEP, gl::KEEP);
+        }
+
+        gl::DrawArrays(gl::TRIANGLES, self.triangle_offset as _, self.triangle_count as _);
+
+        if self.clip.is_some() {
+            gl::Disable(gl::STENCIL_TEST);
+        }
+    }
+
+    unsafe fn stroke(&self, paths: &[BufferRef], uniforms: &FragUniforms) {
+        let paths = &paths[self.path_offset as usize..(self.path_offset + self.path_count) as usize];
+
+        uniforms.bind(self.uniform_offset);
+
+        gl::BindTexture(gl::TEXTURE_2D, self.image);
+
+        if self.clip.is_some() {
+            gl::Enable(gl::STENCIL_TEST);
+            gl::StencilMask(0);
+            gl::StencilFunc(gl::EQUAL, 0, STENCIL_CLIP_BIT);
+            gl::StencilOp(gl::KEEP, gl::KEEP, gl::KEEP);
+        }
+
+        match self.indices {
+            Some(indices) => draw_elements(indices.stroke_offset, indices.stroke_count),
+            None => {
+                for path in paths.iter() {
+                    gl::DrawArrays(gl::TRIANGLE_STRIP, path.stroke_offset as _, path.stroke_count as _);
+                }
+            }
+        }
+
+        if self.clip.is_some() {
+            gl::Disable(gl::STENCIL_TEST);
+        }
+    }
+
+    unsafe fn fill(&self, paths: &[BufferRef], uniforms: &FragUniforms, uniform_size: u32) {
+        let paths = &paths[self.path_offset as usize..(self.path_offset + self.path_count) as usize];
+
+        // Draw shapes, the winding is only counted inside of the clip region
+        gl::Enable(gl::STENCIL_TEST);
+        gl::StencilMask(STENCIL_WINDING_MASK);
+        gl::StencilFunc(gl::EQUAL, 0, STENCIL_CLIP_BIT);
+        gl::ColorMask(gl::FALSE, gl::FALSE, gl::FALSE, gl::FALSE);
+
+        uniforms.bind(self.uniform_offset);
+        gl::StencilOpSeparate(gl::FRONT, gl::KEEP, gl::KEEP, gl::INCR_WRAP);
+        gl::StencilOpSeparate(gl::BACK, gl::KEEP, gl::KEEP, gl::DECR_WRAP);
+        gl::Disable(gl::CULL_FACE);
+        match self.indices {
+            Some(indices) => draw_elements(indices.fill_offset, indices.fill_count),
+            None => {
+                for path in paths.iter() {
+                    gl::DrawArrays(gl::TRIANGLE_FAN, path.fill_offset as _, path.fill_count as _);
+                }
+            }
+        }
+        gl::Enable(gl::CULL_FACE);
+
+        // Draw anti-aliased pixels
+        gl::ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE);
+
+        uniforms.bind(self.uniform_offset + uniform_size);
+        gl::BindTexture(gl::TEXTURE_2D, self.image);
+
+        // Anti-alias
+        {
+            gl::StencilFunc(gl::EQUAL, 0x0, 0xff);
+            gl::StencilOp(gl::KEEP, gl::KEEP, gl::KEEP);
+            // Draw fringes
+            match self.indices {
+                Some(indices) => draw_elements(indices.stroke_offset, indices.stroke_count),
+                None => {
+                    for path in paths.iter() {
+                        gl::DrawArrays(gl::TRIANGLE_STRIP, path.stroke_offset as _, path.stroke_count as _);
+                    }
+                }
+            }
+        }
+
+        // Draw fill
+        gl::StencilFunc(gl::NOTEQUAL, 0x0, STENCIL_WINDING_MASK);
+        gl::StencilOp(gl::ZERO, gl::ZERO, gl::ZERO);
+        gl::DrawArrays(gl::TRIANGLE_STRIP, self.triangle_offset as _, self.triangle_count as _);
+
+        gl::Disable(gl::STENCIL_TEST);
+    }
+}
+
+/// Draws `count` indices from the element buffer as triangles.
+unsafe fn draw_elements(offset: u32, count: u32) {
+    if count > 0 {
+        gl::DrawElements(gl::TRIANGLES, count as _, gl::UNSIGNED_INT, (offset as usize * size_of::<u32>()) as *const _);
+    }
+}
+
+/// Appends the triangles of a triangle strip, keeping their winding.
+fn add_strip_indices(indices: &mut Vec<u32>, offset: u32, count: u32) {
+    for i in 0..count.saturating_sub(2) {
+        let v = offset + i;
+        if i % 2 == 0 {
+            indices.extend_from_slice(&[v, v + 1, v + 2]);
+        } else {
+            indices.extend_from_slice(&[v + 1, v, v + 2]);
+        }
+    }
+}
+
+/// Appends the triangles of a triangle fan.
+fn add_fan_indices(indices: &mut Vec<u32>, offset: u32, count: u32) {
+    for i in 1..count.saturating_sub(1) {
+        indices.extend_from_slice(&[offset, offset + i, offset + i + 1]);
+    }
+}
+
+/// Binds the fragment uniforms of a draw call, from the uniform buffer or as a uniform array.
+struct FragUniforms<'a> {
+    ubo: GLuint,
+    loc: GLint,
+    buf: &'a [u8],
+}
+
+impl<'a> FragUniforms<'a> {
+    unsafe fn bind(&self, offset: u32) {
+        if self.ubo != 0 {
+            gl::BindBufferRange(gl::UNIFORM_BUFFER, FRAG_BINDING, self.ubo, offset as _, FRAG_BLOCK_SIZE as _);
+        } else {
+            gl::Uniform4fv(self.loc, UNIFORMARRAY_SIZE as _, self.buf.as_ptr().add(offset as usize) as *const f32);
+        }
+    }
+}
+
+#[derive(Eq, PartialEq)]
+struct BlendFunc {
+    src_rgb: GLenum,
+    dst_rgb: GLenum,
+    src_alpha: GLenum,
+    dst_alpha: GLenum,
+}
+
+#[repr(C)]
+struct Uniforms {
     scissor_mat: [f32; 12],
     paint_mat: [f32; 12],
     inner_col: [f32; 4],
@@ -463,14 +2012,36 @@ struct Uniforms {
     feather: f32,
     stroke_mult: f32,
     stroke_thr: f32,
-    tex_type: u32,
-    ty: u32,
+    tex_type: f32,
+    ty: f32,
+    blend_mode: f32,
+    ramp_row: f32,
+    sweep_angles: [f32; 2],
+    spread_mode: f32,
 }
 
-const SHADER_FILL_GRADIENT: u32 = 0;
-const SHADER_FILL_IMAGE: u32 = 1;
-const SHADER_SIMPLE: u32 = 2;
-const SHADER_IMAGE: u32 = 3;
+// Integers are passed as floats, since GLSL ES 1.00 has no integer uniform arrays.
+const SHADER_FILL_GRADIENT: f32 = 0.0;
+const SHADER_FILL_IMAGE: f32 = 1.0;
+const SHADER_SIMPLE: f32 = 2.0;
+const SHADER_IMAGE: f32 = 3.0;
+const SHADER_FILL_GRADIENT_RAMP: f32 = 4.0;
+const SHADER_FILL_SWEEP: f32 = 5.0;
+const SHADER_DEBUG: f32 = 6.0;
+
+/// Number of vec4 the fragment uniforms take up.
+const UNIFORMARRAY_SIZE: usize = 13;
+/// Size of the std140 `frag` block, which is bound whole even though `Uniforms` is smaller.
+const FRAG_BLOCK_SIZE: usize = UNIFORMARRAY_SIZE * 16;
+const _: () = assert!(size_of::<Uniforms>() <= FRAG_BLOCK_SIZE);
+
+const TEXTURE_RGBA_PREMULTIPLIED: f32 = 0.0;
+
+const BACKDROP_TEXTURE_UNIT: GLuint = 1;
+const RAMP_TEXTURE_UNIT: GLuint = 2;
+
+/// Number of texels in each row of the gradient ramp texture.
+const RAMP_WIDTH: usize = 256;
 
 #[derive(Debug)]
 struct UniformBuffer {
@@ -487,8 +2058,7 @@ impl UniformBuffer {
 
     fn alloc(&mut self, n: usize) -> usize {
         let nbytes = self.uniform_size * n;
-        self.buf.reserve(nbytes);
-        unsafe { self.buf.set_len(self.buf.len() + nbytes); }
+        self.buf.resize(self.buf.len() + nbytes, 0);
         let offset = self.nuniforms;
         self.nuniforms += n;
         offset
@@ -497,7 +2067,7 @@ impl UniformBuffer {
     fn get_mut(&mut self, index: usize) -> &mut Uniforms {
         assert!(index < self.nuniforms);
         unsafe {
-            &mut *(self.buf.as_mut_ptr().offset((self.uniform_size * index) as _) as *mut Uniforms)
+            &mut *(self.buf.as_mut_ptr().add(self.uniform_size * index) as *mut Uniforms)
         }
     }
 
@@ -524,33 +2094,56 @@ struct Shader {
     prog: GlProgram,
     loc_view_size: GLint,
     loc_tex: GLint,
-    loc_frag: GLuint,
+    loc_backdrop: GLint,
+    loc_ramp: GLint,
+    loc_frame_size: GLint,
+    loc_ramp_size: GLint,
+    loc_frag: GLint,
+    frag_block: GLuint,
 }
 
 static VERTEX_SHADER: &str = include_str!("shader.vert");
 static FRAGMENT_SHADER: &str = include_str!("shader.frag");
 
 impl Shader {
-    pub fn load() -> Shader {
+    pub fn load(backend: GlBackend) -> Result<Shader, RendererError> {
         let mut prog = GlProgram::new();
         let mut vs = GlShader::new(gl::VERTEX_SHADER);
-        vs.compile(VERTEX_SHADER);
+        vs.compile(backend.shader_header(gl::VERTEX_SHADER) + VERTEX_SHADER)
+            .map_err(|log| RendererError::ShaderCompile { stage: ShaderStage::Vertex, log })?;
         prog.attach(&vs);
 
         let mut fs = GlShader::new(gl::FRAGMENT_SHADER);
-        fs.compile(FRAGMENT_SHADER);
+        fs.compile(backend.shader_header(gl::FRAGMENT_SHADER) + FRAGMENT_SHADER)
+            .map_err(|log| RendererError::ShaderCompile { stage: ShaderStage::Fragment, log })?;
         prog.attach(&fs);
 
-        prog.link();
+        // GLSL 1.x has no layout qualifiers, so the attribute locations are bound before linking.
+        prog.bind_attrib_location(0, "v_pos");
+        prog.bind_attrib_location(1, "v_tex_coord");
+        prog.link().map_err(|log| RendererError::ProgramLink { log })?;
 
-        unsafe {
-            Shader {
-                loc_view_size: gl::GetUniformLocation(prog.id, CString::new("u_view_size").unwrap().as_ptr()),
-                loc_tex: gl::GetUniformLocation(prog.id, CString::new("u_tex").unwrap().as_ptr()),
-                loc_frag: gl::GetUniformLocation(prog.id, CString::new("u_frag").unwrap().as_ptr()) as GLuint,
-                prog,
+        let (loc_frag, frag_block) = if backend.uses_uniform_buffer() {
+            let block = unsafe { gl::GetUniformBlockIndex(prog.id, CString::new("frag").unwrap().as_ptr()) };
+            if block == gl::INVALID_INDEX {
+                return Err(RendererError::MissingUniform { name: "frag" });
             }
-        }
+            (-1, block)
+        } else {
+            (prog.uniform_location("u_frag")?, gl::INVALID_INDEX)
+        };
+
+        Ok(Shader {
+            loc_view_size: prog.uniform_location("u_view_size")?,
+            loc_tex: prog.uniform_location("u_tex")?,
+            loc_backdrop: prog.uniform_location("u_backdrop")?,
+            loc_ramp: prog.uniform_location("u_ramp")?,
+            loc_frame_size: prog.uniform_location("u_frame_size")?,
+            loc_ramp_size: prog.uniform_location("u_ramp_size")?,
+            loc_frag,
+            frag_block,
+            prog,
+        })
     }
 }
 
@@ -565,7 +2158,8 @@ impl GlShader {
         }
     }
 
-    pub fn compile<T: Into<Vec<u8>>>(&mut self, source: T) {
+    /// Compiles `source`, returning the info log on failure.
+    pub fn compile<T: Into<Vec<u8>>>(&mut self, source: T) -> Result<(), String> {
         let source = CString::new(source).unwrap();
         unsafe {
             gl::ShaderSource(self.id, 1, &source.as_ptr(), null());
@@ -576,11 +2170,12 @@ impl GlShader {
             if success as u8 != gl::TRUE {
                 let mut len = 0;
                 gl::GetShaderiv(self.id, gl::INFO_LOG_LENGTH, &mut len);
-                let mut buffer = Vec::with_capacity(len as usize);
-                gl::GetShaderInfoLog(self.id, len, null_mut(), buffer.as_mut_ptr() as *mut i8);
-                panic!("{}", CStr::from_ptr(buffer.as_ptr()).to_str().unwrap());
+                let mut buffer = vec![0u8; len.max(1) as usize];
+                gl::GetShaderInfoLog(self.id, len, null_mut(), buffer.as_mut_ptr() as *mut GLchar);
+                return Err(info_log(buffer));
             }
         }
+        Ok(())
     }
 }
 
@@ -607,7 +2202,15 @@ impl GlProgram {
         }
     }
 
-    pub fn link(&mut self) {
+    pub fn bind_attrib_location(&mut self, index: GLuint, name: &str) {
+        let name = CString::new(name).unwrap();
+        unsafe {
+            gl::BindAttribLocation(self.id, index, name.as_ptr());
+        }
+    }
+
+    /// Links the attached shaders, returning the info log on failure.
+    pub fn link(&mut self) -> Result<(), String> {
         unsafe {
             gl::LinkProgram(self.id);
 
@@ -616,12 +2219,30 @@ impl GlProgram {
             if success as u8 != gl::TRUE {
                 let mut len = 0;
                 gl::GetProgramiv(self.id, gl::INFO_LOG_LENGTH, &mut len);
-                let mut buffer = Vec::with_capacity(len as usize);
-                gl::GetProgramInfoLog(self.id, len, null_mut(), buffer.as_mut_ptr() as *mut i8);
-                panic!("{}", CStr::from_ptr(buffer.as_ptr()).to_str().unwrap());
+                let mut buffer = vec![0u8; len.max(1) as usize];
+                gl::GetProgramInfoLog(self.id, len, null_mut(), buffer.as_mut_ptr() as *mut GLchar);
+                return Err(info_log(buffer));
             }
         }
+        Ok(())
+    }
+
+    pub fn uniform_location(&self, name: &'static str) -> Result<GLint, RendererError> {
+        let loc = unsafe { gl::GetUniformLocation(self.id, CString::new(name).unwrap().as_ptr()) };
+        if loc < 0 {
+            Err(RendererError::MissingUniform { name })
+        } else {
+            Ok(loc)
+        }
+    }
+}
+
+/// Converts a nul terminated info log into a string.
+fn info_log(mut buffer: Vec<u8>) -> String {
+    if let Some(end) = buffer.iter().position(|&c| c == 0) {
+        buffer.truncate(end);
     }
+    String::from_utf8_lossy(&buffer).trim_end().to_string()
 }
 
 impl Drop for GlProgram {
@@ -629,3 +2250,871 @@ impl Drop for GlProgram {
         unsafe { gl::DeleteProgram(self.id); }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::{
+        collections::HashMap,
+        sync::Mutex,
+    };
+
+    /// GL state tracked by the fake GL functions, keyed by the `glGet` name that queries it.
+    #[derive(Clone, Debug, Default, PartialEq)]
+    struct FakeGl {
+        integers: HashMap<GLenum, GLint>,
+        enabled: HashMap<GLenum, bool>,
+        textures: HashMap<GLint, GLint>,
+        attribs: HashMap<GLuint, GLint>,
+        element_buffers: HashMap<GLint, GLint>,
+        color_mask: [GLboolean; 4],
+        clear_color: [GLfloat; 4],
+        draws: usize,
+        stencil_clears: usize,
+        /// Internal formats of the textures, set by `glTexImage2D`.
+        texture_formats: HashMap<GLint, GLint>,
+        framebuffer_binds: Vec<GLuint>,
+        /// Names to hand out again, since GL reuses the names of deleted objects.
+        free_names: Vec<GLuint>,
+        next_name: GLuint,
+        /// Offset and size of the last uniform buffer range bound.
+        uniform_range: (GLintptr, GLsizeiptr),
+        /// `[x, y, width, height]` of the backdrop copies.
+        backdrop_copies: Vec<[GLint; 4]>,
+    }
+
+    static FAKE_GL: Mutex<Option<FakeGl>> = Mutex::new(None);
+    /// Held by tests using the fake GL, since the GL functions are global.
+    static GL_TEST: Mutex<()> = Mutex::new(());
+
+    fn with_gl<T>(f: impl FnOnce(&mut FakeGl) -> T) -> T {
+        f(FAKE_GL.lock().unwrap().as_mut().unwrap())
+    }
+
+    fn set(fake: &mut FakeGl, name: GLenum, value: GLint) {
+        fake.integers.insert(name, value);
+    }
+
+    fn set_stencil(fake: &mut FakeGl, face: GLenum, front: [GLenum; 3], back: [GLenum; 3], values: [GLint; 3]) {
+        for (i, &value) in values.iter().enumerate() {
+            if face != gl::BACK {
+                set(fake, front[i], value);
+            }
+            if face != gl::FRONT {
+                set(fake, back[i], value);
+            }
+        }
+    }
+
+    extern "system" fn get_string(_: GLenum) -> *const GLubyte { b"3.3.0 Fake\0".as_ptr() }
+    extern "system" fn create(_: GLenum) -> GLuint { 1 }
+    extern "system" fn create_program() -> GLuint { 1 }
+    extern "system" fn shader_source(_: GLuint, _: GLsizei, _: *const *const GLchar, _: *const GLint) {}
+    extern "system" fn nop_uint(_: GLuint) {}
+    extern "system" fn nop() {}
+    extern "system" fn get_status(_: GLuint, _: GLenum, value: *mut GLint) { unsafe { *value = 1 } }
+    extern "system" fn attach(_: GLuint, _: GLuint) {}
+    extern "system" fn bind_attrib(_: GLuint, _: GLuint, _: *const GLchar) {}
+    extern "system" fn block_index(_: GLuint, _: *const GLchar) -> GLuint { 0 }
+    extern "system" fn uniform_location(_: GLuint, _: *const GLchar) -> GLint { 0 }
+    extern "system" fn block_binding(_: GLuint, _: GLuint, _: GLuint) {}
+    extern "system" fn gen(n: GLsizei, ids: *mut GLuint) {
+        with_gl(|fake| for i in 0..n as usize {
+            let name = fake.free_names.pop().unwrap_or_else(|| {
+                fake.next_name += 1;
+                10 + fake.next_name
+            });
+            unsafe { *ids.add(i) = name }
+        })
+    }
+    extern "system" fn delete(n: GLsizei, ids: *const GLuint) {
+        with_gl(|fake| for i in 0..n as usize {
+            fake.free_names.push(unsafe { *ids.add(i) });
+        })
+    }
+    extern "system" fn tex_image(_: GLenum, _: GLint, format: GLint, _: GLsizei, _: GLsizei, _: GLint, _: GLenum, _: GLenum, _: *const GLvoid) {
+        with_gl(|fake| {
+            let unit = fake.integers.get(&gl::ACTIVE_TEXTURE).copied().unwrap_or(gl::TEXTURE0 as GLint);
+            let texture = fake.textures.get(&unit).copied().unwrap_or(0);
+            fake.texture_formats.insert(texture, format);
+        })
+    }
+    extern "system" fn tex_parameter(_: GLenum, _: GLenum, _: GLint) {}
+    extern "system" fn bind_framebuffer(_: GLenum, id: GLuint) {
+        with_gl(|fake| {
+            set(fake, gl::FRAMEBUFFER_BINDING, id as _);
+            fake.framebuffer_binds.push(id);
+        })
+    }
+    extern "system" fn bind_renderbuffer(_: GLenum, _: GLuint) {}
+    extern "system" fn renderbuffer_storage(_: GLenum, _: GLenum, _: GLsizei, _: GLsizei) {}
+    extern "system" fn framebuffer_texture(_: GLenum, _: GLenum, _: GLenum, _: GLuint, _: GLint) {}
+    extern "system" fn framebuffer_renderbuffer(_: GLenum, _: GLenum, _: GLenum, _: GLuint) {}
+    extern "system" fn copy_tex_sub_image(_: GLenum, _: GLint, _: GLint, _: GLint, x: GLint, y: GLint, width: GLsizei, height: GLsizei) {
+        with_gl(|fake| fake.backdrop_copies.push([x, y, width, height]))
+    }
+    extern "system" fn buffer_data(_: GLenum, _: GLsizeiptr, _: *const GLvoid, _: GLenum) {}
+    extern "system" fn attrib_pointer(_: GLuint, _: GLint, _: GLenum, _: GLboolean, _: GLsizei, _: *const GLvoid) {}
+    extern "system" fn uniform1i(_: GLint, _: GLint) {}
+    extern "system" fn uniform_fv(_: GLint, _: GLsizei, _: *const GLfloat) {}
+    extern "system" fn draw_arrays(_: GLenum, _: GLint, _: GLsizei) { with_gl(|fake| fake.draws += 1) }
+    extern "system" fn draw_elements(_: GLenum, _: GLsizei, _: GLenum, _: *const GLvoid) { with_gl(|fake| fake.draws += 1) }
+    extern "system" fn viewport(_: GLint, _: GLint, _: GLsizei, _: GLsizei) {}
+    extern "system" fn clear(mask: GLbitfield) {
+        with_gl(|fake| if mask & gl::STENCIL_BUFFER_BIT != 0 { fake.stencil_clears += 1 })
+    }
+
+    extern "system" fn get_integer(name: GLenum, value: *mut GLint) {
+        with_gl(|fake| {
+            let v = match name {
+                gl::TEXTURE_BINDING_2D => {
+                    let unit = fake.integers.get(&gl::ACTIVE_TEXTURE).copied().unwrap_or(gl::TEXTURE0 as GLint);
+                    fake.textures.get(&unit).copied().unwrap_or(0)
+                }
+                gl::ELEMENT_ARRAY_BUFFER_BINDING => {
+                    let vao = fake.integers.get(&gl::VERTEX_ARRAY_BINDING).copied().unwrap_or(0);
+                    fake.element_buffers.get(&vao).copied().unwrap_or(0)
+                }
+                gl::UNIFORM_BUFFER_OFFSET_ALIGNMENT => 256,
+                _ => fake.integers.get(&name).copied().unwrap_or(0),
+            };
+            unsafe { *value = v }
+        })
+    }
+    extern "system" fn get_boolean(_: GLenum, value: *mut GLboolean) {
+        with_gl(|fake| for i in 0..4 { unsafe { *value.add(i) = fake.color_mask[i] } })
+    }
+    extern "system" fn get_float(_: GLenum, value: *mut GLfloat) {
+        with_gl(|fake| for i in 0..4 { unsafe { *value.add(i) = fake.clear_color[i] } })
+    }
+    extern "system" fn get_attrib(index: GLuint, _: GLenum, value: *mut GLint) {
+        with_gl(|fake| unsafe { *value = fake.attribs.get(&index).copied().unwrap_or(0) })
+    }
+    extern "system" fn is_enabled(cap: GLenum) -> GLboolean {
+        with_gl(|fake| fake.enabled.get(&cap).copied().unwrap_or(false) as GLboolean)
+    }
+    extern "system" fn enable(cap: GLenum) { with_gl(|fake| { fake.enabled.insert(cap, true); }) }
+    extern "system" fn disable(cap: GLenum) { with_gl(|fake| { fake.enabled.insert(cap, false); }) }
+    extern "system" fn enable_attrib(index: GLuint) { with_gl(|fake| { fake.attribs.insert(index, 1); }) }
+    extern "system" fn disable_attrib(index: GLuint) { with_gl(|fake| { fake.attribs.insert(index, 0); }) }
+    extern "system" fn use_program(id: GLuint) { with_gl(|fake| set(fake, gl::CURRENT_PROGRAM, id as _)) }
+    extern "system" fn bind_vertex_array(id: GLuint) { with_gl(|fake| set(fake, gl::VERTEX_ARRAY_BINDING, id as _)) }
+    extern "system" fn bind_buffer(target: GLenum, id: GLuint) {
+        with_gl(|fake| match target {
+            gl::ARRAY_BUFFER => set(fake, gl::ARRAY_BUFFER_BINDING, id as _),
+            gl::ELEMENT_ARRAY_BUFFER => {
+                // The element buffer binding belongs to the bound vertex array.
+                let vao = fake.integers.get(&gl::VERTEX_ARRAY_BINDING).copied().unwrap_or(0);
+                fake.element_buffers.insert(vao, id as _);
+            }
+            _ => set(fake, gl::UNIFORM_BUFFER_BINDING, id as _),
+        })
+    }
+    extern "system" fn bind_buffer_range(_: GLenum, _: GLuint, id: GLuint, offset: GLintptr, size: GLsizeiptr) {
+        with_gl(|fake| {
+            set(fake, gl::UNIFORM_BUFFER_BINDING, id as _);
+            fake.uniform_range = (offset, size);
+        })
+    }
+    extern "system" fn active_texture(unit: GLenum) { with_gl(|fake| set(fake, gl::ACTIVE_TEXTURE, unit as _)) }
+    extern "system" fn bind_texture(_: GLenum, id: GLuint) {
+        with_gl(|fake| {
+            let unit = fake.integers.get(&gl::ACTIVE_TEXTURE).copied().unwrap_or(gl::TEXTURE0 as GLint);
+            fake.textures.insert(unit, id as _);
+        })
+    }
+    extern "system" fn cull_face(mode: GLenum) { with_gl(|fake| set(fake, gl::CULL_FACE_MODE, mode as _)) }
+    extern "system" fn front_face(mode: GLenum) { with_gl(|fake| set(fake, gl::FRONT_FACE, mode as _)) }
+    extern "system" fn color_mask(r: GLboolean, g: GLboolean, b: GLboolean, a: GLboolean) {
+        with_gl(|fake| fake.color_mask = [r, g, b, a])
+    }
+    extern "system" fn clear_color(r: GLfloat, g: GLfloat, b: GLfloat, a: GLfloat) {
+        with_gl(|fake| fake.clear_color = [r, g, b, a])
+    }
+    extern "system" fn clear_stencil(s: GLint) { with_gl(|fake| set(fake, gl::STENCIL_CLEAR_VALUE, s)) }
+    extern "system" fn pixel_store(name: GLenum, value: GLint) { with_gl(|fake| set(fake, name, value)) }
+    extern "system" fn blend_func(src_rgb: GLenum, dst_rgb: GLenum, src_alpha: GLenum, dst_alpha: GLenum) {
+        with_gl(|fake| {
+            set(fake, gl::BLEND_SRC_RGB, src_rgb as _);
+            set(fake, gl::BLEND_DST_RGB, dst_rgb as _);
+            set(fake, gl::BLEND_SRC_ALPHA, src_alpha as _);
+            set(fake, gl::BLEND_DST_ALPHA, dst_alpha as _);
+        })
+    }
+    extern "system" fn stencil_mask_separate(face: GLenum, mask: GLuint) {
+        with_gl(|fake| {
+            let mask = mask as GLint;
+            set_stencil(fake, face, [gl::STENCIL_WRITEMASK; 3], [gl::STENCIL_BACK_WRITEMASK; 3], [mask; 3]);
+        })
+    }
+    extern "system" fn stencil_mask(mask: GLuint) { stencil_mask_separate(gl::FRONT_AND_BACK, mask) }
+    extern "system" fn stencil_func_separate(face: GLenum, func: GLenum, reference: GLint, mask: GLuint) {
+        with_gl(|fake| set_stencil(
+            fake, face,
+            [gl::STENCIL_FUNC, gl::STENCIL_REF, gl::STENCIL_VALUE_MASK],
+            [gl::STENCIL_BACK_FUNC, gl::STENCIL_BACK_REF, gl::STENCIL_BACK_VALUE_MASK],
+            [func as _, reference, mask as _],
+        ))
+    }
+    extern "system" fn stencil_func(func: GLenum, reference: GLint, mask: GLuint) {
+        stencil_func_separate(gl::FRONT_AND_BACK, func, reference, mask)
+    }
+    extern "system" fn stencil_op_separate(face: GLenum, fail: GLenum, depth_fail: GLenum, depth_pass: GLenum) {
+        with_gl(|fake| set_stencil(
+            fake, face,
+            [gl::STENCIL_FAIL, gl::STENCIL_PASS_DEPTH_FAIL, gl::STENCIL_PASS_DEPTH_PASS],
+            [gl::STENCIL_BACK_FAIL, gl::STENCIL_BACK_PASS_DEPTH_FAIL, gl::STENCIL_BACK_PASS_DEPTH_PASS],
+            [fail as _, depth_fail as _, depth_pass as _],
+        ))
+    }
+    extern "system" fn stencil_op(fail: GLenum, depth_fail: GLenum, depth_pass: GLenum) {
+        stencil_op_separate(gl::FRONT_AND_BACK, fail, depth_fail, depth_pass)
+    }
+
+    /// Loads fake GL functions that record the state they set.
+    fn load_fake_gl() {
+        *FAKE_GL.lock().unwrap() = Some(FakeGl::default());
+        gl::load_with(|name| {
+            match name {
+                "glGetString" => get_string as *const _,
+                "glCreateShader" => create as *const _,
+                "glCreateProgram" => create_program as *const _,
+                "glShaderSource" => shader_source as *const _,
+                "glCompileShader" | "glLinkProgram" | "glDeleteShader" | "glDeleteProgram" => nop_uint as *const _,
+                "glFinish" => nop as *const _,
+                "glGetShaderiv" | "glGetProgramiv" => get_status as *const _,
+                "glAttachShader" => attach as *const _,
+                "glBindAttribLocation" => bind_attrib as *const _,
+                "glGetUniformBlockIndex" => block_index as *const _,
+                "glGetUniformLocation" => uniform_location as *const _,
+                "glUniformBlockBinding" => block_binding as *const _,
+                "glGenVertexArrays" | "glGenBuffers" | "glGenTextures" | "glGenFramebuffers" | "glGenRenderbuffers" => gen as *const _,
+                "glDeleteVertexArrays" | "glDeleteBuffers" | "glDeleteTextures" | "glDeleteFramebuffers" | "glDeleteRenderbuffers" => delete as *const _,
+                "glTexImage2D" => tex_image as *const _,
+                "glTexParameteri" => tex_parameter as *const _,
+                "glBindFramebuffer" => bind_framebuffer as *const _,
+                "glBindRenderbuffer" => bind_renderbuffer as *const _,
+                "glRenderbufferStorage" => renderbuffer_storage as *const _,
+                "glFramebufferTexture2D" => framebuffer_texture as *const _,
+                "glFramebufferRenderbuffer" => framebuffer_renderbuffer as *const _,
+                "glCopyTexSubImage2D" => copy_tex_sub_image as *const _,
+                "glBufferData" => buffer_data as *const _,
+                "glVertexAttribPointer" => attrib_pointer as *const _,
+                "glUniform1i" => uniform1i as *const _,
+                "glUniform2fv" | "glUniform4fv" => uniform_fv as *const _,
+                "glDrawArrays" => draw_arrays as *const _,
+                "glDrawElements" => draw_elements as *const _,
+                "glViewport" => viewport as *const _,
+                "glClear" => clear as *const _,
+                "glGetIntegerv" => get_integer as *const _,
+                "glGetBooleanv" => get_boolean as *const _,
+                "glGetFloatv" => get_float as *const _,
+                "glGetVertexAttribiv" => get_attrib as *const _,
+                "glIsEnabled" => is_enabled as *const _,
+                "glEnable" => enable as *const _,
+                "glDisable" => disable as *const _,
+                "glEnableVertexAttribArray" => enable_attrib as *const _,
+                "glDisableVertexAttribArray" => disable_attrib as *const _,
+                "glUseProgram" => use_program as *const _,
+                "glBindVertexArray" => bind_vertex_array as *const _,
+                "glBindBuffer" => bind_buffer as *const _,
+                "glBindBufferRange" => bind_buffer_range as *const _,
+                "glActiveTexture" => active_texture as *const _,
+                "glBindTexture" => bind_texture as *const _,
+                "glCullFace" => cull_face as *const _,
+                "glFrontFace" => front_face as *const _,
+                "glColorMask" => color_mask as *const _,
+                "glClearColor" => clear_color as *const _,
+                "glClearStencil" => clear_stencil as *const _,
+                "glPixelStorei" => pixel_store as *const _,
+                "glBlendFuncSeparate" => blend_func as *const _,
+                "glStencilMask" => stencil_mask as *const _,
+                "glStencilMaskSeparate" => stencil_mask_separate as *const _,
+                "glStencilFunc" => stencil_func as *const _,
+                "glStencilFuncSeparate" => stencil_func_separate as *const _,
+                "glStencilOp" => stencil_op as *const _,
+                "glStencilOpSeparate" => stencil_op_separate as *const _,
+                _ => null(),
+            }
+        });
+    }
+
+    fn fake_gl_state() -> FakeGl {
+        let mut fake = FAKE_GL.lock().unwrap().clone().unwrap();
+        // Only the state set by the GL functions is compared, not what they were called for.
+        fake.draws = 0;
+        fake.stencil_clears = 0;
+        fake.texture_formats.clear();
+        fake.framebuffer_binds.clear();
+        fake.free_names.clear();
+        fake.next_name = 0;
+        fake.uniform_range = (0, 0);
+        fake.backdrop_copies.clear();
+        fake
+    }
+
+    /// Returns the uniforms the draw call at `index` is shaded with, before they are flushed.
+    /// Fills come after the uniforms of their stencil pass.
+    fn draw_call_uniforms(renderer: &mut GlCanvasRenderer, index: usize) -> &Uniforms {
+        let draw_call = &renderer.draw_calls[index];
+        let stencil = (draw_call.ty == DrawCallType::Fill) as usize;
+        let uniform_index = draw_call.uniform_offset as usize / renderer.uniform_buffer.uniform_size;
+        renderer.uniform_buffer.get_mut(uniform_index + stencil)
+    }
+
+    fn draw_frame(renderer: &mut GlCanvasRenderer) {
+        let mut canvas = Canvas::new();
+        canvas.begin_path();
+        canvas.rect(10.0, 10.0, 100.0, 50.0);
+        canvas.set_fill_color(Color::rgb(255, 0, 0));
+        canvas.fill(renderer);
+        renderer.flush();
+    }
+
+    #[test]
+    fn test_restore_state() {
+        let _lock = GL_TEST.lock().unwrap();
+        load_fake_gl();
+
+        // State left by another renderer drawing a 3D scene.
+        unsafe {
+            for cap in [gl::BLEND, gl::CULL_FACE, gl::STENCIL_TEST, gl::FRAMEBUFFER_SRGB] {
+                gl::Disable(cap);
+            }
+            for unit in 0..3 {
+                gl::ActiveTexture(gl::TEXTURE0 + unit);
+                gl::BindTexture(gl::TEXTURE_2D, 0);
+            }
+            gl::DisableVertexAttribArray(0);
+            gl::DisableVertexAttribArray(1);
+            gl::ClearStencil(0);
+            gl::UseProgram(42);
+            gl::Enable(gl::DEPTH_TEST);
+            gl::Enable(gl::SCISSOR_TEST);
+            gl::CullFace(gl::FRONT);
+            gl::FrontFace(gl::CW);
+            gl::BlendFuncSeparate(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA, gl::ONE, gl::ZERO);
+            gl::ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::FALSE);
+            gl::StencilMask(0x0f);
+            gl::StencilFunc(gl::EQUAL, 1, 0xff);
+            gl::StencilOp(gl::KEEP, gl::KEEP, gl::REPLACE);
+            gl::ClearColor(0.1, 0.2, 0.3, 1.0);
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 8);
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindTexture(gl::TEXTURE_2D, 7);
+            gl::ActiveTexture(gl::TEXTURE3);
+            gl::BindVertexArray(3);
+            gl::BindBuffer(gl::ARRAY_BUFFER, 4);
+            gl::BindBuffer(gl::UNIFORM_BUFFER, 5);
+        }
+        let before = fake_gl_state();
+
+        let mut renderer = GlCanvasRenderer::new(GlBackend::Gl3).unwrap();
+        renderer.set_viewport_size(200.0, 100.0, 1.0);
+        renderer.set_restore_state(true);
+        draw_frame(&mut renderer);
+        let mut after = fake_gl_state();
+        // The renderer's own vertex array keeps its element buffer.
+        after.element_buffers.retain(|&vao, _| vao == 3);
+        assert_eq!(after, before);
+
+        renderer.set_restore_state(false);
+        draw_frame(&mut renderer);
+        let after = fake_gl_state();
+        assert_eq!(after.integers[&gl::CURRENT_PROGRAM], 0);
+        assert_eq!(after.integers[&gl::BLEND_DST_RGB], gl::ZERO as GLint);
+        assert_eq!(after.integers[&gl::UNPACK_ALIGNMENT], 4);
+        assert_eq!(after.integers[&gl::STENCIL_FUNC], gl::ALWAYS as GLint);
+        assert!(!after.enabled[&gl::BLEND] && !after.enabled[&gl::STENCIL_TEST] && !after.enabled[&gl::DEPTH_TEST]);
+    }
+
+    #[test]
+    fn test_composite_blend_func() {
+        let cases = [
+            (CompositeOp::SourceOver, gl::ONE, gl::ONE_MINUS_SRC_ALPHA),
+            (CompositeOp::SourceIn, gl::DST_ALPHA, gl::ZERO),
+            (CompositeOp::SourceOut, gl::ONE_MINUS_DST_ALPHA, gl::ZERO),
+            (CompositeOp::Atop, gl::DST_ALPHA, gl::ONE_MINUS_SRC_ALPHA),
+            (CompositeOp::DestinationOver, gl::ONE_MINUS_DST_ALPHA, gl::ONE),
+            (CompositeOp::DestinationIn, gl::ZERO, gl::SRC_ALPHA),
+            (CompositeOp::DestinationOut, gl::ZERO, gl::ONE_MINUS_SRC_ALPHA),
+            (CompositeOp::DestinationAtop, gl::ONE_MINUS_DST_ALPHA, gl::SRC_ALPHA),
+            (CompositeOp::Lighter, gl::ONE, gl::ONE),
+            (CompositeOp::Copy, gl::ONE, gl::ZERO),
+            (CompositeOp::Xor, gl::ONE_MINUS_DST_ALPHA, gl::ONE_MINUS_SRC_ALPHA),
+        ];
+        for &(op, src, dst) in cases.iter() {
+            let blend = convert_blend_func(&CompositeState::from_op(op));
+            assert!(blend == BlendFunc { src_rgb: src, dst_rgb: dst, src_alpha: src, dst_alpha: dst });
+        }
+
+        let composite = CompositeState {
+            src_rgb: BlendFactor::SrcColor,
+            dst_rgb: BlendFactor::OneMinusDstColor,
+            src_alpha: BlendFactor::SrcAlphaSaturate,
+            dst_alpha: BlendFactor::DstColor,
+            blend_mode: BlendMode::Normal,
+        };
+        let blend = convert_blend_func(&composite);
+        assert!(blend == BlendFunc {
+            src_rgb: gl::SRC_COLOR,
+            dst_rgb: gl::ONE_MINUS_DST_COLOR,
+            src_alpha: gl::SRC_ALPHA_SATURATE,
+            dst_alpha: gl::DST_COLOR,
+        });
+    }
+
+    /// CPU port of `blend_with_backdrop` in shader.frag followed by source-over blending, on
+    /// premultiplied colors.
+    fn blend_reference(mode: BlendMode, dst: [f32; 4], src: [f32; 4]) -> [f32; 4] {
+        let hard_light = |cb: f32, cs: f32| {
+            if cs <= 0.5 {
+                cb * 2.0 * cs
+            } else {
+                let s = 2.0 * cs - 1.0;
+                cb + s - cb * s
+            }
+        };
+        let blend = |cb: f32, cs: f32| match mode {
+            BlendMode::Normal => cs,
+            BlendMode::Multiply => cb * cs,
+            BlendMode::Screen => cb + cs - cb * cs,
+            BlendMode::Overlay => hard_light(cs, cb),
+            BlendMode::Darken => cb.min(cs),
+            BlendMode::Lighten => cb.max(cs),
+            BlendMode::ColorDodge if cb == 0.0 => 0.0,
+            BlendMode::ColorDodge if cs == 1.0 => 1.0,
+            BlendMode::ColorDodge => (cb / (1.0 - cs)).min(1.0),
+            BlendMode::ColorBurn if cb == 1.0 => 1.0,
+            BlendMode::ColorBurn if cs == 0.0 => 0.0,
+            BlendMode::ColorBurn => 1.0 - ((1.0 - cb) / cs).min(1.0),
+            BlendMode::HardLight => hard_light(cb, cs),
+            BlendMode::SoftLight if cs <= 0.5 => cb - (1.0 - 2.0 * cs) * cb * (1.0 - cb),
+            BlendMode::SoftLight => {
+                let d = if cb <= 0.25 { ((16.0 * cb - 12.0) * cb + 4.0) * cb } else { cb.sqrt() };
+                cb + (2.0 * cs - 1.0) * (d - cb)
+            }
+            BlendMode::Difference => (cb - cs).abs(),
+            BlendMode::Exclusion => cb + cs - 2.0 * cb * cs,
+        };
+
+        let mut out = [0.0; 4];
+        for i in 0..3 {
+            let cs = src[i] / src[3];
+            let cb = dst[i] / dst[3];
+            let mixed = src[i] * (1.0 - dst[3]) + src[3] * dst[3] * blend(cb, cs).clamp(0.0, 1.0);
+            out[i] = mixed + dst[i] * (1.0 - src[3]);
+        }
+        out[3] = src[3] + dst[3] * (1.0 - src[3]);
+        out
+    }
+
+    /// The blend modes with their names in the comments of `blend` in shader.frag.
+    const BLEND_MODES: [(BlendMode, &str); 12] = [
+        (BlendMode::Normal, ""),
+        (BlendMode::Multiply, "Multiply"),
+        (BlendMode::Screen, "Screen"),
+        (BlendMode::Overlay, "Overlay"),
+        (BlendMode::Darken, "Darken"),
+        (BlendMode::Lighten, "Lighten"),
+        (BlendMode::ColorDodge, "Color dodge"),
+        (BlendMode::ColorBurn, "Color burn"),
+        (BlendMode::HardLight, "Hard light"),
+        (BlendMode::SoftLight, "Soft light"),
+        (BlendMode::Difference, "Difference"),
+        (BlendMode::Exclusion, "Exclusion"),
+    ];
+
+    #[test]
+    fn test_blend_modes() {
+        // Half transparent colors, premultiplied, with the results of the W3C compositing formulas.
+        let dst = [0.4, 0.2, 0.1, 0.5];
+        let src = [0.25, 0.3, 0.45, 0.5];
+        let expected = [
+            [0.45, 0.4, 0.5, 0.75],
+            [0.425, 0.31, 0.32, 0.75],
+            [0.55, 0.44, 0.505, 0.75],
+            [0.525, 0.37, 0.365, 0.75],
+            [0.45, 0.35, 0.325, 0.75],
+            [0.525, 0.4, 0.5, 0.75],
+            [0.575, 0.5, 0.525, 0.75],
+            [0.475, 0.25, 0.302_778, 0.75],
+            [0.525, 0.38, 0.485, 0.75],
+            [0.525, 0.361_623, 0.374_6, 0.75],
+            [0.4, 0.3, 0.45, 0.75],
+            [0.45, 0.38, 0.46, 0.75],
+        ];
+        for (&(mode, name), expected) in BLEND_MODES.iter().zip(expected.iter()) {
+            let result = blend_reference(mode, dst, src);
+            assert!(result.iter().zip(expected.iter()).all(|(a, b)| (a - b).abs() < 1e-5), "{}: {:?}", name, result);
+        }
+
+        // An opaque source over an opaque backdrop is just the blend function.
+        let result = blend_reference(BlendMode::Multiply, [0.5, 1.0, 0.0, 1.0], [0.5, 0.5, 0.5, 1.0]);
+        assert_eq!(result, [0.25, 0.5, 0.0, 1.0]);
+
+        // The shader numbers the modes like `BlendMode`.
+        for &(mode, name) in BLEND_MODES[1..].iter() {
+            let branch = format!("blend_mode == {}) {{ // {}\n", mode as u32, name);
+            assert!(FRAGMENT_SHADER.contains(&branch), "{}", branch);
+        }
+    }
+
+    #[test]
+    fn test_blend_mode_draws() {
+        let _lock = GL_TEST.lock().unwrap();
+        load_fake_gl();
+
+        let mut renderer = GlCanvasRenderer::new(GlBackend::Gl3).unwrap();
+        renderer.set_viewport_size(200.0, 100.0, 1.0);
+        let mut canvas = Canvas::new();
+        for &(mode, _) in BLEND_MODES.iter() {
+            canvas.set_blend_mode(mode);
+            canvas.begin_path();
+            canvas.rect(10.0, 10.0, 50.0, 20.0);
+            canvas.fill(&mut renderer);
+        }
+        for (i, &(mode, _)) in BLEND_MODES.iter().enumerate() {
+            assert!(renderer.draw_calls[i].blend_mode == mode);
+            assert_eq!(draw_call_uniforms(&mut renderer, i).blend_mode, mode as u32 as f32);
+        }
+
+        renderer.flush();
+        // Every mode but Normal copies the covered pixels, with the fringe, in window coordinates.
+        let copies = FAKE_GL.lock().unwrap().as_mut().unwrap().backdrop_copies.split_off(0);
+        assert_eq!(copies, vec![[9, 69, 52, 22]; 11]);
+    }
+
+    #[test]
+    fn test_scissor() {
+        let _lock = GL_TEST.lock().unwrap();
+        load_fake_gl();
+
+        let mut renderer = GlCanvasRenderer::new(GlBackend::Gl3).unwrap();
+        renderer.set_viewport_size(200.0, 100.0, 1.0);
+        let mut canvas = Canvas::new();
+        canvas.state.scissor = Scissor {
+            transform: Transform::scale(2.0, 1.0) * Transform::translate(50.0, 40.0),
+            extent: [20.0, 10.0],
+        };
+        canvas.begin_path();
+        canvas.rect(0.0, 0.0, 200.0, 100.0);
+        canvas.fill(&mut renderer);
+
+        let uniforms = draw_call_uniforms(&mut renderer, 0);
+        assert_eq!(uniforms.scissor_mat[..6], [0.5, 0.0, 0.0, 0.0, 0.0, 1.0]);
+        assert_eq!(uniforms.scissor_mat[8..10], [-25.0, -40.0]);
+        assert_eq!(uniforms.scissor_ext, [20.0, 10.0]);
+        assert_eq!(uniforms.scissor_scale, [2.0, 1.0]);
+        renderer.flush();
+    }
+
+    #[test]
+    fn test_pixel_bounds() {
+        let verts = [[10.25, 20.0], [30.0, 25.5], [-5.0, 22.0], [12.0, 110.0]]
+            .iter()
+            .map(|&pos| ShaderVertex { pos, tex_coord: [0.0; 2] })
+            .collect::<Vec<_>>();
+        let paths = [BufferRef { fill_offset: 0, fill_count: 2, stroke_offset: 2, stroke_count: 1 }];
+        let mut draw_call = DrawCall {
+            ty: DrawCallType::Fill,
+            path_offset: 0,
+            path_count: 1,
+            triangle_offset: 0,
+            triangle_count: 0,
+            uniform_offset: 0,
+            image: 0,
+            blend_func: convert_blend_func(&CompositeState::from_op(CompositeOp::SourceOver)),
+            blend_mode: BlendMode::Multiply,
+            clip: None,
+            indices: None,
+        };
+
+        // Clamped on the left, flipped to start at the bottom and scaled to pixels.
+        assert_eq!(draw_call.pixel_bounds(&paths, &verts, 2.0, [200, 100]), Some([0, 49, 60, 11]));
+
+        // The vertex of the cover quad is off the bottom of the framebuffer.
+        draw_call.triangle_offset = 3;
+        draw_call.triangle_count = 1;
+        assert_eq!(draw_call.pixel_bounds(&paths, &verts, 1.0, [200, 100]), Some([0, 0, 30, 80]));
+        assert_eq!(draw_call.pixel_bounds(&paths, &verts, 1.0, [5, 5]), None);
+    }
+
+    #[test]
+    fn test_clip_save_restore() {
+        let _lock = GL_TEST.lock().unwrap();
+        load_fake_gl();
+
+        let mut renderer = GlCanvasRenderer::new(GlBackend::Gl3).unwrap();
+        renderer.set_viewport_size(200.0, 100.0, 1.0);
+        let mut canvas = Canvas::new();
+        let fill = |canvas: &mut Canvas, renderer: &mut GlCanvasRenderer| {
+            canvas.begin_path();
+            canvas.rect(0.0, 0.0, 200.0, 100.0);
+            canvas.fill(renderer);
+        };
+
+        fill(&mut canvas, &mut renderer);
+        canvas.save();
+        canvas.begin_path();
+        canvas.rect(10.0, 10.0, 100.0, 50.0);
+        canvas.clip(&mut renderer);
+        fill(&mut canvas, &mut renderer);
+        canvas.save();
+        canvas.begin_path();
+        canvas.circle(50.0, 50.0, 20.0);
+        canvas.clip(&mut renderer);
+        fill(&mut canvas, &mut renderer);
+        canvas.restore();
+        fill(&mut canvas, &mut renderer);
+        canvas.restore();
+        fill(&mut canvas, &mut renderer);
+
+        let clips = renderer.draw_calls.iter().map(|draw_call| draw_call.clip).collect::<Vec<_>>();
+        assert_eq!(clips, [None, Some(0), Some(1), Some(0), None]);
+        assert_eq!(renderer.clip_paths[1].parent, Some(0));
+
+        // The nested clip only intersects its own path, restoring to the outer clip rebuilds it
+        // and the last fill clears the clip bit. Each fill adds two passes of its own.
+        let stats = renderer.flush();
+        assert_eq!(stats.stencil_passes, 3 * (1 + 1 + 1) + 2 * 5);
+        let fake = FAKE_GL.lock().unwrap().clone().unwrap();
+        assert_eq!(fake.stencil_clears, 2);
+        assert!(!fake.enabled[&gl::STENCIL_TEST]);
+
+        // A clip made after restoring to the root starts a new chain.
+        canvas.begin_path();
+        canvas.rect(0.0, 0.0, 10.0, 10.0);
+        canvas.clip(&mut renderer);
+        fill(&mut canvas, &mut renderer);
+        assert_eq!(renderer.draw_calls[0].clip, Some(0));
+        assert_eq!(renderer.clip_paths[0].parent, None);
+    }
+
+    #[test]
+    fn test_layers() {
+        let _lock = GL_TEST.lock().unwrap();
+        load_fake_gl();
+
+        let mut renderer = GlCanvasRenderer::new(GlBackend::Gl3).unwrap();
+        renderer.set_viewport_size(200.0, 100.0, 1.0);
+        let mut canvas = Canvas::new();
+        let fill = |canvas: &mut Canvas, renderer: &mut GlCanvasRenderer| {
+            canvas.begin_path();
+            canvas.rect(10.0, 10.0, 50.0, 50.0);
+            canvas.fill(renderer);
+        };
+
+        fill(&mut canvas, &mut renderer);
+        canvas.push_layer(&mut renderer, 0.5, BlendMode::Normal);
+        fill(&mut canvas, &mut renderer);
+        canvas.push_layer(&mut renderer, 2.0, BlendMode::Multiply);
+        fill(&mut canvas, &mut renderer);
+        canvas.pop_layer(&mut renderer);
+        canvas.pop_layer(&mut renderer);
+        // Unbalanced, so it's ignored.
+        canvas.pop_layer(&mut renderer);
+        fill(&mut canvas, &mut renderer);
+
+        use DrawCallType::*;
+        let types = renderer.draw_calls.iter().map(|draw_call| draw_call.ty).collect::<Vec<_>>();
+        assert_eq!(types, [Fill, PushLayer, Fill, PushLayer, Fill, PopLayer, PopLayer, Fill]);
+        // The inner layer is popped first, with its opacity clamped.
+        assert_eq!(draw_call_uniforms(&mut renderer, 5).inner_col, [1.0; 4]);
+        assert!(renderer.draw_calls[5].blend_mode == BlendMode::Multiply);
+        assert_eq!(draw_call_uniforms(&mut renderer, 6).inner_col, [0.5; 4]);
+
+        let stats = renderer.flush();
+        assert_eq!(stats.layer_calls, 2);
+        assert_eq!(renderer.layers.len(), 2);
+        let (outer, inner) = (renderer.layers[0].fbo, renderer.layers[1].fbo);
+        let binds = FAKE_GL.lock().unwrap().as_mut().unwrap().framebuffer_binds.split_off(0);
+        // Allocating the layers restores the target, then each layer is drawn into in turn.
+        assert_eq!(&binds[binds.len() - 4..], [outer, inner, outer, 0]);
+
+        // A layer left open is composited by the flush.
+        canvas.push_layer(&mut renderer, 0.5, BlendMode::Normal);
+        fill(&mut canvas, &mut renderer);
+        renderer.flush();
+        let binds = FAKE_GL.lock().unwrap().as_mut().unwrap().framebuffer_binds.split_off(0);
+        assert_eq!(binds, [outer, 0]);
+    }
+
+    #[test]
+    fn test_framebuffer_image_after_drop() {
+        let _lock = GL_TEST.lock().unwrap();
+        load_fake_gl();
+
+        let mut renderer = GlCanvasRenderer::new(GlBackend::Gl3).unwrap();
+        renderer.set_viewport_size(200.0, 100.0, 1.0);
+        let framebuffer = renderer.create_framebuffer(64, 64);
+        let texture = framebuffer.texture;
+        let image = renderer.framebuffer_image(&framebuffer);
+        assert_eq!(renderer.framebuffer_image(&framebuffer), image);
+        drop(framebuffer);
+
+        // The new framebuffer gets the deleted texture name, but not the old handle.
+        let framebuffer = renderer.create_framebuffer(64, 64);
+        assert_eq!(framebuffer.texture, texture);
+        let new_image = renderer.framebuffer_image(&framebuffer);
+        assert_ne!(new_image, image);
+        assert!(find_image(&renderer.images, image).is_none());
+        assert_eq!(find_image(&renderer.images, new_image).unwrap().texture, texture);
+        assert_eq!(renderer.images.len(), 1);
+
+        let mut canvas = Canvas::new();
+        for &image in [image, new_image].iter() {
+            canvas.begin_path();
+            canvas.rect(0.0, 0.0, 64.0, 64.0);
+            canvas.set_fill_paint(&canvas.image_pattern(0.0, 0.0, 64.0, 64.0, 0.0, image, 1.0));
+            canvas.fill(&mut renderer);
+        }
+        assert_eq!(renderer.draw_calls.len(), 1);
+        assert_eq!(renderer.draw_calls[0].image, texture);
+    }
+
+    #[test]
+    fn test_linear_srgb() {
+        let _lock = GL_TEST.lock().unwrap();
+        load_fake_gl();
+
+        let mut renderer = GlCanvasRenderer::new(GlBackend::Gl3).unwrap();
+        renderer.set_viewport_size(200.0, 100.0, 1.0);
+        let mut canvas = Canvas::new();
+        let mut draw_layer = |renderer: &mut GlCanvasRenderer| {
+            canvas.push_layer(renderer, 0.5, BlendMode::Normal);
+            canvas.begin_path();
+            canvas.rect(10.0, 10.0, 50.0, 50.0);
+            canvas.fill(renderer);
+            canvas.pop_layer(renderer);
+            renderer.flush();
+            let texture = renderer.layers[0].texture as GLint;
+            with_gl(|fake| fake.texture_formats[&texture])
+        };
+        let framebuffer_format = |renderer: &mut GlCanvasRenderer| {
+            let texture = renderer.create_framebuffer(16, 16).texture as GLint;
+            with_gl(|fake| fake.texture_formats[&texture])
+        };
+
+        assert_eq!(draw_layer(&mut renderer), gl::RGBA8 as GLint);
+        assert_eq!(framebuffer_format(&mut renderer), gl::RGBA8 as GLint);
+        renderer.set_color_space(ColorSpace::LinearSrgb);
+        assert_eq!(draw_layer(&mut renderer), gl::SRGB8_ALPHA8 as GLint);
+        assert_eq!(framebuffer_format(&mut renderer), gl::SRGB8_ALPHA8 as GLint);
+
+        // Colors are decoded to linear light and premultiplied.
+        let color = convert_color([0.5, 1.0, 0.0, 0.5], ColorSpace::LinearSrgb);
+        assert!((color[0] - 0.214_041 * 0.5).abs() < 1e-5);
+        assert_eq!(&color[1..], [0.5, 0.0, 0.5]);
+        assert_eq!(convert_color([0.5, 1.0, 0.0, 0.5], ColorSpace::Srgb), [0.25, 0.5, 0.0, 0.5]);
+
+        // The midpoint of a black to white gradient is half the light in linear sRGB.
+        let black = [0.0, 0.0, 0.0, 1.0];
+        let white = [1.0; 4];
+        let mid = interpolate_color(black, white, 0.5, ColorSpace::LinearSrgb, GradientInterpolation::ColorSpace);
+        assert_eq!(mid, [0.5, 0.5, 0.5, 1.0]);
+        // OKLab puts it at the perceptual middle gray instead, encoded as sRGB here.
+        let mid = interpolate_color(black, white, 0.5, ColorSpace::Srgb, GradientInterpolation::Oklab);
+        assert!(mid[..3].iter().all(|&c| (c - 0.3885).abs() < 1e-3), "{:?}", mid);
+    }
+
+    #[test]
+    fn test_uniform_block_range() {
+        let _lock = GL_TEST.lock().unwrap();
+        load_fake_gl();
+
+        let mut renderer = GlCanvasRenderer::new(GlBackend::Gl3).unwrap();
+        renderer.set_viewport_size(200.0, 100.0, 1.0);
+        // Spaced by the fake 256 byte offset alignment.
+        assert_eq!(renderer.uniform_buffer.uniform_size, 256);
+        draw_frame(&mut renderer);
+        let (offset, size) = with_gl(|fake| fake.uniform_range);
+        assert_eq!(size, 208);
+        assert_eq!(offset % 256, 0);
+    }
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(parse_version("4.6.0 NVIDIA 535.54.03"), Some((false, 4, 6)));
+        assert_eq!(parse_version("3.3 (Core Profile) Mesa 23.0.4"), Some((false, 3, 3)));
+        assert_eq!(parse_version("OpenGL ES 3.0 (WebGL 2.0)"), Some((true, 3, 0)));
+        assert_eq!(parse_version("OpenGL ES-CM 1.1"), Some((true, 1, 1)));
+        assert_eq!(parse_version(""), None);
+    }
+
+    #[test]
+    fn test_supports_backend() {
+        use GlBackend::*;
+        assert!(supports_backend(Gl2, false, (2, 1), false, true));
+        assert!(!supports_backend(Gl2, false, (2, 1), false, false));
+        assert!(supports_backend(Gl2, false, (3, 0), false, false));
+        assert!(supports_backend(Gl2, false, (4, 6), false, false));
+        assert!(!supports_backend(Gl2, false, (4, 6), true, false));
+        assert!(!supports_backend(Gl2, true, (3, 0), false, true));
+
+        assert!(supports_backend(Gl3, false, (3, 3), true, false));
+        assert!(!supports_backend(Gl3, false, (3, 2), true, false));
+        assert!(!supports_backend(Gl3, true, (3, 2), false, false));
+
+        assert!(supports_backend(Gles2, true, (2, 0), false, false));
+        assert!(supports_backend(Gles2, false, (4, 6), false, false));
+        assert!(!supports_backend(Gles2, false, (4, 6), true, false));
+        assert!(!supports_backend(Gles2, false, (3, 3), false, false));
+
+        assert!(supports_backend(Gles3, true, (3, 2), false, false));
+        assert!(supports_backend(Gles3, false, (4, 3), true, false));
+        assert!(!supports_backend(Gles3, true, (2, 0), false, false));
+    }
+
+    #[test]
+    fn test_strip_indices() {
+        let mut indices = Vec::new();
+        add_strip_indices(&mut indices, 10, 5);
+        assert_eq!(indices, [10, 11, 12, 12, 11, 13, 12, 13, 14]);
+
+        indices.clear();
+        add_fan_indices(&mut indices, 10, 4);
+        assert_eq!(indices, [10, 11, 12, 10, 12, 13]);
+
+        indices.clear();
+        add_strip_indices(&mut indices, 0, 1);
+        add_fan_indices(&mut indices, 0, 2);
+        assert!(indices.is_empty());
+    }
+
+    #[test]
+    fn test_batch_draw_calls() {
+        let _lock = GL_TEST.lock().unwrap();
+        load_fake_gl();
+
+        let mut renderer = GlCanvasRenderer::new(GlBackend::Gl3).unwrap();
+        renderer.set_viewport_size(200.0, 100.0, 1.0);
+        let mut canvas = Canvas::new();
+        canvas.set_stroke_color(Color::rgb(0, 0, 255));
+        for i in 0..3 {
+            canvas.begin_path();
+            let y = 10.0 + i as f32 * 20.0;
+            canvas.move_to(10.0, y).line_to(100.0, y);
+            canvas.move_to(120.0, y).line_to(170.0, y + 10.0);
+            canvas.stroke(&mut renderer);
+        }
+        let stats = renderer.flush();
+
+        // Three strokes of two paths each become a single draw.
+        assert_eq!(stats.stroke_calls, 3);
+        assert_eq!(stats.paths, 6);
+        assert_eq!(stats.draws_saved, 5);
+        assert_eq!(FAKE_GL.lock().unwrap().as_ref().unwrap().draws, 1);
+
+        // The merged call covers the paths of all three, for the debug overlays.
+        for i in 0..3 {
+            canvas.begin_path();
+            canvas.move_to(10.0, 10.0 * i as f32).line_to(100.0, 10.0 * i as f32);
+            canvas.move_to(120.0, 10.0).line_to(170.0, 20.0);
+            canvas.stroke(&mut renderer);
+        }
+        renderer.batch_draw_calls();
+        assert_eq!(renderer.draw_calls.len(), 1);
+        assert_eq!((renderer.draw_calls[0].path_offset, renderer.draw_calls[0].path_count), (0, 6));
+    }
+}
@@ -1,29 +1,42 @@
-#version 330 core
-
 #define EDGE_AA 1
 
-layout(std140) uniform u_frag {
-    mat3 scissor_mat;
-    mat3 paint_mat;
-    vec4 inner_col;
-    vec4 outer_col;
-    vec2 scissor_ext;
-    vec2 scissor_scale;
-    vec2 extent;
-    float radius;
-    float feather;
-    float stroke_mult;
-    float stroke_thr;
-    int tex_type;
-    int type;
+// The fragment uniforms are packed into vec4 so that the same layout works as a std140 block
+// and as a plain uniform array on versions without uniform buffers.
+#ifdef USE_UNIFORMBUFFER
+layout(std140) uniform frag {
+    vec4 u_frag[UNIFORMARRAY_SIZE];
 };
+#else
+uniform vec4 u_frag[UNIFORMARRAY_SIZE];
+#endif
 
-uniform sampler2D u_tex;
+#define scissor_mat mat3(u_frag[0].xyz, u_frag[1].xyz, u_frag[2].xyz)
+#define paint_mat mat3(u_frag[3].xyz, u_frag[4].xyz, u_frag[5].xyz)
+#define inner_col u_frag[6]
+#define outer_col u_frag[7]
+#define scissor_ext u_frag[8].xy
+#define scissor_scale u_frag[8].zw
+#define extent u_frag[9].xy
+#define radius u_frag[9].z
+#define feather u_frag[9].w
+#define stroke_mult u_frag[10].x
+#define stroke_thr u_frag[10].y
+#define tex_type int(u_frag[10].z)
+#define type int(u_frag[10].w)
+#define blend_mode int(u_frag[11].x)
+#define ramp_row u_frag[11].y
+#define sweep_angles u_frag[11].zw
+#define spread_mode int(u_frag[12].x)
 
-in vec2 f_tex_coord;
-in vec2 f_pos;
+uniform sampler2D u_tex;
+uniform sampler2D u_backdrop;
+uniform sampler2D u_ramp;
+// Framebuffer size in pixels and ramp texture size in texels, GLSL 1.x can't query them.
+uniform vec2 u_frame_size;
+uniform vec2 u_ramp_size;
 
-out vec4 out_color;
+VARYING vec2 f_tex_coord;
+VARYING vec2 f_pos;
 
 float sdroundrect(vec2 pt, vec2 ext, float rad) {
     vec2 ext2 = ext - vec2(rad, rad);
@@ -37,6 +50,30 @@ float scissor_mask(vec2 p) {
     return clamp(sc.x, 0.0, 1.0) * clamp(sc.y, 0.0, 1.0);
 }
 
+// Maps a gradient offset into [0, 1], numbered as rdraw::SpreadMode.
+float spread(float d) {
+    if (spread_mode == 1) { // Repeat
+        return fract(d);
+    } else if (spread_mode == 2) { // Reflect
+        return 1.0 - abs(mod(d, 2.0) - 1.0);
+    }
+    return clamp(d, 0.0, 1.0);
+}
+
+vec4 ramp_color(float d) {
+    vec2 size = u_ramp_size;
+    return TEXTURE(u_ramp, vec2((d * (size.x - 1.0) + 0.5) / size.x, (ramp_row + 0.5) / size.y));
+}
+
+// Offset along the sweep from sweep_angles.x to sweep_angles.y, in either direction.
+float sweep_offset(vec2 pt) {
+    const float TWO_PI = 6.28318530718;
+    float span = sweep_angles.y - sweep_angles.x;
+    float dir = span < 0.0 ? -1.0 : 1.0;
+    float a = mod(dir * (atan(pt.y, pt.x) - sweep_angles.x), TWO_PI);
+    return a / max(abs(span), 1e-6);
+}
+
 #ifdef EDGE_AA
 // Stroke - from [0..1] to clipped pyramid, where the slope is 1px.
 float stroke_mask() {
@@ -44,6 +81,82 @@ float stroke_mask() {
 }
 #endif
 
+float blend_color_dodge(float cb, float cs) {
+    if (cb == 0.0) {
+        return 0.0;
+    } else if (cs == 1.0) {
+        return 1.0;
+    }
+    return min(1.0, cb / (1.0 - cs));
+}
+
+float blend_color_burn(float cb, float cs) {
+    if (cb == 1.0) {
+        return 1.0;
+    } else if (cs == 0.0) {
+        return 0.0;
+    }
+    return 1.0 - min(1.0, (1.0 - cb) / cs);
+}
+
+float blend_hard_light(float cb, float cs) {
+    if (cs <= 0.5) {
+        return cb * 2.0 * cs;
+    }
+    float s = 2.0 * cs - 1.0;
+    return cb + s - cb * s;
+}
+
+float blend_soft_light(float cb, float cs) {
+    if (cs <= 0.5) {
+        return cb - (1.0 - 2.0 * cs) * cb * (1.0 - cb);
+    }
+    float d = cb <= 0.25 ? ((16.0 * cb - 12.0) * cb + 4.0) * cb : sqrt(cb);
+    return cb + (2.0 * cs - 1.0) * (d - cb);
+}
+
+// Separable blend modes on non-premultiplied colors, numbered as rdraw::BlendMode.
+vec3 blend(vec3 cb, vec3 cs) {
+    if (blend_mode == 1) { // Multiply
+        return cb * cs;
+    } else if (blend_mode == 2) { // Screen
+        return cb + cs - cb * cs;
+    } else if (blend_mode == 3) { // Overlay
+        return vec3(blend_hard_light(cs.r, cb.r), blend_hard_light(cs.g, cb.g), blend_hard_light(cs.b, cb.b));
+    } else if (blend_mode == 4) { // Darken
+        return min(cb, cs);
+    } else if (blend_mode == 5) { // Lighten
+        return max(cb, cs);
+    } else if (blend_mode == 6) { // Color dodge
+        return vec3(blend_color_dodge(cb.r, cs.r), blend_color_dodge(cb.g, cs.g), blend_color_dodge(cb.b, cs.b));
+    } else if (blend_mode == 7) { // Color burn
+        return vec3(blend_color_burn(cb.r, cs.r), blend_color_burn(cb.g, cs.g), blend_color_burn(cb.b, cs.b));
+    } else if (blend_mode == 8) { // Hard light
+        return vec3(blend_hard_light(cb.r, cs.r), blend_hard_light(cb.g, cs.g), blend_hard_light(cb.b, cs.b));
+    } else if (blend_mode == 9) { // Soft light
+        return vec3(blend_soft_light(cb.r, cs.r), blend_soft_light(cb.g, cs.g), blend_soft_light(cb.b, cs.b));
+    } else if (blend_mode == 10) { // Difference
+        return abs(cb - cs);
+    } else if (blend_mode == 11) { // Exclusion
+        return cb + cs - 2.0 * cb * cs;
+    }
+    return cs;
+}
+
+// Mix the premultiplied source with the backdrop so that the default source-over
+// blending afterwards yields cs * (1 - ab) + cb * (1 - as) + as * ab * B(cb, cs).
+// Other composite operations apply their blend factors to the mixed color instead.
+vec4 blend_with_backdrop(vec4 src) {
+    if (blend_mode == 0 || src.a <= 0.0) {
+        return src;
+    }
+    vec4 dst = TEXTURE(u_backdrop, gl_FragCoord.xy / u_frame_size);
+    vec3 cs = src.rgb / src.a;
+    vec3 cb = dst.a > 0.0 ? dst.rgb / dst.a : vec3(0.0);
+    vec3 rgb = src.rgb * (1.0 - dst.a) + src.a * dst.a * clamp(blend(cb, cs), 0.0, 1.0);
+    return vec4(rgb, src.a);
+}
+
 void main() {
     #ifdef EDGE_AA
         float stroke_alpha = stroke_mask();
@@ -59,15 +172,43 @@ void main() {
 
     if (type == 0) { // Gradient
         vec2 pt = (paint_mat * vec3(f_pos, 1.0)).xy;
-        float d = clamp((sdroundrect(pt, extent, radius) + feather * 0.5) / feather, 0.0, 1.0);
+        float d = spread((sdroundrect(pt, extent, radius) + feather * 0.5) / feather);
         vec4 color = mix(inner_col, outer_col, d);
         color *= stroke_alpha * scissor;
         result = color;
     } else if (type == 1) { // image
+        vec2 pt = (paint_mat * vec3(f_pos, 1.0)).xy / extent;
+        vec4 color = TEXTURE(u_tex, pt);
+        if (tex_type == 1) {
+            color = vec4(color.xyz * color.w, color.w);
+        } else if (tex_type == 2) {
+            color = vec4(color.x);
+        }
+        color *= inner_col;
+        color *= stroke_alpha * scissor;
+        result = color;
     } else if (type == 2) { // Stencil fill
 		result = vec4(1,1,1,1);
     } else if (type == 3) { // Textured tris
+        vec4 color = TEXTURE(u_tex, f_tex_coord);
+        color *= scissor;
+        result = color * inner_col;
+    } else if (type == 4) { // Gradient with a ramp of color stops
+        vec2 pt = (paint_mat * vec3(f_pos, 1.0)).xy;
+        float d = spread((sdroundrect(pt, extent, radius) + feather * 0.5) / feather);
+        vec4 color = ramp_color(d);
+        color *= stroke_alpha * scissor;
+        result = color;
+    } else if (type == 5) { // Sweep gradient
+        vec2 pt = (paint_mat * vec3(f_pos, 1.0)).xy;
+        float d = spread(sweep_offset(pt));
+        vec4 color = ramp_color(d);
+        color *= stroke_alpha * scissor;
+        result = color;
+    } else if (type == 6) { // Debug overlay, tex_type 1 shades the fringes
+        float w = tex_type == 1 ? abs(f_tex_coord.x * 2.0 - 1.0) : 1.0;
+        result = inner_col * w;
     }
 
-    out_color = result;
+    FRAG_COLOR = blend_with_backdrop(result);
 }
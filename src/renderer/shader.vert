@@ -1,12 +1,10 @@
-#version 330 core
-
 uniform vec2 u_view_size;
 
-layout (location = 0) in vec2 v_pos;
-layout (location = 1) in vec2 v_tex_coord;
+ATTRIBUTE vec2 v_pos;
+ATTRIBUTE vec2 v_tex_coord;
 
-out vec2 f_tex_coord;
-out vec2 f_pos;
+VARYING vec2 f_tex_coord;
+VARYING vec2 f_pos;
 
 void main() {
     f_tex_coord = v_tex_coord;